@@ -0,0 +1,141 @@
+use crate::{backend::Backend, error::Result, screen::Style, Config};
+use crossterm::{
+    cursor,
+    event::{self, Event},
+    style::{self, Attribute},
+    terminal::{self, ClearType},
+    QueueableCommand,
+};
+use std::io::{self, Stdout, Write};
+
+/// Draws to standard output, queueing escape sequences until flushed.
+#[derive(Debug)]
+pub struct CrosstermBackend {
+    stdout: Stdout,
+    style: Style, // Style the terminal is left writing in
+}
+
+impl CrosstermBackend {
+    pub fn new() -> Self {
+        Self {
+            stdout: io::stdout(),
+            style: Style::Plain,
+        }
+    }
+
+    fn set_style(&mut self, style: Style) -> Result<()> {
+        if self.style != style {
+            let attribute = match style {
+                Style::Plain => Attribute::NoReverse,
+                Style::Reversed => Attribute::Reverse,
+            };
+            self.stdout.queue(style::SetAttribute(attribute))?;
+            self.style = style;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for CrosstermBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for CrosstermBackend {
+    fn enter(&mut self, config: &Config) -> Result<()> {
+        terminal::enable_raw_mode()?;
+
+        if config.alternate_screen {
+            self.stdout.queue(terminal::EnterAlternateScreen)?;
+        } else {
+            self.stdout.queue(terminal::Clear(ClearType::All))?;
+        }
+
+        if config.line_wrapping {
+            self.stdout.queue(terminal::EnableLineWrap)?;
+        }
+
+        if config.mouse_capture {
+            self.stdout.queue(event::EnableMouseCapture)?;
+        }
+
+        let theme = &config.theme;
+        if let Some(color) = theme.foreground_color {
+            self.stdout.queue(style::SetForegroundColor(color.into()))?;
+        }
+
+        if let Some(color) = theme.background_color {
+            self.stdout.queue(style::SetBackgroundColor(color.into()))?;
+        }
+
+        if let Some(color) = theme.underline_color {
+            self.stdout.queue(style::SetUnderlineColor(color.into()))?;
+        }
+
+        Ok(())
+    }
+
+    fn leave(&mut self, config: &Config) -> Result<()> {
+        if config.alternate_screen {
+            self.stdout.queue(terminal::LeaveAlternateScreen)?;
+        }
+
+        if config.line_wrapping {
+            self.stdout.queue(terminal::DisableLineWrap)?;
+        }
+
+        if config.mouse_capture {
+            self.stdout.queue(event::DisableMouseCapture)?;
+        }
+
+        self.stdout.flush()?;
+        terminal::disable_raw_mode()?;
+
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str, style: Style) -> Result<()> {
+        self.set_style(style)?;
+        self.stdout.queue(style::Print(text))?;
+
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, (column, row): (u16, u16)) -> Result<()> {
+        self.stdout.queue(cursor::MoveTo(column, row))?;
+
+        Ok(())
+    }
+
+    fn show_cursor(&mut self, visible: bool) -> Result<()> {
+        match visible {
+            true => self.stdout.queue(cursor::Show)?,
+            false => self.stdout.queue(cursor::Hide)?,
+        };
+
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.stdout.queue(terminal::Clear(ClearType::All))?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.set_style(Style::Plain)?;
+        self.stdout.flush()?;
+
+        Ok(())
+    }
+
+    fn size(&self) -> Result<(u16, u16)> {
+        Ok(terminal::size()?)
+    }
+
+    fn read_event(&mut self) -> Result<Event> {
+        Ok(event::read()?)
+    }
+}
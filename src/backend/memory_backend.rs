@@ -0,0 +1,140 @@
+use crate::{
+    backend::Backend,
+    error::Result,
+    screen::{Grid, Style},
+    Config,
+};
+use crossterm::event::Event;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    rc::Rc,
+};
+
+/// Draws to a screen held in memory, for driving the editor without a
+/// terminal. Clones share the same screen, so one kept aside can look at
+/// what the editor has drawn.
+#[derive(Debug, Clone)]
+pub struct MemoryBackend {
+    screen: Rc<RefCell<VirtualScreen>>,
+}
+
+#[derive(Debug)]
+struct VirtualScreen {
+    grid: Grid,
+    cursor_visible: bool,
+    events: VecDeque<Event>, // Handed out by `read_event`, oldest first
+}
+
+impl MemoryBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        let screen = VirtualScreen {
+            grid: Grid::new((width as usize, height as usize), false),
+            cursor_visible: true,
+            events: VecDeque::new(),
+        };
+
+        Self {
+            screen: Rc::new(RefCell::new(screen)),
+        }
+    }
+
+    /// Text of every row on screen, without trailing blanks.
+    pub fn lines(&self) -> Vec<String> {
+        let screen = self.screen.borrow();
+        let (width, height) = screen.grid.size();
+
+        (0..height)
+            .map(|row| {
+                let line: String = (0..width)
+                    .filter_map(|column| screen.grid.cell((column, row)))
+                    .map(|cell| cell.symbol.as_str())
+                    .collect();
+                line.trim_end().to_owned()
+            })
+            .collect()
+    }
+
+    pub fn style_at(&self, position: (u16, u16)) -> Option<Style> {
+        let screen = self.screen.borrow();
+        let cell = screen
+            .grid
+            .cell((position.0 as usize, position.1 as usize))?;
+
+        Some(cell.style)
+    }
+
+    pub fn cursor(&self) -> (u16, u16) {
+        let (column, row) = self.screen.borrow().grid.cursor();
+
+        (column as u16, row as u16)
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.screen.borrow().cursor_visible
+    }
+
+    /// Changes the size of the screen, as a terminal window being resized.
+    pub fn resize(&self, width: u16, height: u16) {
+        let mut screen = self.screen.borrow_mut();
+        screen.grid.resize((width as usize, height as usize));
+    }
+
+    pub fn push_event(&self, event: Event) {
+        self.screen.borrow_mut().events.push_back(event);
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn enter(&mut self, _config: &Config) -> Result<()> {
+        Ok(())
+    }
+
+    fn leave(&mut self, _config: &Config) -> Result<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str, style: Style) -> Result<()> {
+        self.screen.borrow_mut().grid.write(text, style);
+
+        Ok(())
+    }
+
+    fn move_cursor(&mut self, (column, row): (u16, u16)) -> Result<()> {
+        let position = (column as usize, row as usize);
+        self.screen.borrow_mut().grid.move_to(position);
+
+        Ok(())
+    }
+
+    fn show_cursor(&mut self, visible: bool) -> Result<()> {
+        self.screen.borrow_mut().cursor_visible = visible;
+
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        self.screen.borrow_mut().grid.clear();
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> Result<(u16, u16)> {
+        let (width, height) = self.screen.borrow().grid.size();
+
+        Ok((width as u16, height as u16))
+    }
+
+    /// Next event pushed, failing once there are none left.
+    fn read_event(&mut self) -> Result<Event> {
+        match self.screen.borrow_mut().events.pop_front() {
+            Some(event) => Ok(event),
+            None => Err(io::Error::new(ErrorKind::UnexpectedEof, "no events left").into()),
+        }
+    }
+}
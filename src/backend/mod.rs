@@ -0,0 +1,38 @@
+//! Where the frames [`Terminal`](crate::Terminal) draws end up, and where the
+//! events driving the editor come from.
+
+mod crossterm_backend;
+mod memory_backend;
+
+use crate::{error::Result, screen::Style, Config};
+use crossterm::event::Event;
+use std::fmt::Debug;
+
+pub use crossterm_backend::CrosstermBackend;
+pub use memory_backend::MemoryBackend;
+
+pub trait Backend: Debug {
+    /// Readies the screen for drawing, as `config` asks.
+    fn enter(&mut self, config: &Config) -> Result<()>;
+
+    /// Returns the screen to how it was before [`Backend::enter`].
+    fn leave(&mut self, config: &Config) -> Result<()>;
+
+    /// Writes `text` at the cursor in `style`, moving the cursor past it.
+    fn write(&mut self, text: &str, style: Style) -> Result<()>;
+
+    fn move_cursor(&mut self, position: (u16, u16)) -> Result<()>;
+
+    fn show_cursor(&mut self, visible: bool) -> Result<()>;
+
+    fn clear(&mut self) -> Result<()>;
+
+    /// Sends whatever has been written since the last flush.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Columns and rows of the screen.
+    fn size(&self) -> Result<(u16, u16)>;
+
+    /// Waits for the next event.
+    fn read_event(&mut self) -> Result<Event>;
+}
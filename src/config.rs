@@ -1,5 +1,8 @@
 use crossterm::style::Color;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -7,25 +10,61 @@ pub struct Config {
     pub alternate_screen: bool,
     pub line_wrapping: bool,
     pub mouse_capture: bool,
+    #[serde(default = "default_tab_width")]
+    pub tab_width: usize,
+    #[serde(default)]
+    pub scroll_off: usize, // Rows kept in view above and below the cursor
+    #[serde(default)]
+    pub backup: bool,
+    #[serde(default)]
+    pub undo_dir: Option<PathBuf>, // Undo history is only persisted when set
 }
 
 impl Config {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         theme: ThemeConfig,
         alternate_screen: bool,
         line_wrapping: bool,
         mouse_capture: bool,
+        tab_width: usize,
+        scroll_off: usize,
+        backup: bool,
+        undo_dir: Option<PathBuf>,
     ) -> Self {
         Self {
             theme,
             alternate_screen,
             line_wrapping,
             mouse_capture,
+            tab_width,
+            scroll_off,
+            backup,
+            undo_dir,
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: ThemeConfig::default(),
+            alternate_screen: true,
+            line_wrapping: false,
+            mouse_capture: true,
+            tab_width: DEFAULT_TAB_WIDTH,
+            scroll_off: 0,
+            backup: false,
+            undo_dir: None,
+        }
+    }
+}
+
+fn default_tab_width() -> usize {
+    DEFAULT_TAB_WIDTH
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct ThemeConfig {
     pub foreground_color: Option<ColorConfig>,
     pub background_color: Option<ColorConfig>,
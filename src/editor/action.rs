@@ -1,10 +1,15 @@
 use crate::{
-    editor::{Mode, Move},
-    error::Result,
-    frame_buffer::{Line, GUTTER_WIDTH},
-    Editor, Span, CHAR_MAP,
+    editor::{history::HistoryNode, Mode, Operator, VisualMode},
+    error::{Error, Result},
+    frame_buffer::Line,
+    line_ending::LineEnding,
+    motion::Motion,
+    register::{Register, RegisterKind},
+    text_object::TextObject,
+    unicode, Editor, Span, CHAR_MAP,
 };
 use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
 pub enum Message {
@@ -12,37 +17,80 @@ pub enum Message {
     Exit,
 }
 
-#[derive(Debug)]
-pub struct HistoryNode {
-    action: Action,
-    position: (usize, usize),
-}
-
-impl Display for HistoryNode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(
-            f,
-            "Action: {}\nPosition: ({}, {})",
-            self.action, self.position.0, self.position.1
-        )
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
 pub enum Action {
     ChangeMode(Mode),
+    Append,
+    AppendLineEnd,
+    InsertFirstNonBlank,
     MoveLeft,
     MoveRight,
     MoveUp,
     MoveDown,
     MoveTo(usize, usize),
+    MoveWordForward,
+    MoveWordBackward,
+    MoveWordEnd,
+    MoveBigWordForward,
+    MoveBigWordBackward,
+    MoveBigWordEnd,
+    MoveLineStart,
+    MoveFirstNonBlank,
+    MoveLineEnd,
+    MoveFirstLine,
+    MoveLastLine,
+    MoveParagraphForward,
+    MoveParagraphBackward,
+    MoveMatchingBracket,
+    FindForward(char),
+    FindBackward(char),
+    TillForward(char),
+    TillBackward(char),
+    JumpToMark(char),
+    JumpToMarkLine(char),
+    RepeatFind,
+    RepeatFindReversed,
+    SearchNext,
+    SearchPrevious,
+    SearchWordForward,
+    SearchWordBackward,
+    Operator(Operator),
+    SelectInner(TextObject),
+    SelectAround(TextObject),
+    SelectRegister(char),
+    SetMark(char),
+    JumpBack,
+    JumpForward,
+    SwapSelectionEnds,
+    BlockInsert,
+    BlockAppend,
+    PutAfter,
+    PutBefore,
+    RepeatChange,
+    RecordMacro(char),
+    StopRecording,
+    PlayMacro(char),
     ScrollUp(usize),
     ScrollDown(usize),
+    ScrollHalfPageUp,
+    ScrollHalfPageDown,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollCursorTop,
+    ScrollCursorCenter,
+    ScrollCursorBottom,
     Write(KeyCode),
     Newline,
     Tab,
     DeleteLast,
     DeleteCurrent,
+    SetLineEnding(LineEnding),
+    Save,
+    Undo,
+    Redo,
+    OpenUndoTree,
+    UndoJump(usize),
+    OpenCommandLine(char),
     Exit,
     None,
 }
@@ -52,19 +100,83 @@ impl Display for Action {
         let message = match self {
             Self::ChangeMode(Mode::Insert) => "Insert Mode",
             Self::ChangeMode(Mode::Normal) => "Normal Mode",
-            Self::ChangeMode(Mode::Visual) => "Visual Mode",
+            Self::ChangeMode(Mode::Visual(VisualMode::Charwise)) => "Visual Mode",
+            Self::ChangeMode(Mode::Visual(VisualMode::Linewise)) => "Visual Line Mode",
+            Self::ChangeMode(Mode::Visual(VisualMode::Blockwise)) => "Visual Block Mode",
+            Self::ChangeMode(Mode::Command) => "Command Mode",
+            Self::ChangeMode(Mode::Confirm) => "Confirm Mode",
+            Self::ChangeMode(Mode::UndoTree) => "Undo Tree Mode",
+            Self::Append => "Append",
+            Self::AppendLineEnd => "Append Line End",
+            Self::InsertFirstNonBlank => "Insert First Non-Blank",
             Self::MoveRight => "Move Right",
             Self::MoveLeft => "Move Left",
             Self::MoveUp => "Move Up",
             Self::MoveDown => "Move Down",
             Self::MoveTo(column, row) => return write!(f, "Move `({column}, {row})`"),
+            Self::MoveWordForward => "Move Word Forward",
+            Self::MoveWordBackward => "Move Word Backward",
+            Self::MoveWordEnd => "Move Word End",
+            Self::MoveBigWordForward => "Move WORD Forward",
+            Self::MoveBigWordBackward => "Move WORD Backward",
+            Self::MoveBigWordEnd => "Move WORD End",
+            Self::MoveLineStart => "Move Line Start",
+            Self::MoveFirstNonBlank => "Move First Non-Blank",
+            Self::MoveLineEnd => "Move Line End",
+            Self::MoveFirstLine => "Move First Line",
+            Self::MoveLastLine => "Move Last Line",
+            Self::MoveParagraphForward => "Move Paragraph Forward",
+            Self::MoveParagraphBackward => "Move Paragraph Backward",
+            Self::MoveMatchingBracket => "Move Matching Bracket",
+            Self::FindForward(c) => return write!(f, "Find Forward `{c}`"),
+            Self::FindBackward(c) => return write!(f, "Find Backward `{c}`"),
+            Self::TillForward(c) => return write!(f, "Till Forward `{c}`"),
+            Self::TillBackward(c) => return write!(f, "Till Backward `{c}`"),
+            Self::JumpToMark(name) => return write!(f, "Jump To Mark `{name}`"),
+            Self::JumpToMarkLine(name) => return write!(f, "Jump To Mark Line `{name}`"),
+            Self::RepeatFind => "Repeat Find",
+            Self::RepeatFindReversed => "Repeat Find Reversed",
+            Self::SearchNext => "Search Next",
+            Self::SearchPrevious => "Search Previous",
+            Self::SearchWordForward => "Search Word Forward",
+            Self::SearchWordBackward => "Search Word Backward",
+            Self::Operator(operator) => return write!(f, "Operator {operator}"),
+            Self::SelectInner(object) => return write!(f, "Select Inner {object}"),
+            Self::SelectAround(object) => return write!(f, "Select Around {object}"),
+            Self::SelectRegister(name) => return write!(f, "Select Register `{name}`"),
+            Self::SetMark(name) => return write!(f, "Set Mark `{name}`"),
+            Self::JumpBack => "Jump Back",
+            Self::JumpForward => "Jump Forward",
+            Self::SwapSelectionEnds => "Swap Selection Ends",
+            Self::BlockInsert => "Block Insert",
+            Self::BlockAppend => "Block Append",
+            Self::PutAfter => "Put After",
+            Self::PutBefore => "Put Before",
+            Self::RepeatChange => "Repeat Change",
+            Self::RecordMacro(name) => return write!(f, "Record Macro `{name}`"),
+            Self::StopRecording => "Stop Recording",
+            Self::PlayMacro(name) => return write!(f, "Play Macro `{name}`"),
             Self::ScrollUp(n) => return write!(f, "Scroll Up {n}"),
             Self::ScrollDown(n) => return write!(f, "Scroll Down {n}"),
+            Self::ScrollHalfPageUp => "Scroll Half Page Up",
+            Self::ScrollHalfPageDown => "Scroll Half Page Down",
+            Self::ScrollPageUp => "Scroll Page Up",
+            Self::ScrollPageDown => "Scroll Page Down",
+            Self::ScrollCursorTop => "Scroll Cursor Top",
+            Self::ScrollCursorCenter => "Scroll Cursor Center",
+            Self::ScrollCursorBottom => "Scroll Cursor Bottom",
             Self::Write(char) => return write!(f, "Write `{char:?}`"),
             Self::Newline => "Newline",
             Self::Tab => "Tab",
             Self::DeleteLast => "Delete Last",
             Self::DeleteCurrent => "Delete Current",
+            Self::SetLineEnding(line_ending) => return write!(f, "Set Line Ending {line_ending}"),
+            Self::Save => "Save",
+            Self::Undo => "Undo",
+            Self::Redo => "Redo",
+            Self::OpenUndoTree => "Open Undo Tree",
+            Self::UndoJump(seq) => return write!(f, "Undo Jump {seq}"),
+            Self::OpenCommandLine(prefix) => return write!(f, "Open Command Line `{prefix}`"),
             Self::Exit => "Exit",
             Self::None => "None",
         };
@@ -73,117 +185,400 @@ impl Display for Action {
     }
 }
 
+impl Action {
+    /// Motion the action moves the cursor by, for those that are motions.
+    pub fn motion(&self) -> Option<Motion> {
+        let motion = match *self {
+            Self::MoveLeft => Motion::Left,
+            Self::MoveRight => Motion::Right,
+            Self::MoveUp => Motion::Up,
+            Self::MoveDown => Motion::Down,
+            Self::MoveWordForward => Motion::WordForward,
+            Self::MoveWordBackward => Motion::WordBackward,
+            Self::MoveWordEnd => Motion::WordEnd,
+            Self::MoveBigWordForward => Motion::BigWordForward,
+            Self::MoveBigWordBackward => Motion::BigWordBackward,
+            Self::MoveBigWordEnd => Motion::BigWordEnd,
+            Self::MoveLineStart => Motion::LineStart,
+            Self::MoveFirstNonBlank => Motion::FirstNonBlank,
+            Self::MoveLineEnd => Motion::LineEnd,
+            Self::MoveFirstLine => Motion::FirstLine,
+            Self::MoveLastLine => Motion::LastLine,
+            Self::MoveParagraphForward => Motion::ParagraphForward,
+            Self::MoveParagraphBackward => Motion::ParagraphBackward,
+            Self::MoveMatchingBracket => Motion::MatchingBracket,
+            Self::FindForward(c) => Motion::FindForward(c),
+            Self::FindBackward(c) => Motion::FindBackward(c),
+            Self::TillForward(c) => Motion::TillForward(c),
+            Self::TillBackward(c) => Motion::TillBackward(c),
+            Self::JumpToMark(name) => Motion::Mark(name),
+            Self::JumpToMarkLine(name) => Motion::MarkLine(name),
+            _ => return None,
+        };
+
+        Some(motion)
+    }
+}
+
 impl Editor {
     pub fn execute(&mut self, action: Action) -> Result<Message> {
+        // Anything but a motion or another operator cancels a pending operator
+        if self.operator.is_some()
+            && action.motion().is_none()
+            && !matches!(
+                action,
+                Action::Operator(_)
+                    | Action::SelectInner(_)
+                    | Action::SelectAround(_)
+                    | Action::RepeatFind
+                    | Action::RepeatFindReversed
+                    | Action::SearchNext
+                    | Action::SearchPrevious
+                    | Action::SearchWordForward
+                    | Action::SearchWordBackward
+                    | Action::OpenCommandLine('/' | '?')
+            )
+        {
+            self.operator = None;
+            self.count = None;
+
+            return Ok(Message::Continue);
+        }
+
+        // Large moves are recorded in the jump list, unless an operator
+        // applies over them
+        let position = *self.buffer.position.borrow();
+        let jump_from = match action {
+            // Marks may lead to another file, so record their own jumps
+            Action::JumpToMark(_) | Action::JumpToMarkLine(_) => None,
+            Action::MoveTo(..)
+            | Action::SearchNext
+            | Action::SearchPrevious
+            | Action::SearchWordForward
+            | Action::SearchWordBackward => Some(position),
+            action if action.motion().is_some_and(|motion| motion.is_jump()) => Some(position),
+            _ => None,
+        }
+        .filter(|_| self.operator.is_none());
+
+        // A command begun from rest in Normal mode may turn out to be a change
+        // that `.` repeats
+        if self.at_rest() {
+            self.changes.clear();
+        }
+        // A search prompt is repeated by the search it ends in
+        if !matches!(
+            action,
+            Action::RepeatChange | Action::OpenCommandLine(_) | Action::None
+        ) {
+            self.changes.record(action, self.count);
+        }
+
         match action {
-            Action::ChangeMode(mode) => self.change_mode(mode),
+            Action::ChangeMode(Mode::Visual(visual)) => self.toggle_visual(visual)?,
+            Action::ChangeMode(mode) => {
+                let previous = *self.mode.borrow();
+                self.change_mode(mode);
+                match previous {
+                    Mode::Visual(_) => self.rerender()?,
+                    Mode::Insert => self.finish_block_insert()?,
+                    _ => {}
+                }
+            }
+            Action::Append | Action::AppendLineEnd | Action::InsertFirstNonBlank => {
+                self.begin_insert(action)?
+            }
+            Action::MoveLeft | Action::MoveRight | Action::MoveUp | Action::MoveDown
+                if self.count.is_some() || self.operator.is_some() =>
+            {
+                // SAFETY: each of these actions is a motion
+                self.move_by(action.motion().unwrap())?
+            }
             Action::MoveLeft => self.move_left()?,
             Action::MoveRight => self.move_right()?,
             Action::MoveUp => self.move_up()?,
             Action::MoveDown => self.move_down()?,
             Action::MoveTo(column, row) => self.move_to((column, row))?,
-            Action::ScrollUp(n) => self.scroll_up(n)?,
-            Action::ScrollDown(n) => self.scroll_down(n)?,
+            Action::JumpToMark(name) | Action::JumpToMarkLine(name) => {
+                // SAFETY: each of these actions is a motion
+                self.jump_to_mark(action.motion().unwrap(), name)?
+            }
+            Action::MoveWordForward
+            | Action::MoveWordBackward
+            | Action::MoveWordEnd
+            | Action::MoveBigWordForward
+            | Action::MoveBigWordBackward
+            | Action::MoveBigWordEnd
+            | Action::MoveLineStart
+            | Action::MoveFirstNonBlank
+            | Action::MoveLineEnd
+            | Action::MoveFirstLine
+            | Action::MoveLastLine
+            | Action::MoveParagraphForward
+            | Action::MoveParagraphBackward
+            | Action::MoveMatchingBracket
+            | Action::FindForward(_)
+            | Action::FindBackward(_)
+            | Action::TillForward(_)
+            | Action::TillBackward(_) => {
+                // SAFETY: each of these actions is a motion
+                self.move_by(action.motion().unwrap())?
+            }
+            Action::RepeatFind => self.repeat_find(false)?,
+            Action::RepeatFindReversed => self.repeat_find(true)?,
+            Action::SearchNext => self.search_next(false)?,
+            Action::SearchPrevious => self.search_next(true)?,
+            Action::SearchWordForward => self.search_word(true)?,
+            Action::SearchWordBackward => self.search_word(false)?,
+            Action::Operator(operator) => self.begin_operator(operator)?,
+            Action::SelectInner(object) => self.select_object(object, false)?,
+            Action::SelectAround(object) => self.select_object(object, true)?,
+            Action::SelectRegister(name) => {
+                // The register and count carry over to the command that follows
+                self.register = Some(name);
+                return Ok(Message::Continue);
+            }
+            Action::SetMark(name) => self.set_mark(name),
+            Action::JumpBack => self.jump_back()?,
+            Action::JumpForward => self.jump_forward()?,
+            Action::SwapSelectionEnds => self.swap_selection_ends()?,
+            Action::BlockInsert => self.begin_block_insert(false)?,
+            Action::BlockAppend => self.begin_block_insert(true)?,
+            Action::PutAfter => self.put(false)?,
+            Action::PutBefore => self.put(true)?,
+            Action::RepeatChange => self.repeat_change()?,
+            Action::RecordMacro(name) => self.record_macro(name),
+            Action::StopRecording => self.stop_recording(),
+            Action::PlayMacro(name) => {
+                if let Message::Exit = self.play_macro(name)? {
+                    return Ok(Message::Exit);
+                }
+            }
+            Action::ScrollUp(n) => self.scroll_view(-(n as isize))?,
+            Action::ScrollDown(n) => self.scroll_view(n as isize)?,
+            Action::ScrollHalfPageUp => self.scroll_half_page(false)?,
+            Action::ScrollHalfPageDown => self.scroll_half_page(true)?,
+            Action::ScrollPageUp => self.scroll_page(false)?,
+            Action::ScrollPageDown => self.scroll_page(true)?,
+            Action::ScrollCursorTop | Action::ScrollCursorCenter | Action::ScrollCursorBottom => {
+                self.scroll_cursor(action)?
+            }
             Action::Write(code) => self.write_char(code)?,
             Action::Newline => self.newline()?,
             Action::Tab => self.tab()?,
             Action::DeleteLast => self.delete_last()?,
             Action::DeleteCurrent => self.delete_current()?,
+            Action::SetLineEnding(line_ending) => self.set_line_ending(line_ending),
+            Action::Save => {
+                if let Err(err) = self.save(None) {
+                    self.set_message(err);
+                }
+            }
+            Action::Undo => self.undo()?,
+            Action::Redo => self.redo()?,
+            Action::OpenUndoTree => self.open_undo_tree()?,
+            Action::UndoJump(seq) => self.undo_jump(seq)?,
+            Action::OpenCommandLine(prefix) => self.open_command_line(prefix),
             Action::Exit => return Ok(Message::Exit),
             Action::None => return Ok(Message::Continue),
         };
+        self.count = None;
+        if self.operator.is_none() {
+            self.register = None;
+        }
+
+        if let Some(from) = jump_from {
+            if *self.buffer.position.borrow() != from {
+                self.buffer.push_jump(from);
+            }
+        }
+
+        // Everything short of an insert session is undone in one step
+        if !matches!(*self.mode.borrow(), Mode::Insert) {
+            let changed = self.buffer.commit();
+            if self.at_rest() {
+                self.changes.finish(changed);
+            }
+        }
 
         let position = *self.buffer.position.borrow();
-        self.history.push(HistoryNode { action, position });
+        self.history.push_action(HistoryNode { action, position });
 
         Ok(Message::Continue)
     }
 
-    fn change_mode(&mut self, mode: Mode) {
-        self.mode.replace(mode);
+    pub(super) fn change_mode(&mut self, mode: Mode) {
+        let previous = self.mode.replace(mode);
+        if let (Mode::Visual(_), false) = (previous, matches!(mode, Mode::Visual(_))) {
+            self.set_visual_marks();
+        }
+    }
+
+    /// Enters Insert mode after the cursor, at the end of the line or before
+    /// its first non-blank, as `a`, `A` and `I` do.
+    fn begin_insert(&mut self, action: Action) -> Result<()> {
+        let (column, row) = *self.buffer.position.borrow();
+        let line_len = self.buffer.line_len(Line::Index(row));
+        let column = match action {
+            Action::Append => (column + 1).min(line_len),
+            Action::AppendLineEnd => line_len,
+            _ => self.buffer.first_non_blank(row),
+        };
+
+        self.move_to((column, row))?;
+        self.change_mode(Mode::Insert);
+
+        Ok(())
     }
 
-    fn move_to(&mut self, (column, row): (usize, usize)) -> Result<()> {
+    /// Moves the cursor to `(column, row)`, scrolling it into view when it
+    /// leaves the view or its context.
+    pub(super) fn move_to(&mut self, (column, row): (usize, usize)) -> Result<()> {
         self.buffer.position.replace((column, row));
+        let top = self.buffer.viewable_rows.start;
+        self.buffer.scroll_to_row(row, self.scroll_off);
+        if self.buffer.viewable_rows.start != top {
+            return self.rerender();
+        }
+
+        let screen_position = self.screen_position((column, row));
 
-        self.terminal.cursor_move_to((column + GUTTER_WIDTH, row))
+        self.terminal.cursor_move_to(screen_position)
     }
 
     fn move_left(&mut self) -> Result<()> {
-        if self.buffer.position.borrow().0 > 0 {
-            self.buffer.position.borrow_mut().0 -= 1;
-
-            return self.terminal.cursor_move(Move::Left(1));
+        let (column, row) = *self.buffer.position.borrow();
+        if column > 0 {
+            return self.move_to((column - 1, row));
         }
 
-        if self.buffer.position.borrow().1 == 0 {
+        if row == 0 {
+            self.failed = true;
             return Ok(());
         }
 
-        let row = self.buffer.position.borrow().1;
-        match self.buffer.get(Line::Previous) {
-            Some(line) => self.move_to((line.len(), row - 1)),
-            None => self.move_to((0, row - 1)),
-        }
+        let column = self.buffer.line_len(Line::Previous);
+        self.move_to((column, row - 1))
     }
 
     fn move_right(&mut self) -> Result<()> {
-        if let Some(line) = self.buffer.get(Line::Current) {
-            if self.buffer.position.borrow().0 < line.len() {
-                self.buffer.position.borrow_mut().0 += 1;
-                self.terminal.cursor_move(Move::Right(1))?;
+        let (column, row) = *self.buffer.position.borrow();
+        if column < self.buffer.line_len(Line::Current) {
+            return self.move_to((column + 1, row));
+        }
 
-                return Ok(());
-            }
+        if row + 1 >= self.buffer.len() {
+            self.failed = true;
+            return Ok(());
         }
 
-        let row = self.buffer.position.borrow().1;
         self.move_to((0, row + 1))
     }
 
     fn move_up(&mut self) -> Result<()> {
-        if self.buffer.position.borrow().1 == 0 {
+        let (column, row) = *self.buffer.position.borrow();
+        if row == 0 {
+            self.failed = true;
             return Ok(());
         }
 
-        let row = self.buffer.position.borrow().1 - 1;
-        let column = match self.buffer.get(Line::Next) {
-            Some(line) => line.len(),
-            None => 0,
-        };
-
-        self.move_to((column, row))
+        let column = column.min(self.buffer.line_len(Line::Previous));
+        self.move_to((column, row - 1))
     }
 
     fn move_down(&mut self) -> Result<()> {
-        let row = self.buffer.position.borrow().1 + 1;
-        let column = match self.buffer.get(Line::Next) {
-            Some(line) => line.len(),
-            None => 0,
-        };
+        let (column, row) = *self.buffer.position.borrow();
+        if row + 1 >= self.buffer.len() {
+            self.failed = true;
+            return Ok(());
+        }
+
+        let column = column.min(self.buffer.line_len(Line::Next));
 
-        self.move_to((column, row))
+        self.move_to((column, row + 1))
     }
 
-    // TODO: integrate with buffers
-    fn scroll_up(&mut self, n: usize) -> Result<()> {
-        self.terminal.scroll_down(n as u16)
+    /// Moves the cursor by `motion`, leaving it in place when the motion
+    /// fails.
+    pub(super) fn move_by(&mut self, motion: Motion) -> Result<()> {
+        if motion.is_find() {
+            self.last_find = Some(motion);
+        }
+
+        if self.operate_over(motion)? {
+            return Ok(());
+        }
+
+        let position = *self.buffer.position.borrow();
+        let count = self.count.take();
+        match self.buffer.motion_target(motion, position, count) {
+            Some(target) => self.move_to(target),
+            None => {
+                self.failed = true;
+                Ok(())
+            }
+        }
     }
 
-    // TODO: integrate with buffers
-    fn scroll_down(&mut self, n: usize) -> Result<()> {
-        self.terminal.scroll_up(n as u16)
+    /// Repeats the last `f`, `t`, `F` or `T`, in the opposite direction when
+    /// `reversed`.
+    fn repeat_find(&mut self, reversed: bool) -> Result<()> {
+        let motion = match (self.last_find, reversed) {
+            (Some(motion), false) => motion,
+            (Some(motion), true) => motion.reversed().unwrap_or(motion),
+            (None, _) => {
+                self.failed = true;
+                return Ok(());
+            }
+        };
+
+        if self.operate_over(motion)? {
+            return Ok(());
+        }
+
+        // A repeated till skips over the character it stopped in front of
+        let position = *self.buffer.position.borrow();
+        let count = self.count.take();
+        let target = match motion {
+            Motion::TillForward(_) | Motion::TillBackward(_) => self
+                .buffer
+                .motion_target(motion, position, count)
+                .or_else(|| {
+                    let count = count.unwrap_or(1) + 1;
+                    self.buffer.motion_target(motion, position, Some(count))
+                }),
+            motion => self.buffer.motion_target(motion, position, count),
+        };
+
+        match target {
+            Some(target) => self.move_to(target),
+            None => {
+                self.failed = true;
+                Ok(())
+            }
+        }
     }
 
     fn write_char(&mut self, keycode: KeyCode) -> Result<()> {
         if let Some(value) = CHAR_MAP.get(&keycode) {
-            let column = self.buffer.position.borrow().0;
+            let (column, row) = *self.buffer.position.borrow();
+            let byte = match self.buffer.get(Line::Current) {
+                Some(line) => {
+                    let padding = column.saturating_sub(unicode::grapheme_len(&line));
+                    unicode::grapheme_to_byte(&line, column) + padding
+                }
+                None => column,
+            };
             self.buffer.line_insert(Line::Current, column, *value);
 
+            // A combining character joins the preceding grapheme, so the
+            // cursor only advances past whichever cluster now holds it
             // SAFETY: if the line did not exist before, we've just created it
             let current_line = self.buffer.get(Line::Current).unwrap();
-            self.terminal.write(&current_line[column..])?;
-            self.buffer.position.borrow_mut().0 += 1;
+            let start = unicode::byte_to_grapheme(&current_line, byte);
+            let end = unicode::byte_to_grapheme(&current_line, byte + value.len_utf8());
+            self.redraw_line_from(start)?;
+            self.move_to((end, row))?;
         };
 
         Ok(())
@@ -197,7 +592,7 @@ impl Editor {
         // moving cursor to the start of the next line
         // SAFETY: we ensure that there is data present prior to each unwrap
         if let Some(data) = self.buffer.get(Line::Current) {
-            let line_len = data.len();
+            let line_len = unicode::grapheme_len(&data);
             if column < line_len {
                 // remove remainder of the line
                 let line_slice = self
@@ -249,9 +644,7 @@ impl Editor {
     }
 
     fn tab(&mut self) -> Result<()> {
-        self.buffer.position.borrow_mut().0 += 4;
-
-        self.terminal.write('\t')
+        self.write_char(KeyCode::Tab)
     }
 
     fn delete_last(&mut self) -> Result<()> {
@@ -261,19 +654,201 @@ impl Editor {
     }
 
     fn delete_current(&mut self) -> Result<()> {
-        let column = self.buffer.position.borrow().0;
-        self.buffer.line_remove(Line::Current, column);
+        let position = *self.buffer.position.borrow();
+        let span = Span {
+            start: position.0,
+            end: position.0 + self.count.take().unwrap_or(1),
+        };
+        if let Some(removed) = self.buffer.line_remove_span(Line::Current, span) {
+            let register = self.register.take();
+            self.store_register(
+                register,
+                Register::new(removed, RegisterKind::Charwise),
+                false,
+            );
+            self.redraw_line_from(position.0)?;
+            self.move_to(position)?;
+        }
+
+        Ok(())
+    }
+
+    /// Puts the selected register `count` times after the cursor, or before
+    /// it when `before` is set. Lines go below or above the cursor's line.
+    fn put(&mut self, before: bool) -> Result<()> {
+        let name = self.register.take();
+        let register = match self.read_register(name) {
+            Some(register) => register,
+            None => {
+                self.set_message(Error::EmptyRegister(name.unwrap_or('"')));
+                self.failed = true;
+                return Ok(());
+            }
+        };
+        let count = self.count.take().unwrap_or(1);
+        let (column, row) = *self.buffer.position.borrow();
+
+        let position = match register.kind {
+            RegisterKind::Charwise => {
+                // Text put into an empty buffer still needs a line to go in
+                if self.buffer.is_empty() {
+                    self.buffer.append("");
+                }
+
+                let column = match before {
+                    true => column,
+                    false => (column + 1).min(self.buffer.line_len(Line::Current)),
+                };
+                let index = self.buffer.char_index((column, row));
+                let text = register.text.repeat(count);
+                self.buffer.insert_chars(index, &text);
+
+                // The cursor ends on the last character put, unless that
+                // spans lines
+                match text.contains('\n') {
+                    true => (column, row),
+                    false => self.buffer.char_position(index + text.chars().count() - 1),
+                }
+            }
+            RegisterKind::Linewise => {
+                let row = match before {
+                    true => row,
+                    false => (row + 1).min(self.buffer.len()),
+                };
+                let index = self.buffer.rows_chars(&(row..row)).start;
+                self.buffer
+                    .insert_chars(index, &register.text.repeat(count));
+
+                (self.buffer.first_non_blank(row), row)
+            }
+            RegisterKind::Blockwise => {
+                let column = match before || self.buffer.line_is_empty(Line::Current) {
+                    true => column,
+                    false => column + 1,
+                };
+                for (i, line) in register.text.split('\n').enumerate() {
+                    let line_len = self.buffer.line_len(Line::Index(row + i));
+                    if column > line_len && line.is_empty() {
+                        continue;
+                    }
+
+                    self.buffer
+                        .line_insert_str(Line::Index(row + i), column, &line.repeat(count));
+                }
+
+                (column, row)
+            }
+        };
+        self.buffer.position.replace(position);
 
-        self.terminal.write(' ')
+        self.rerender()
     }
 
-    fn rerender(&mut self) -> Result<()> {
+    /// Contents of register `name`, or of the unnamed register when none is
+    /// given.
+    pub(super) fn read_register(&self, name: Option<char>) -> Option<Register> {
+        match name {
+            Some('%') => self
+                .buffer
+                .entry
+                .borrow()
+                .as_ref()
+                .map(|entry| Register::new(entry.display().to_string(), RegisterKind::Charwise)),
+            name => self.registers.get(name),
+        }
+    }
+
+    /// Writes yanked or deleted text to register `name`, reporting rather
+    /// than failing when the register cannot be written. Returns whether the
+    /// text was stored.
+    pub(super) fn store_register(
+        &mut self,
+        name: Option<char>,
+        register: Register,
+        yank: bool,
+    ) -> bool {
+        let result = match yank {
+            true => self.registers.yank(name, register),
+            false => self.registers.delete(name, register),
+        };
+
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                self.set_message(err);
+                false
+            }
+        }
+    }
+
+    fn undo(&mut self) -> Result<()> {
+        match self.buffer.undo() {
+            Some(position) => self.restore_position(position),
+            None => {
+                self.set_message("Already at oldest change");
+                Ok(())
+            }
+        }
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        match self.buffer.redo() {
+            Some(position) => self.restore_position(position),
+            None => {
+                self.set_message("Already at newest change");
+                Ok(())
+            }
+        }
+    }
+
+    fn undo_jump(&mut self, seq: usize) -> Result<()> {
+        self.change_mode(Mode::Normal);
+        match self.buffer.undo_jump(seq) {
+            Some(position) => self.restore_position(position),
+            None => self.rerender(),
+        }
+    }
+
+    fn restore_position(&mut self, position: (usize, usize)) -> Result<()> {
+        self.buffer.position.replace(position);
+
+        self.rerender()
+    }
+
+    fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.buffer.set_line_ending(line_ending);
+    }
+
+    fn redraw_line_from(&mut self, column: usize) -> Result<()> {
+        let row = self.buffer.position.borrow().1;
+        let line = self.buffer.get(Line::Current).unwrap_or_default();
+        let screen_position = self.screen_position((column, row));
+
+        self.terminal.cursor_move_to(screen_position)?;
+        self.terminal.clear_until_newline()?;
+        self.terminal.write(unicode::expand_tabs_from(
+            &line,
+            column,
+            self.buffer.tab_width,
+        ))
+    }
+
+    pub(super) fn rerender(&mut self) -> Result<()> {
+        if self.terminal.is_suspended() {
+            return Ok(());
+        }
+
         let current_position = *self.buffer.position.borrow();
+        self.buffer
+            .scroll_to_row(current_position.1, self.scroll_off);
         let viewable = self.buffer.format_viewable();
         self.terminal.cursor_hide()?;
         self.terminal.clear()?;
         self.terminal.cursor_reset()?;
         self.terminal.write(viewable)?;
+        self.draw_selection()?;
+        self.draw_search_matches()?;
+        self.draw_substitution()?;
         self.move_to(current_position)?;
 
         self.terminal.cursor_show()
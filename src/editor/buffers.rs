@@ -0,0 +1,340 @@
+//! Buffers open alongside the one being edited. Each is numbered from one in
+//! the order it was opened, and while hidden keeps its cursor, view, undo
+//! history and changes until it is switched back to.
+
+use crate::{
+    editor::command::BufferName,
+    error::{CommandError, Result},
+    Editor, FrameBuffer,
+};
+use std::{collections::BTreeMap, fs, mem, path::Path};
+
+#[derive(Debug)]
+pub struct BufferList {
+    current: usize,           // Number of the buffer being edited
+    alternate: Option<usize>, // Buffer edited before it, `#` in `:ls`
+    hidden: BTreeMap<usize, FrameBuffer>,
+    next: usize,
+}
+
+impl BufferList {
+    pub fn new() -> Self {
+        Self {
+            current: 1,
+            alternate: None,
+            hidden: BTreeMap::new(),
+            next: 2,
+        }
+    }
+
+    /// Numbers of every buffer, the current one included, in order.
+    fn numbers(&self) -> Vec<usize> {
+        let mut numbers: Vec<_> = self.hidden.keys().copied().collect();
+        let index = numbers.partition_point(|number| *number < self.current);
+        numbers.insert(index, self.current);
+
+        numbers
+    }
+
+    fn add(&mut self, buffer: FrameBuffer) -> usize {
+        let number = self.next;
+        self.hidden.insert(number, buffer);
+        self.next += 1;
+
+        number
+    }
+}
+
+impl Default for BufferList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    /// Opens `buffer` hidden behind the current one, returning its number.
+    pub fn add_buffer(&mut self, mut buffer: FrameBuffer) -> usize {
+        let viewable_rows = self.buffer.viewable_rows.clone();
+        Self::configure_buffer(&self.terminal, &mut buffer, viewable_rows);
+
+        self.buffers.add(buffer)
+    }
+
+    /// Edits buffer `number`, hiding the current one.
+    pub(super) fn switch_to_buffer(&mut self, number: usize) -> Result<()> {
+        if number == self.buffers.current {
+            return Ok(());
+        }
+
+        let mut buffer = self
+            .buffers
+            .hidden
+            .remove(&number)
+            .ok_or(CommandError::NoSuchBuffer(number))?;
+        // The terminal may have been resized while it was hidden
+        let top = buffer.viewable_rows.start;
+        buffer.viewable_rows = top..top + self.buffer.view_height() - 1;
+
+        self.store_file_marks();
+        self.status_bar.attach(&buffer);
+        let previous = mem::replace(&mut self.buffer, buffer);
+        self.buffers.hidden.insert(self.buffers.current, previous);
+        self.buffers.alternate = Some(self.buffers.current);
+        self.buffers.current = number;
+        self.restore_file_marks();
+
+        self.rerender()
+    }
+
+    /// Switches to the buffer editing `path`, opening one for it when there
+    /// is none.
+    pub(super) fn edit_other(&mut self, path: &Path) -> Result<()> {
+        let number = self
+            .buffers
+            .hidden
+            .iter()
+            .find(|(_, buffer)| is_entry(buffer, path))
+            .map(|(number, _)| *number);
+        if let Some(number) = number {
+            return self.switch_to_buffer(number);
+        }
+
+        // An untouched scratch buffer is taken over rather than kept hidden
+        let scratch = self.buffer.entry.borrow().is_none() && !*self.buffer.modified.borrow();
+        if scratch && self.buffer.text().is_empty() {
+            return self.edit(Some(path.to_owned()));
+        }
+
+        let view_span = self.buffer.viewable_rows.clone();
+        let buffer = match path.exists() {
+            true => FrameBuffer::try_from_path(path.to_owned(), view_span)?,
+            false => FrameBuffer::new(vec![], Some(path.to_owned()), view_span),
+        };
+        let number = self.add_buffer(buffer);
+
+        self.switch_to_buffer(number)
+    }
+
+    /// Whether `path` is the entry of the current buffer.
+    pub(super) fn is_editing(&self, path: &Path) -> bool {
+        is_entry(&self.buffer, path)
+    }
+
+    /// Moves `count` buffers on from the current one, or back when
+    /// `reverse`, wrapping around at either end.
+    pub(super) fn cycle_buffers(&mut self, count: usize, reverse: bool) -> Result<()> {
+        let numbers = self.buffers.numbers();
+        let len = numbers.len();
+        let index = numbers
+            .iter()
+            .position(|number| *number == self.buffers.current)
+            .unwrap_or_default();
+        let index = match reverse {
+            true => (index + len - count % len) % len,
+            false => (index + count) % len,
+        };
+
+        self.switch_to_buffer(numbers[index])
+    }
+
+    /// Number of the buffer `name` refers to: its number, or part of the
+    /// name of exactly one buffer.
+    pub(super) fn find_buffer(&self, name: &BufferName) -> Result<usize> {
+        let pattern = match name {
+            BufferName::Number(number) => {
+                return match self.buffers.numbers().contains(number) {
+                    true => Ok(*number),
+                    false => Err(CommandError::NoSuchBuffer(*number).into()),
+                };
+            }
+            BufferName::Name(pattern) => pattern,
+        };
+
+        let matches: Vec<_> = self
+            .buffers
+            .numbers()
+            .into_iter()
+            .filter(|number| {
+                let name = self.buffer_name(*number);
+                name.contains(pattern.as_str())
+            })
+            .collect();
+
+        match matches[..] {
+            [number] => Ok(number),
+            [] => Err(CommandError::NoMatchingBuffer(pattern.clone()).into()),
+            _ => Err(CommandError::MultipleMatchingBuffers(pattern.clone()).into()),
+        }
+    }
+
+    /// Closes buffer `name`, or the current one, refusing to drop changes
+    /// unless `force`d. Closing the current buffer edits the one before it,
+    /// or a new empty one when it was the last.
+    pub(super) fn delete_buffer(&mut self, name: Option<BufferName>, force: bool) -> Result<()> {
+        let number = match name {
+            Some(name) => self.find_buffer(&name)?,
+            None => self.buffers.current,
+        };
+        let modified = match self.buffer_by_number(number) {
+            Some(buffer) => *buffer.modified.borrow(),
+            None => return Err(CommandError::NoSuchBuffer(number).into()),
+        };
+        if modified && !force {
+            return Err(CommandError::NoWriteSinceLastBufferChange(number).into());
+        }
+
+        if number != self.buffers.current {
+            self.buffers.hidden.remove(&number);
+        } else {
+            let next = self
+                .buffers
+                .alternate
+                .filter(|alternate| self.buffers.hidden.contains_key(alternate))
+                .or_else(|| self.buffers.hidden.keys().next().copied());
+            let next = match next {
+                Some(next) => next,
+                None => {
+                    let view_span = self.buffer.viewable_rows.clone();
+                    self.add_buffer(FrameBuffer::new(vec![], None, view_span))
+                }
+            };
+
+            self.switch_to_buffer(next)?;
+            self.buffers.hidden.remove(&number);
+        }
+
+        if self.buffers.alternate == Some(number) {
+            self.buffers.alternate = None;
+        }
+
+        Ok(())
+    }
+
+    /// Fails when a hidden buffer has changes not yet written, which
+    /// quitting would lose.
+    pub(super) fn check_hidden_buffers(&self) -> Result<()> {
+        match self.modified_hidden_buffer() {
+            Some(number) => {
+                Err(CommandError::HiddenBufferModified(self.buffer_name(number)).into())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Number of the first hidden buffer with changes not yet written.
+    fn modified_hidden_buffer(&self) -> Option<usize> {
+        self.buffers
+            .hidden
+            .iter()
+            .find(|(_, buffer)| *buffer.modified.borrow())
+            .map(|(number, _)| *number)
+    }
+
+    /// Writes every buffer with changes not yet written, hidden ones
+    /// included, returning to the current buffer afterwards.
+    pub fn save_modified(&mut self) -> Result<()> {
+        let current = self.buffers.current;
+        while let Some(number) = self.modified_hidden_buffer() {
+            self.switch_to_buffer(number)?;
+            self.save(None)?;
+        }
+
+        self.switch_to_buffer(current)?;
+        if *self.buffer.modified.borrow() {
+            self.save(None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Listing of every buffer as shown by `:ls`, one per line with its
+    /// number, whether it is current (`%`) or alternate (`#`), active (`a`)
+    /// or hidden (`h`), modified (`+`), its name and the line it is on.
+    pub(super) fn list_buffers(&self) -> String {
+        self.buffers
+            .numbers()
+            .into_iter()
+            .filter_map(|number| {
+                let buffer = self.buffer_by_number(number)?;
+                let marker = match number {
+                    number if number == self.buffers.current => '%',
+                    number if Some(number) == self.buffers.alternate => '#',
+                    _ => ' ',
+                };
+                let state = match number == self.buffers.current {
+                    true => 'a',
+                    false => 'h',
+                };
+                let modified = match *buffer.modified.borrow() {
+                    true => '+',
+                    false => ' ',
+                };
+                let line = buffer.position.borrow().1 + 1;
+                let name = format!("\"{}\"", self.buffer_name(number));
+
+                Some(format!(
+                    "{number:>3} {marker}{state} {modified} {name:<20} line {line}"
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn buffer_by_number(&self, number: usize) -> Option<&FrameBuffer> {
+        match number == self.buffers.current {
+            true => Some(&self.buffer),
+            false => self.buffers.hidden.get(&number),
+        }
+    }
+
+    /// Path buffer `number` edits as shown, or `[No Name]` for a scratch
+    /// buffer.
+    fn buffer_name(&self, number: usize) -> String {
+        let entry = self
+            .buffer_by_number(number)
+            .and_then(|buffer| buffer.entry.borrow().clone());
+
+        match entry {
+            Some(path) => path.display().to_string(),
+            None => "[No Name]".to_owned(),
+        }
+    }
+}
+
+/// Whether `buffer` edits `path`, by whatever name either was given.
+fn is_entry(buffer: &FrameBuffer, path: &Path) -> bool {
+    let entry = buffer.entry.borrow();
+    let entry = match entry.as_ref() {
+        Some(entry) => entry,
+        None => return false,
+    };
+
+    if entry == path {
+        return true;
+    }
+
+    match (fs::canonicalize(entry), fs::canonicalize(path)) {
+        (Ok(entry), Ok(path)) => entry == path,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers_buffers() {
+        let mut buffers = BufferList::new();
+        assert_eq!(buffers.numbers(), [1]);
+
+        buffers.add(FrameBuffer::new(vec![], None, 0..4));
+        buffers.add(FrameBuffer::new(vec![], None, 0..4));
+        buffers.current = 2;
+        buffers.hidden.remove(&2);
+        buffers
+            .hidden
+            .insert(1, FrameBuffer::new(vec![], None, 0..4));
+        assert_eq!(buffers.numbers(), [1, 2, 3]);
+    }
+}
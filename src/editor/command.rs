@@ -0,0 +1,600 @@
+use crate::error::CommandError;
+use std::{
+    iter::Peekable,
+    path::PathBuf,
+    str::{CharIndices, Chars},
+};
+
+/// Line a range refers to before `offset` is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+    Line(usize), // One-based, as typed
+    Current,
+    Last,
+    Mark(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub kind: AddressKind,
+    pub offset: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: Address,
+    pub end: Address,
+}
+
+impl LineRange {
+    /// The `%` range.
+    pub fn whole() -> Self {
+        Self {
+            start: Address {
+                kind: AddressKind::Line(1),
+                offset: 0,
+            },
+            end: Address {
+                kind: AddressKind::Last,
+                offset: 0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetArgument {
+    Enable(String),
+    Disable(String),
+    Toggle(String),
+    Query(String),
+    Assign(String, String),
+}
+
+/// Flags following a substitution, as in `:s/a/b/gc`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubstituteFlags {
+    pub global: bool,      // `g`, every match on a line rather than the first
+    pub ignore_case: bool, // `i`
+    pub confirm: bool,     // `c`, asking before each replacement
+    pub count_only: bool,  // `n`, reporting the matches without replacing them
+}
+
+/// Arguments of `:s/pattern/replacement/flags`, with escaped delimiters
+/// already taken out of the pattern and replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitute {
+    pub pattern: String,
+    pub replacement: String,
+    pub flags: SubstituteFlags,
+}
+
+/// Buffer named by `:b` or `:bd`, by its number or part of its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BufferName {
+    Number(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    Goto,
+    Write {
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    WriteQuit {
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    Exit {
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    Quit {
+        force: bool,
+    },
+    Edit {
+        path: Option<PathBuf>,
+        force: bool,
+    },
+    Set(Vec<SetArgument>),
+    Substitute(Option<Substitute>), // `None` repeats the last substitution
+    Delete {
+        register: Option<char>,
+    },
+    Normal(String), // Keys run as typed in Normal mode
+    Global {
+        pattern: String,
+        command: String,
+        invert: bool,
+    },
+    Undo,
+    Redo,
+    UndoTree,
+    Buffer(Option<BufferName>), // `None` stays on the current buffer
+    BufferNext(usize),
+    BufferPrevious(usize),
+    BufferDelete {
+        buffer: Option<BufferName>, // `None` closes the current buffer
+        force: bool,
+    },
+    ListBuffers,
+}
+
+/// A parsed ex command line such as `:w`, `:q!` or `:'a,'b`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub range: Option<LineRange>,
+    pub kind: CommandKind,
+}
+
+/// Command names along with the shortest abbreviation each accepts.
+const COMMANDS: &[(&str, usize)] = &[
+    ("bNext", 2),
+    ("bdelete", 2),
+    ("bnext", 2),
+    ("bprevious", 2),
+    ("buffer", 1),
+    ("buffers", 7),
+    ("delete", 1),
+    ("edit", 1),
+    ("exit", 3),
+    ("files", 5),
+    ("global", 1),
+    ("ls", 2),
+    ("normal", 4),
+    ("quit", 1),
+    ("redo", 3),
+    ("set", 2),
+    ("substitute", 1),
+    ("undo", 1),
+    ("undotree", 5),
+    ("vglobal", 1),
+    ("write", 1),
+    ("wq", 2),
+    ("xit", 1),
+];
+
+pub fn parse(input: &str) -> Result<Command, CommandError> {
+    let mut chars = input.char_indices().peekable();
+    skip_whitespace(&mut chars);
+    let range = parse_range(&mut chars)?;
+    skip_whitespace(&mut chars);
+
+    let start = offset(&mut chars, input);
+    while matches!(chars.peek(), Some((_, c)) if c.is_ascii_alphabetic()) {
+        chars.next();
+    }
+    let name = &input[start..offset(&mut chars, input)];
+    let force = matches!(chars.peek(), Some((_, '!')));
+    if force {
+        chars.next();
+    }
+    // Keys and commands run by other commands keep any blanks they end in
+    let raw_argument = input[offset(&mut chars, input)..].trim_start();
+    let argument = raw_argument.trim_end();
+
+    if name.is_empty() {
+        return match (force, argument.is_empty(), range) {
+            (false, true, Some(_)) => Ok(Command {
+                range,
+                kind: CommandKind::Goto,
+            }),
+            (false, true, None) => Err(CommandError::NotAnEditorCommand(input.to_owned())),
+            _ => Err(CommandError::NotAnEditorCommand(input.trim().to_owned())),
+        };
+    }
+
+    let full_name = COMMANDS
+        .iter()
+        .find(|(full, min)| name.len() >= *min && full.starts_with(name))
+        .map(|(full, _)| *full)
+        .ok_or_else(|| CommandError::NotAnEditorCommand(input.trim().to_owned()))?;
+    let path = match argument.is_empty() {
+        true => None,
+        false => Some(PathBuf::from(argument)),
+    };
+    let no_argument = |kind: CommandKind| match argument.is_empty() {
+        true => Ok(kind),
+        false => Err(CommandError::TrailingCharacters(argument.to_owned())),
+    };
+
+    let kind = match full_name {
+        "bNext" | "bprevious" => CommandKind::BufferPrevious(parse_count(argument)?),
+        "bdelete" => CommandKind::BufferDelete {
+            buffer: parse_buffer_name(argument),
+            force,
+        },
+        "bnext" => CommandKind::BufferNext(parse_count(argument)?),
+        "buffer" => CommandKind::Buffer(parse_buffer_name(argument)),
+        "buffers" | "files" | "ls" => no_argument(CommandKind::ListBuffers)?,
+        "delete" => CommandKind::Delete {
+            register: match argument.chars().collect::<Vec<_>>()[..] {
+                [] => None,
+                [name] => Some(name),
+                _ => return Err(CommandError::TrailingCharacters(argument.to_owned())),
+            },
+        },
+        "edit" => CommandKind::Edit { path, force },
+        "exit" | "xit" => CommandKind::Exit { path, force },
+        "global" => parse_global(raw_argument, force)?,
+        "normal" => CommandKind::Normal(raw_argument.to_owned()),
+        "quit" => no_argument(CommandKind::Quit { force })?,
+        "redo" => no_argument(CommandKind::Redo)?,
+        "set" => CommandKind::Set(parse_set_arguments(argument)),
+        // `!` is as good a delimiter as any other
+        "substitute" => match force {
+            true => CommandKind::Substitute(parse_substitute(&format!("!{argument}"))?),
+            false => CommandKind::Substitute(parse_substitute(argument)?),
+        },
+        "undo" => no_argument(CommandKind::Undo)?,
+        "undotree" => no_argument(CommandKind::UndoTree)?,
+        "vglobal" => parse_global(raw_argument, true)?,
+        "write" => CommandKind::Write { path, force },
+        "wq" => CommandKind::WriteQuit { path, force },
+        _ => unreachable!(),
+    };
+
+    Ok(Command { range, kind })
+}
+
+fn parse_range(chars: &mut Peekable<CharIndices>) -> Result<Option<LineRange>, CommandError> {
+    if let Some((_, '%')) = chars.peek() {
+        chars.next();
+        return Ok(Some(LineRange::whole()));
+    }
+
+    let start = match parse_address(chars)? {
+        Some(address) => address,
+        None => return Ok(None),
+    };
+
+    skip_whitespace(chars);
+    if let Some((_, ',' | ';')) = chars.peek() {
+        chars.next();
+        skip_whitespace(chars);
+        let end = parse_address(chars)?.ok_or(CommandError::InvalidRange)?;
+
+        return Ok(Some(LineRange { start, end }));
+    }
+
+    Ok(Some(LineRange { start, end: start }))
+}
+
+fn parse_address(chars: &mut Peekable<CharIndices>) -> Result<Option<Address>, CommandError> {
+    let kind = match chars.peek().map(|(_, c)| *c) {
+        Some('.') => {
+            chars.next();
+            AddressKind::Current
+        }
+        Some('$') => {
+            chars.next();
+            AddressKind::Last
+        }
+        Some('\'') => {
+            chars.next();
+            match chars.next() {
+                Some((_, mark)) => AddressKind::Mark(mark),
+                None => return Err(CommandError::InvalidRange),
+            }
+        }
+        Some(c) if c.is_ascii_digit() => AddressKind::Line(parse_number(chars).unwrap_or(0)),
+        Some('+' | '-') => AddressKind::Current,
+        _ => return Ok(None),
+    };
+
+    let mut offset = 0;
+    while let Some((_, sign @ ('+' | '-'))) = chars.peek().copied() {
+        chars.next();
+        let amount = parse_number(chars).unwrap_or(1) as isize;
+        match sign {
+            '+' => offset += amount,
+            _ => offset -= amount,
+        }
+    }
+
+    Ok(Some(Address { kind, offset }))
+}
+
+fn parse_number(chars: &mut Peekable<CharIndices>) -> Option<usize> {
+    let mut number = None;
+    while let Some(digit) = chars.peek().and_then(|(_, c)| c.to_digit(10)) {
+        chars.next();
+        number = Some(number.unwrap_or(0) * 10 + digit as usize);
+    }
+
+    number
+}
+
+/// Count given to `:bn` and `:bp`, one when there is none.
+fn parse_count(argument: &str) -> Result<usize, CommandError> {
+    if argument.is_empty() {
+        return Ok(1);
+    }
+
+    match argument.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(CommandError::InvalidArgument(argument.to_owned())),
+    }
+}
+
+fn parse_buffer_name(argument: &str) -> Option<BufferName> {
+    if argument.is_empty() {
+        return None;
+    }
+
+    match argument.parse() {
+        Ok(number) => Some(BufferName::Number(number)),
+        Err(_) => Some(BufferName::Name(argument.to_owned())),
+    }
+}
+
+fn parse_set_arguments(argument: &str) -> Vec<SetArgument> {
+    argument
+        .split_whitespace()
+        .map(|argument| {
+            if let Some((name, value)) = argument.split_once(['=', ':']) {
+                return SetArgument::Assign(name.to_owned(), value.to_owned());
+            }
+
+            if let Some(name) = argument.strip_suffix('?') {
+                return SetArgument::Query(name.to_owned());
+            }
+
+            if let Some(name) = argument.strip_suffix('!') {
+                return SetArgument::Toggle(name.to_owned());
+            }
+
+            if let Some(name) = argument.strip_prefix("inv") {
+                return SetArgument::Toggle(name.to_owned());
+            }
+
+            match argument.strip_prefix("no") {
+                Some(name) => SetArgument::Disable(name.to_owned()),
+                None => SetArgument::Enable(argument.to_owned()),
+            }
+        })
+        .collect()
+}
+
+fn parse_substitute(argument: &str) -> Result<Option<Substitute>, CommandError> {
+    let mut chars = argument.chars();
+    let delimiter = match parse_delimiter(&mut chars)? {
+        Some(delimiter) => delimiter,
+        None => return Ok(None),
+    };
+    let pattern = parse_delimited(&mut chars, delimiter);
+    let replacement = parse_delimited(&mut chars, delimiter);
+
+    let mut flags = SubstituteFlags::default();
+    let rest: String = chars.collect();
+    for flag in rest.trim().chars() {
+        match flag {
+            'g' => flags.global = true,
+            'i' => flags.ignore_case = true,
+            'c' => flags.confirm = true,
+            'n' => flags.count_only = true,
+            _ => return Err(CommandError::TrailingCharacters(rest)),
+        }
+    }
+
+    Ok(Some(Substitute {
+        pattern,
+        replacement,
+        flags,
+    }))
+}
+
+/// Parses `:g/pattern/command`, where `invert`ed as `:v` or `:g!` the
+/// command runs on the lines that do not match.
+fn parse_global(argument: &str, invert: bool) -> Result<CommandKind, CommandError> {
+    let mut chars = argument.chars();
+    let delimiter = parse_delimiter(&mut chars)?.ok_or(CommandError::GlobalPatternMissing)?;
+    let pattern = parse_delimited(&mut chars, delimiter);
+
+    Ok(CommandKind::Global {
+        pattern,
+        command: chars.as_str().trim_start().to_owned(),
+        invert,
+    })
+}
+
+/// Character separating the pattern of `:s` or `:g` from what follows it,
+/// which can be any but a letter, a digit, `\`, `"` or `|`.
+fn parse_delimiter(chars: &mut Chars) -> Result<Option<char>, CommandError> {
+    match chars.next() {
+        Some(c) if c.is_alphanumeric() || matches!(c, '\\' | '"' | '|') => {
+            Err(CommandError::InvalidDelimiter)
+        }
+        delimiter => Ok(delimiter),
+    }
+}
+
+/// Text up to the next unescaped `delimiter`, which may be left off the
+/// end. Escaped delimiters stand for themselves, while other escapes are
+/// kept as they are.
+fn parse_delimited(chars: &mut Chars, delimiter: char) -> String {
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == delimiter => break,
+            '\\' => match chars.next() {
+                Some(c) if c == delimiter => text.push(c),
+                Some(c) => {
+                    text.push('\\');
+                    text.push(c);
+                }
+                None => text.push('\\'),
+            },
+            c => text.push(c),
+        }
+    }
+
+    text
+}
+
+fn skip_whitespace(chars: &mut Peekable<CharIndices>) {
+    while matches!(chars.peek(), Some((_, c)) if c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Byte offset of the next char, or the end of `input`.
+fn offset(chars: &mut Peekable<CharIndices>, input: &str) -> usize {
+    chars.peek().map(|(i, _)| *i).unwrap_or(input.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn address(kind: AddressKind, offset: isize) -> Address {
+        Address { kind, offset }
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            parse("w").unwrap().kind,
+            CommandKind::Write {
+                path: None,
+                force: false
+            }
+        );
+        assert_eq!(parse("q!").unwrap().kind, CommandKind::Quit { force: true });
+        assert_eq!(
+            parse(" wq  notes.md").unwrap().kind,
+            CommandKind::WriteQuit {
+                path: Some(PathBuf::from("notes.md")),
+                force: false
+            }
+        );
+        assert_eq!(
+            parse("set ts=8 nobackup ff?").unwrap().kind,
+            CommandKind::Set(vec![
+                SetArgument::Assign("ts".to_owned(), "8".to_owned()),
+                SetArgument::Disable("backup".to_owned()),
+                SetArgument::Query("ff".to_owned()),
+            ])
+        );
+        assert!(parse("foo").is_err());
+        assert!(parse("q now").is_err());
+    }
+
+    #[test]
+    fn ranges() {
+        let command = parse("42").unwrap();
+        assert_eq!(command.kind, CommandKind::Goto);
+        assert_eq!(
+            command.range.unwrap().end,
+            address(AddressKind::Line(42), 0)
+        );
+
+        assert_eq!(parse("%").unwrap().range, Some(LineRange::whole()));
+        assert_eq!(
+            parse(".,$-2").unwrap().range,
+            Some(LineRange {
+                start: address(AddressKind::Current, 0),
+                end: address(AddressKind::Last, -2),
+            })
+        );
+        assert_eq!(
+            parse("'a,'b").unwrap().range,
+            Some(LineRange {
+                start: address(AddressKind::Mark('a'), 0),
+                end: address(AddressKind::Mark('b'), 0),
+            })
+        );
+        assert_eq!(
+            parse("+3").unwrap().range.unwrap().start,
+            address(AddressKind::Current, 3)
+        );
+        assert!(parse("1,").is_err());
+    }
+
+    #[test]
+    fn buffers() {
+        assert_eq!(
+            parse("b2").unwrap().kind,
+            CommandKind::Buffer(Some(BufferName::Number(2)))
+        );
+        assert_eq!(
+            parse("buffer notes").unwrap().kind,
+            CommandKind::Buffer(Some(BufferName::Name("notes".to_owned())))
+        );
+        assert_eq!(parse("bn").unwrap().kind, CommandKind::BufferNext(1));
+        assert_eq!(parse("bN 3").unwrap().kind, CommandKind::BufferPrevious(3));
+        assert_eq!(
+            parse("bd!").unwrap().kind,
+            CommandKind::BufferDelete {
+                buffer: None,
+                force: true
+            }
+        );
+        assert_eq!(parse("buffers").unwrap().kind, CommandKind::ListBuffers);
+        assert_eq!(parse("ls").unwrap().kind, CommandKind::ListBuffers);
+        assert!(parse("bp 0").is_err());
+    }
+
+    #[test]
+    fn substitutions() {
+        let substitute = |input| match parse(input).unwrap().kind {
+            CommandKind::Substitute(substitute) => substitute,
+            kind => panic!("parsed as {kind:?}"),
+        };
+
+        let parsed = substitute("s/a\\/b/\\1\\//gi").unwrap();
+        assert_eq!(parsed.pattern, "a/b");
+        assert_eq!(parsed.replacement, "\\1/");
+        assert_eq!(
+            parsed.flags,
+            SubstituteFlags {
+                global: true,
+                ignore_case: true,
+                ..SubstituteFlags::default()
+            }
+        );
+
+        // Other delimiters, and a replacement left unterminated
+        let parsed = substitute("s#x\\d#y").unwrap();
+        assert_eq!(
+            (parsed.pattern.as_str(), parsed.replacement.as_str()),
+            ("x\\d", "y")
+        );
+        assert!(substitute("s!x!y!n").unwrap().flags.count_only);
+
+        assert_eq!(parse("%s").unwrap().kind, CommandKind::Substitute(None));
+        assert!(parse("s/a/b/x").is_err());
+        assert!(parse("sxaxbx").is_err());
+    }
+
+    #[test]
+    fn globals() {
+        let global = |pattern: &str, command: &str, invert| CommandKind::Global {
+            pattern: pattern.to_owned(),
+            command: command.to_owned(),
+            invert,
+        };
+
+        assert_eq!(
+            parse("g/TODO/normal A; ").unwrap().kind,
+            global("TODO", "normal A; ", false)
+        );
+        assert_eq!(parse("g!/a\\/b/d").unwrap().kind, global("a/b", "d", true));
+        assert_eq!(parse("v#keep#").unwrap().kind, global("keep", "", true));
+        assert!(parse("g").is_err());
+
+        assert_eq!(
+            parse("norm  dd ").unwrap().kind,
+            CommandKind::Normal("dd ".to_owned())
+        );
+        assert_eq!(
+            parse("d a").unwrap().kind,
+            CommandKind::Delete {
+                register: Some('a')
+            }
+        );
+        assert!(parse("d ab").is_err());
+    }
+}
@@ -0,0 +1,492 @@
+use crate::{
+    editor::{
+        action::{Action, Message},
+        command::{self, Address, AddressKind, Command, CommandKind, LineRange, SetArgument},
+        operator::{Operator, Region},
+        Mode,
+    },
+    error::{CommandError, Error, Result},
+    line_ending::LineEnding,
+    register::Registers,
+    unicode, Editor, FrameBuffer, Span,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+/// Editable line shown in place of the status bar while typing a command.
+///
+/// Each prefix (`:` for ex commands, `/` and `?` for searches) keeps its own
+/// history.
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    prefix: char,
+    text: String,
+    cursor: usize, // Graphemes into `text`
+    history: HashMap<char, Vec<String>>,
+    history_index: Option<usize>,
+}
+
+impl CommandLine {
+    pub fn open(&mut self, prefix: char) {
+        self.prefix = prefix;
+        self.text.clear();
+        self.cursor = 0;
+        self.history_index = None;
+    }
+
+    #[inline]
+    pub fn prefix(&self) -> char {
+        self.prefix
+    }
+
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Display column of the cursor, counting the prefix.
+    pub fn cursor_column(&self) -> usize {
+        1 + unicode::grapheme_to_display(&self.text, self.cursor, 1)
+    }
+
+    pub fn insert(&mut self, character: char) {
+        let byte = unicode::grapheme_to_byte(&self.text, self.cursor);
+        self.text.insert(byte, character);
+        self.cursor = unicode::byte_to_grapheme(&self.text, byte + character.len_utf8());
+    }
+
+    /// Removes the grapheme before the cursor, returning false when there is
+    /// nothing left to remove.
+    pub fn backspace(&mut self) -> bool {
+        if self.text.is_empty() {
+            return false;
+        }
+
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.delete();
+        }
+
+        true
+    }
+
+    pub fn delete(&mut self) {
+        let start = unicode::grapheme_to_byte(&self.text, self.cursor);
+        let end = unicode::grapheme_to_byte(&self.text, self.cursor + 1);
+        self.text.replace_range(start..end, "");
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(unicode::grapheme_len(&self.text));
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor = unicode::grapheme_len(&self.text);
+    }
+
+    pub fn history_previous(&mut self) {
+        let len = self.history.get(&self.prefix).map_or(0, Vec::len);
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if len == 0 => return,
+            None => len - 1,
+        };
+
+        self.recall(Some(index));
+    }
+
+    pub fn history_next(&mut self) {
+        let len = self.history.get(&self.prefix).map_or(0, Vec::len);
+        match self.history_index {
+            Some(i) if i + 1 < len => self.recall(Some(i + 1)),
+            Some(_) => self.recall(None),
+            None => {}
+        }
+    }
+
+    fn recall(&mut self, index: Option<usize>) {
+        self.history_index = index;
+        self.text = match index {
+            Some(i) => self.history[&self.prefix][i].clone(),
+            None => String::new(),
+        };
+        self.move_to_end();
+    }
+
+    /// Takes the typed text, recording it in the prefix's history.
+    pub fn submit(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        let history = self.history.entry(self.prefix).or_default();
+        if !text.is_empty() && history.last() != Some(&text) {
+            history.push(text.clone());
+        }
+
+        self.cursor = 0;
+        self.history_index = None;
+
+        text
+    }
+}
+
+impl Display for CommandLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.prefix, self.text)
+    }
+}
+
+impl Editor {
+    /// Opens the command line, which from Visual mode starts out with the
+    /// range of the selection. A search prompt previews its matches as the
+    /// pattern is typed.
+    pub(super) fn open_command_line(&mut self, prefix: char) {
+        if matches!(prefix, '/' | '?') {
+            self.begin_search();
+        }
+
+        let visual = matches!(*self.mode.borrow(), Mode::Visual(_));
+        let mut command_line = self.command_line.borrow_mut();
+        command_line.open(prefix);
+        if visual && prefix == ':' {
+            "'<,'>".chars().for_each(|c| command_line.insert(c));
+        }
+        drop(command_line);
+
+        self.change_mode(Mode::Command);
+    }
+
+    pub(super) fn handle_command_mode_key_event(&mut self, event: KeyEvent) -> Result<Message> {
+        let mut command_line = self.command_line.borrow_mut();
+        match (event.code, event.modifiers) {
+            (KeyCode::Esc, KeyModifiers::NONE) => {
+                drop(command_line);
+                return self.close_command_line();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let prefix = command_line.prefix();
+                let text = command_line.submit();
+                drop(command_line);
+                self.change_mode(Mode::Normal);
+                self.end_search()?;
+
+                return self.submit_command_line(prefix, &text);
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                if !command_line.backspace() {
+                    drop(command_line);
+                    return self.close_command_line();
+                }
+            }
+            (KeyCode::Delete, KeyModifiers::NONE) => command_line.delete(),
+            (KeyCode::Left, KeyModifiers::NONE) => command_line.move_left(),
+            (KeyCode::Right, KeyModifiers::NONE) => command_line.move_right(),
+            (KeyCode::Home, KeyModifiers::NONE) => command_line.move_to_start(),
+            (KeyCode::End, KeyModifiers::NONE) => command_line.move_to_end(),
+            (KeyCode::Up, KeyModifiers::NONE) => command_line.history_previous(),
+            (KeyCode::Down, KeyModifiers::NONE) => command_line.history_next(),
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => command_line.insert(c),
+            _ => {}
+        }
+        drop(command_line);
+
+        self.update_search()?;
+
+        Ok(Message::Continue)
+    }
+
+    /// Leaves the command line without running it, along with any operator
+    /// waiting on a search.
+    fn close_command_line(&mut self) -> Result<Message> {
+        self.change_mode(Mode::Normal);
+        self.end_search()?;
+        self.operator = None;
+        self.count = None;
+
+        Ok(Message::Continue)
+    }
+
+    fn submit_command_line(&mut self, prefix: char, text: &str) -> Result<Message> {
+        let result = match prefix {
+            ':' => {
+                if !text.is_empty() {
+                    self.registers.set_last_command(text);
+                }
+
+                self.run_command(text)
+            }
+            '/' | '?' => self.submit_search(prefix, text).map(|_| Message::Continue),
+            _ => Ok(Message::Continue),
+        };
+
+        // Mistyped commands and failed writes are reported rather than ending
+        // the session
+        match result {
+            Ok(message) => Ok(message),
+            Err(err) => {
+                self.set_message(&err);
+                self.last_error = Some(err);
+                self.operator = None;
+                self.failed = true;
+                Ok(Message::Continue)
+            }
+        }
+    }
+
+    /// Parses and runs a single ex command, without its leading `:`.
+    pub fn run_command(&mut self, input: &str) -> Result<Message> {
+        let command = command::parse(input)?;
+
+        self.execute_command(command)
+    }
+
+    fn execute_command(&mut self, Command { range, kind }: Command) -> Result<Message> {
+        let modified = *self.buffer.modified.borrow();
+        match kind {
+            CommandKind::Goto => {
+                let rows = self.resolve_range(range)?;
+                return self.execute(Action::MoveTo(0, rows.end - 1));
+            }
+            CommandKind::Write { path, force } => {
+                self.check_overwrite(path.as_deref(), force)?;
+                self.save(path)?;
+            }
+            CommandKind::WriteQuit { path, force } => {
+                self.check_overwrite(path.as_deref(), force)?;
+                self.save(path)?;
+                if !force {
+                    self.check_hidden_buffers()?;
+                }
+
+                return Ok(Message::Exit);
+            }
+            CommandKind::Exit { path, force } => {
+                self.check_overwrite(path.as_deref(), force)?;
+                if modified || path.is_some() {
+                    self.save(path)?;
+                }
+                if !force {
+                    self.check_hidden_buffers()?;
+                }
+
+                return Ok(Message::Exit);
+            }
+            CommandKind::Quit { force } => {
+                if !force {
+                    if modified {
+                        return Err(CommandError::NoWriteSinceLastChange.into());
+                    }
+                    self.check_hidden_buffers()?;
+                }
+
+                return Ok(Message::Exit);
+            }
+            // Another file is opened in a buffer of its own, leaving this one
+            // hidden along with its changes
+            CommandKind::Edit {
+                path: Some(path), ..
+            } if !self.is_editing(&path) => self.edit_other(&path)?,
+            CommandKind::Edit { path, force } => {
+                if modified && !force {
+                    return Err(CommandError::NoWriteSinceLastChange.into());
+                }
+
+                self.edit(path)?;
+            }
+            CommandKind::Set(arguments) => {
+                for argument in arguments {
+                    self.set_option(argument)?;
+                }
+            }
+            CommandKind::Substitute(substitute) => self.substitute(range, substitute)?,
+            CommandKind::Delete { register } => {
+                if let Some(name) = register.filter(|name| !Registers::is_valid(*name)) {
+                    return Err(Error::InvalidRegister(name));
+                }
+
+                let rows = self.resolve_range(range)?;
+                self.register = register;
+                self.apply_operator(Operator::Delete, Region::Lines(rows))?;
+                self.buffer.commit();
+                self.rerender()?;
+            }
+            CommandKind::Normal(keys) => {
+                let rows = match range {
+                    Some(range) => Some(self.resolve_range(Some(range))?),
+                    None => None,
+                };
+                return self.normal(rows, &keys);
+            }
+            CommandKind::Global {
+                pattern,
+                command,
+                invert,
+            } => return self.global(range, &pattern, &command, invert),
+            CommandKind::Undo => return self.execute(Action::Undo),
+            CommandKind::Redo => return self.execute(Action::Redo),
+            CommandKind::UndoTree => return self.execute(Action::OpenUndoTree),
+            CommandKind::Buffer(name) => {
+                if let Some(name) = name {
+                    let number = self.find_buffer(&name)?;
+                    self.switch_to_buffer(number)?;
+                }
+            }
+            CommandKind::BufferNext(count) => self.cycle_buffers(count, false)?,
+            CommandKind::BufferPrevious(count) => self.cycle_buffers(count, true)?,
+            CommandKind::BufferDelete { buffer, force } => self.delete_buffer(buffer, force)?,
+            CommandKind::ListBuffers => {
+                let listing = self.list_buffers();
+                self.set_message(listing);
+            }
+        }
+
+        Ok(Message::Continue)
+    }
+
+    /// Reloads the current buffer from `path`, or from its entry when none is
+    /// given, dropping whatever changes it had.
+    pub(super) fn edit(&mut self, path: Option<std::path::PathBuf>) -> Result<()> {
+        let entry = self.buffer.entry.borrow().clone();
+        let path = match path.or(entry) {
+            Some(path) => path,
+            None => return Err(Error::NoFileName),
+        };
+
+        let view_span = self.buffer.viewable_rows.clone();
+        let buffer = match path.exists() {
+            true => FrameBuffer::try_from_path(path, view_span)?,
+            false => FrameBuffer::new(vec![], Some(path), view_span),
+        };
+
+        self.open_buffer(buffer)
+    }
+
+    /// Fails when writing to `path` would replace a file other than the one
+    /// being edited, unless `force`d with `!`.
+    fn check_overwrite(&self, path: Option<&Path>, force: bool) -> Result<()> {
+        match path {
+            Some(path) if !force && path.exists() && !self.is_editing(path) => {
+                Err(CommandError::FileExists.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Resolves `range` to the rows it spans, defaulting to the current line.
+    pub(super) fn resolve_range(&self, range: Option<LineRange>) -> Result<Span> {
+        let (start, end) = match range {
+            Some(range) => (
+                self.resolve_address(range.start)?,
+                self.resolve_address(range.end)?,
+            ),
+            None => {
+                let row = self.buffer.position.borrow().1;
+                (row, row)
+            }
+        };
+
+        Ok(start.min(end)..start.max(end) + 1)
+    }
+
+    fn resolve_address(&self, address: Address) -> Result<usize> {
+        let last = self.buffer.len().saturating_sub(1) as isize;
+        let row = match address.kind {
+            AddressKind::Line(line) => line.saturating_sub(1) as isize,
+            AddressKind::Current => self.buffer.position.borrow().1 as isize,
+            AddressKind::Last => last,
+            AddressKind::Mark(name) => match self.buffer.mark(name) {
+                Some((_, row)) => row as isize,
+                None => return Err(CommandError::MarkNotSet.into()),
+            },
+        } + address.offset;
+
+        match row {
+            row if row < 0 => Err(CommandError::InvalidRange.into()),
+            row => Ok(row.min(last) as usize),
+        }
+    }
+
+    fn set_option(&mut self, argument: SetArgument) -> Result<()> {
+        match argument {
+            SetArgument::Assign(name, value) => {
+                let invalid = || CommandError::InvalidArgument(format!("{name}={value}"));
+                match name.as_str() {
+                    "tabstop" | "ts" => {
+                        let tab_width = value.parse().ok().filter(|width| *width > 0);
+                        self.buffer.tab_width = tab_width.ok_or_else(invalid)?;
+                        self.rerender()?;
+                    }
+                    "scrolloff" | "so" => {
+                        self.scroll_off = value.parse().map_err(|_| invalid())?;
+                        self.rerender()?;
+                    }
+                    "fileformat" | "ff" => {
+                        let line_ending = match value.as_str() {
+                            "unix" => LineEnding::Lf,
+                            "dos" => LineEnding::Crlf,
+                            "mac" => LineEnding::Cr,
+                            _ => return Err(invalid().into()),
+                        };
+                        self.execute(Action::SetLineEnding(line_ending))?;
+                    }
+                    name if is_boolean_option(name) => return Err(invalid().into()),
+                    _ => return Err(CommandError::UnknownOption(name).into()),
+                }
+            }
+            // Naming a boolean option turns it on, while naming any other
+            // shows its value
+            SetArgument::Enable(name) if is_boolean_option(&name) => {
+                self.set_boolean_option(name, Some(true))?
+            }
+            SetArgument::Query(name) | SetArgument::Enable(name) => {
+                let value = match name.as_str() {
+                    "tabstop" | "ts" => format!("tabstop={}", self.buffer.tab_width),
+                    "scrolloff" | "so" => format!("scrolloff={}", self.scroll_off),
+                    "fileformat" | "ff" => {
+                        let format = match *self.buffer.line_ending.borrow() {
+                            LineEnding::Lf => "unix",
+                            LineEnding::Crlf => "dos",
+                            LineEnding::Cr => "mac",
+                        };
+                        format!("fileformat={format}")
+                    }
+                    "backup" | "bk" => match self.backup {
+                        true => "backup".to_owned(),
+                        false => "nobackup".to_owned(),
+                    },
+                    _ => return Err(CommandError::UnknownOption(name).into()),
+                };
+                self.set_message(format!("  {value}"));
+            }
+            SetArgument::Disable(name) => self.set_boolean_option(name, Some(false))?,
+            SetArgument::Toggle(name) => self.set_boolean_option(name, None)?,
+        }
+
+        Ok(())
+    }
+
+    /// Turns boolean option `name` on or off, or toggles it when `value` is
+    /// not given, as `:set {name}`, `:set no{name}` and `:set inv{name}` do.
+    fn set_boolean_option(&mut self, name: String, value: Option<bool>) -> Result<()> {
+        match name.as_str() {
+            "backup" | "bk" => self.backup = value.unwrap_or(!self.backup),
+            "tabstop" | "ts" | "scrolloff" | "so" | "fileformat" | "ff" => {
+                return Err(CommandError::InvalidArgument(name).into())
+            }
+            _ => return Err(CommandError::UnknownOption(name).into()),
+        }
+
+        Ok(())
+    }
+}
+
+fn is_boolean_option(name: &str) -> bool {
+    matches!(name, "backup" | "bk")
+}
@@ -0,0 +1,206 @@
+use crate::{
+    editor::{action::Message, command::LineRange},
+    error::{CommandError, Error, Result},
+    search::Search,
+    Editor, Span,
+};
+use std::collections::{BTreeSet, VecDeque};
+
+/// Line breaks of the lines `:g` has yet to visit, in order.
+///
+/// An edit moves every mark on one side of it, so the marks hold offsets
+/// from a shift they share: whichever side has fewer marks is moved one by
+/// one, and the other by moving the shift. As `:g` edits around the line
+/// it is visiting, which comes before all those left, that is rarely more
+/// than a few marks.
+#[derive(Debug, Clone, Default)]
+pub struct MarkedLines {
+    offsets: VecDeque<isize>,
+    shift: isize,
+}
+
+impl MarkedLines {
+    pub fn set(&mut self, offsets: impl IntoIterator<Item = usize>) {
+        self.offsets = offsets.into_iter().map(|offset| offset as isize).collect();
+        self.shift = 0;
+    }
+
+    pub fn pop_front(&mut self) -> Option<usize> {
+        let offset = self.offsets.pop_front()?;
+
+        Some((offset + self.shift) as usize)
+    }
+
+    /// Index of the first mark at or after `offset`.
+    fn partition(&self, offset: usize) -> usize {
+        let offset = offset as isize - self.shift;
+        self.offsets.partition_point(|mark| *mark < offset)
+    }
+
+    /// Moves the marks from the `index`th on by `len`.
+    fn shift_from(&mut self, index: usize, len: isize) {
+        match index < self.offsets.len() - index {
+            true => {
+                self.shift += len;
+                self.offsets
+                    .range_mut(..index)
+                    .for_each(|mark| *mark -= len);
+            }
+            false => self
+                .offsets
+                .range_mut(index..)
+                .for_each(|mark| *mark += len),
+        }
+    }
+
+    pub fn inserted(&mut self, index: usize, len: usize) {
+        let first = self.partition(index);
+        self.shift_from(first, len as isize);
+    }
+
+    /// Unmarks lines whose line break is within `range`, then moves those
+    /// after it back.
+    pub fn removed(&mut self, range: Span) {
+        let first = self.partition(range.start);
+        let end = self.partition(range.end);
+        self.offsets.drain(first..end);
+        self.shift_from(first, -(range.len() as isize));
+    }
+}
+
+impl Editor {
+    /// Runs `command` on every line within `range` that matches `pattern`,
+    /// or that does not when `invert`ed, as `:g` and `:v` do.
+    ///
+    /// The lines are all marked before the command runs on any of them, so
+    /// it can add and remove lines freely. Lines it removes are passed over,
+    /// and everything it changes is undone in one step.
+    pub(super) fn global(
+        &mut self,
+        range: Option<LineRange>,
+        pattern: &str,
+        command: &str,
+        invert: bool,
+    ) -> Result<Message> {
+        if self.in_global {
+            return Err(CommandError::GlobalRecursive.into());
+        }
+
+        let rows = self.resolve_range(range.or_else(|| Some(LineRange::whole())))?;
+        let pattern = match (pattern, self.last_search.as_ref()) {
+            ("", Some(last)) => last.pattern.clone(),
+            ("", None) => return Err(Error::NoPreviousPattern),
+            (pattern, _) => pattern.to_owned(),
+        };
+        let search = Search::new(&pattern, true)?;
+        let matching: BTreeSet<usize> = self
+            .buffer
+            .matches(&search, &rows)
+            .iter()
+            .map(|found| self.buffer.char_position(found.start).1)
+            .collect();
+        let marked: Vec<usize> = rows
+            .filter(|row| matching.contains(row) != invert)
+            .collect();
+
+        self.registers.set_last_search(&pattern);
+        self.last_search = Some(search);
+        if marked.is_empty() {
+            return Err(Error::PatternNotFound(pattern));
+        }
+
+        self.buffer.commit();
+        let len = self.buffer.undo_tree.nodes().len();
+        let suspended = self.terminal.is_suspended();
+        self.terminal.suspend();
+        self.in_global = true;
+        self.buffer.mark_lines(marked);
+
+        let result = self.run_on_marked_lines(command);
+
+        self.in_global = false;
+        self.buffer.clear_marked_lines();
+        self.buffer.commit();
+        self.buffer.undo_tree.join_since(len);
+        if !suspended {
+            self.terminal.resume();
+            self.rerender()?;
+        }
+
+        result
+    }
+
+    fn run_on_marked_lines(&mut self, command: &str) -> Result<Message> {
+        // Only the last of any failures is reported, once every line is done
+        let mut error = None;
+        while let Some(row) = self.buffer.next_marked_line() {
+            self.buffer.position.replace((0, row));
+            if command.trim().is_empty() {
+                continue;
+            }
+
+            match self.run_command(command) {
+                Ok(Message::Exit) => return Ok(Message::Exit),
+                Ok(Message::Continue) => {}
+                Err(err) => error = Some(err),
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(Message::Continue),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MarkedLines;
+
+    fn marked(offsets: &[usize]) -> MarkedLines {
+        let mut lines = MarkedLines::default();
+        lines.set(offsets.iter().copied());
+
+        lines
+    }
+
+    fn offsets(lines: &mut MarkedLines) -> Vec<usize> {
+        std::iter::from_fn(|| lines.pop_front()).collect()
+    }
+
+    #[test]
+    fn shifts_marked_lines() {
+        // Text added before every mark moves none of them one by one
+        let mut lines = marked(&[4, 9, 14, 19]);
+        lines.inserted(0, 5);
+        assert_eq!(lines.shift, 5);
+        assert_eq!(lines.offsets, [4, 9, 14, 19]);
+
+        // After the first, which is all there is to move on its side
+        lines.inserted(10, 2);
+        assert_eq!(lines.shift, 7);
+        assert_eq!(lines.offsets, [2, 9, 14, 19]);
+
+        // Before the last, which is moved alone
+        lines.inserted(25, 1);
+        assert_eq!(lines.shift, 7);
+        assert_eq!(offsets(&mut lines), [9, 16, 21, 27]);
+    }
+
+    #[test]
+    fn removes_marked_lines() {
+        // A removed line break takes its mark along
+        let mut lines = marked(&[4, 9, 14, 19]);
+        lines.removed(0..5);
+        assert_eq!(lines.shift, -5);
+        assert_eq!(lines.offsets, [9, 14, 19]);
+
+        // Text removed within a line leaves it marked
+        lines.removed(6..8);
+        assert_eq!(lines.offsets, [11, 14, 19]);
+
+        // As do lines removed from between marks, which all go at once
+        lines.removed(8..13);
+        assert_eq!(offsets(&mut lines), [4, 7]);
+    }
+}
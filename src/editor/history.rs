@@ -0,0 +1,148 @@
+//! Log of a session: every event the editor handled, when it did and the
+//! actions it ran, from which the session can be replayed.
+
+use crate::{
+    editor::{action::Action, Message},
+    error::{Result, SerdeError},
+    keymap, Editor,
+};
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct History {
+    pub started: u64, // Unix time in milliseconds
+    pub entries: Vec<HistoryEntry>,
+    #[serde(skip)]
+    actions: Vec<HistoryNode>, // Run for the event being handled
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub time: u64, // Milliseconds into the session
+    pub event: Event,
+    pub actions: Vec<HistoryNode>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HistoryNode {
+    pub action: Action,
+    pub position: (usize, usize), // Cursor position the action left
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            started: now(),
+            entries: vec![],
+            actions: vec![],
+        }
+    }
+
+    pub fn push_action(&mut self, node: HistoryNode) {
+        self.actions.push(node);
+    }
+
+    /// Records `event` as handled just now, along with the actions pushed
+    /// while it was.
+    pub fn push_event(&mut self, event: Event) {
+        self.entries.push(HistoryEntry {
+            time: now().saturating_sub(self.started),
+            event,
+            actions: std::mem::take(&mut self.actions),
+        });
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let config = ron::ser::PrettyConfig::default();
+        match ron::ser::to_string_pretty(self, config) {
+            Ok(data) => Ok(fs::write(path, data)?),
+            Err(err) => Err(SerdeError::Serialize(err.to_string()).into()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)?;
+
+        match ron::from_str(&data) {
+            Ok(history) => Ok(history),
+            Err(err) => Err(SerdeError::Deserialize(err.to_string()).into()),
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Replays the events of `history` in turn, each once a key is pressed,
+    /// until `Esc` hands the session back.
+    pub fn replay(&mut self, history: &History) -> Result<Message> {
+        let total = history.entries.len();
+        for (i, entry) in history.entries.iter().enumerate() {
+            self.set_message(format!(
+                "Replay {}/{total}: {} (any key steps, Esc stops)",
+                i + 1,
+                describe(&entry.event)
+            ));
+            self.draw_status_bar()?;
+            self.terminal.flush()?;
+
+            loop {
+                match self.terminal.read_event()? {
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Esc, ..
+                    }) => {
+                        self.message.replace(None);
+                        self.draw_status_bar()?;
+                        self.terminal.flush()?;
+                        return Ok(Message::Continue);
+                    }
+                    Event::Key(_) => break,
+                    event @ Event::Resize(..) => {
+                        self.handle_event(&event)?;
+                    }
+                    Event::Mouse(_) => {}
+                }
+            }
+
+            if let Message::Exit = self.handle_event(&entry.event)? {
+                return Ok(Message::Exit);
+            }
+        }
+
+        Ok(Message::Continue)
+    }
+}
+
+/// `event` as shown while replaying, keys in the notation of vim's key codes.
+fn describe(event: &Event) -> String {
+    match event {
+        Event::Key(key) => keymap::format_keys(&[*key]),
+        Event::Mouse(mouse) => format!("{:?} at ({}, {})", mouse.kind, mouse.column, mouse.row),
+        Event::Resize(width, height) => format!("resize to {width}x{height}"),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,174 @@
+use crate::{
+    editor::{action::Message, Mode},
+    error::{Error, Result},
+    keymap,
+    register::{Register, RegisterKind},
+    Editor, Span,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::VecDeque;
+
+impl Editor {
+    /// Starts recording the keys typed into register `name`, as `q{name}`
+    /// does. An uppercase name appends to the register.
+    pub(super) fn record_macro(&mut self, name: char) {
+        if !name.is_ascii_alphanumeric() && name != '"' {
+            self.set_message(Error::InvalidRegister(name));
+            return;
+        }
+
+        self.recorded_keys.clear();
+        self.recording.replace(Some(name));
+    }
+
+    /// Stores the keys recorded so far as text, leaving out the `q` that
+    /// ended the recording.
+    pub(super) fn stop_recording(&mut self) {
+        let name = match self.recording.take() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let mut keys = std::mem::take(&mut self.recorded_keys);
+        keys.pop();
+
+        let register = Register::new(keymap::format_keys(&keys), RegisterKind::Charwise);
+        let result = match name {
+            '"' => self.registers.yank(None, register),
+            name => self.registers.set(name, register),
+        };
+        if let Err(err) = result {
+            self.set_message(err);
+        }
+    }
+
+    /// Plays the keys held in register `name` as though they were typed,
+    /// `count` times over. `@@` plays the last register played again, and
+    /// `@:` repeats the last command line.
+    ///
+    /// Nothing is drawn until playback ends, and any command that fails
+    /// stops it, discarding the rest of the keys.
+    pub(super) fn play_macro(&mut self, name: char) -> Result<Message> {
+        let name = match (name, self.last_macro) {
+            ('@', Some(last)) => last,
+            ('@', None) => {
+                self.set_message("E748: No previously used register");
+                self.failed = true;
+                return Ok(Message::Continue);
+            }
+            (name, _) => name,
+        };
+        let count = self.count.take().unwrap_or(1);
+
+        let text = match self.read_register(Some(name)) {
+            Some(register) => register.text,
+            None => {
+                self.set_message(Error::EmptyRegister(name));
+                self.failed = true;
+                return Ok(Message::Continue);
+            }
+        };
+        self.last_macro = Some(name);
+
+        let keys: Vec<KeyEvent> = match name {
+            ':' => format!(":{text}")
+                .chars()
+                .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+                .chain([KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)])
+                .collect(),
+            _ => keymap::parse_keys(&text),
+        };
+
+        // A macro played from within another goes ahead of whatever is left
+        // of the one playing it, which carries on playing them both
+        if let Some(playback) = self.playback.as_mut() {
+            for _ in 0..count {
+                for key in keys.iter().rev() {
+                    playback.push_front(*key);
+                }
+            }
+
+            return Ok(Message::Continue);
+        }
+
+        let playback = (0..count).flat_map(|_| keys.iter().copied()).collect();
+
+        self.play_keys(playback)
+    }
+
+    /// Runs `keys` as Normal mode commands, once at the cursor or else at the
+    /// start of each of `rows`, as `:normal` does. A command they leave
+    /// unfinished is abandoned, as though `Esc` were typed after them.
+    pub(super) fn normal(&mut self, rows: Option<Span>, keys: &str) -> Result<Message> {
+        let keys: VecDeque<KeyEvent> = keys
+            .chars()
+            .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+            .collect();
+        let rows: Vec<Option<usize>> = match rows {
+            Some(rows) => rows.map(Some).collect(),
+            None => vec![None],
+        };
+
+        for row in rows {
+            if let Some(row) = row {
+                if row >= self.buffer.len() {
+                    break;
+                }
+                self.buffer.position.replace((0, row));
+            }
+
+            if let Message::Exit = self.play_keys(keys.clone())? {
+                return Ok(Message::Exit);
+            }
+
+            if *self.mode.borrow() != Mode::Normal {
+                let escape = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+                if let Message::Exit = self.handle_key_event(escape)? {
+                    return Ok(Message::Exit);
+                }
+            }
+            self.pending = None;
+            self.count = None;
+            self.operator = None;
+            self.register = None;
+        }
+
+        Ok(Message::Continue)
+    }
+
+    /// Plays `keys` on their own, ahead of any others still to be played.
+    fn play_keys(&mut self, keys: VecDeque<KeyEvent>) -> Result<Message> {
+        let outer = self.playback.replace(keys);
+        let suspended = self.terminal.is_suspended();
+        self.terminal.suspend();
+
+        let result = self.play_queued_keys();
+
+        self.playback = outer;
+        if !suspended {
+            self.terminal.resume();
+            self.rerender()?;
+        }
+
+        result
+    }
+
+    fn play_queued_keys(&mut self) -> Result<Message> {
+        while let Some(key) = self.playback.as_mut().and_then(VecDeque::pop_front) {
+            self.failed = false;
+            if let Message::Exit = self.handle_key_event(key)? {
+                return Ok(Message::Exit);
+            }
+
+            if self.failed {
+                self.pending = None;
+                self.count = None;
+                self.operator = None;
+                self.register = None;
+                break;
+            }
+        }
+
+        Ok(Message::Continue)
+    }
+}
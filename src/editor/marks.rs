@@ -0,0 +1,120 @@
+use crate::{
+    error::{CommandError, Error, Result},
+    mark::Marks,
+    motion::Motion,
+    Editor,
+};
+
+impl Editor {
+    /// Sets mark `name` at the cursor, as `m{name}` does. Uppercase marks
+    /// also remember the file they were set in.
+    pub(super) fn set_mark(&mut self, name: char) {
+        if !Marks::is_valid(name) {
+            self.set_message(Error::InvalidMark);
+            self.failed = true;
+            return;
+        }
+
+        let position = *self.buffer.position.borrow();
+        self.buffer.set_mark(name, position);
+        if name.is_ascii_uppercase() {
+            let entry = self.buffer.entry.borrow().clone();
+            self.file_marks.insert(name, (entry, position));
+        }
+    }
+
+    /// Moves to mark `name` by `motion`, first switching to the buffer of the
+    /// file an uppercase mark was set in when another is being edited.
+    pub(super) fn jump_to_mark(&mut self, motion: Motion, name: char) -> Result<()> {
+        let entry = self.buffer.entry.borrow().clone();
+        match self.file_marks.get(&name).cloned() {
+            Some((Some(path), _)) if Some(&path) != entry.as_ref() => {
+                if self.operator.is_some() {
+                    self.operator = None;
+                    self.failed = true;
+                    return Ok(());
+                }
+
+                if let Err(err) = self.edit_other(&path) {
+                    self.set_message(err);
+                    self.failed = true;
+                    return Ok(());
+                }
+
+                return self.move_by(motion);
+            }
+            _ => {}
+        }
+
+        if self.buffer.mark(name).is_none() {
+            self.set_message(CommandError::MarkNotSet);
+            self.operator = None;
+            self.failed = true;
+            return Ok(());
+        }
+
+        let position = *self.buffer.position.borrow();
+        let operating = self.operator.is_some();
+        self.move_by(motion)?;
+        if !operating && *self.buffer.position.borrow() != position {
+            self.buffer.push_jump(position);
+        }
+
+        Ok(())
+    }
+
+    /// Goes back through the jump list, as `Ctrl-o` does.
+    pub(super) fn jump_back(&mut self) -> Result<()> {
+        let count = self.count.take().unwrap_or(1);
+        let position = *self.buffer.position.borrow();
+        match self.buffer.jump_back(position, count) {
+            Some(target) => self.move_to(target),
+            None => {
+                self.failed = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Goes forward through the jump list, as `Ctrl-i` does.
+    pub(super) fn jump_forward(&mut self) -> Result<()> {
+        let count = self.count.take().unwrap_or(1);
+        match self.buffer.jump_forward(count) {
+            Some(target) => self.move_to(target),
+            None => {
+                self.failed = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Updates the file marks set in the buffer being left with where its
+    /// edits have moved them, dropping those deleted along with their line.
+    pub(super) fn store_file_marks(&mut self) {
+        let entry = self.buffer.entry.borrow().clone();
+        let buffer = &self.buffer;
+        self.file_marks.retain(|name, (path, position)| {
+            if *path != entry {
+                return true;
+            }
+
+            match buffer.mark(*name) {
+                Some(mark) => {
+                    *position = mark;
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Sets the file marks belonging to the buffer just opened.
+    pub(super) fn restore_file_marks(&mut self) {
+        let entry = self.buffer.entry.borrow().clone();
+        for (name, (path, position)) in self.file_marks.iter() {
+            if *path == entry {
+                self.buffer.set_mark(*name, *position);
+            }
+        }
+    }
+}
@@ -1,23 +1,66 @@
 use crate::{
-    error::Result,
-    frame_buffer::{FrameBuffer, GUTTER_WIDTH},
+    error::{Error, Result},
+    frame_buffer::{FrameBuffer, Line, GUTTER_WIDTH},
+    motion::Motion,
+    register::Registers,
+    search::Search,
     status_bar::StatusBar,
     terminal::Terminal,
-    Span,
+    text_object::TextObject,
+    util, Span,
 };
 use crossterm::event::{
-    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    rc::Rc,
 };
-use std::{cell::RefCell, rc::Rc};
 
 mod action;
-use action::{Action, HistoryNode, Message};
+mod buffers;
+mod command;
+mod command_line;
+mod global;
+mod history;
+mod macros;
+mod marks;
+mod operator;
+mod repeat;
+mod scroll;
+mod search;
+mod substitute;
+mod undo_tree;
+mod visual;
+use action::Action;
+pub use action::Message;
+use buffers::BufferList;
+use command::Substitute;
+pub use command_line::CommandLine;
+pub use global::MarkedLines;
+pub use history::History;
+pub use operator::Operator;
+use operator::PendingOperator;
+use repeat::ChangeRecord;
+use search::IncrementalSearch;
+use substitute::PendingSubstitution;
+use visual::BlockInsert;
+pub use visual::VisualMode;
+
+/// Rows scrolled by each step of the mouse wheel.
+const MOUSE_SCROLL_ROWS: usize = 3;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Mode {
     Normal,
     Insert,
-    Visual,
+    Visual(VisualMode),
+    Command,
+    Confirm, // Asking whether to make each replacement of `:s///c`
+    UndoTree,
 }
 
 #[derive(Debug)]
@@ -36,42 +79,129 @@ pub struct Editor {
     terminal: Terminal,
     pub buffer: FrameBuffer,
     pub status_bar: StatusBar,
+    buffers: BufferList, // Buffers hidden behind `buffer`
     mode: Rc<RefCell<Mode>>,
-    history: Vec<HistoryNode>,
+    message: Rc<RefCell<Option<String>>>,
+    command_line: Rc<RefCell<CommandLine>>,
+    pending: Option<char>, // Key awaiting the rest of its command, as `g` in `gg`
+    count: Option<usize>,
+    operator: Option<PendingOperator>,
+    last_find: Option<Motion>, // Repeated by `;` and `,`
+    registers: Registers,
+    register: Option<char>, // Register named with `"` for the next command
+    visual_anchor: (usize, usize), // End of the Visual selection the cursor is not at
+    visual_to_end: bool,    // Whether `$` took the selection to the end of each line
+    block_insert: Option<BlockInsert>,
+    changes: ChangeRecord, // Commands making up the last change, for `.`
+    recording: Rc<RefCell<Option<char>>>, // Register a macro is being recorded into
+    recorded_keys: Vec<KeyEvent>,
+    playback: Option<VecDeque<KeyEvent>>, // Keys left to play while a macro plays
+    last_macro: Option<char>,             // Played again by `@@`
+    failed: bool,                         // Set by a command that could not be carried out
+    file_marks: HashMap<char, (Option<PathBuf>, (usize, usize))>, // Uppercase marks, by file
+    last_search: Option<Search>,          // Repeated by `n` and `N`
+    incremental: Option<IncrementalSearch>,
+    search_count: Rc<RefCell<Option<(usize, usize)>>>, // Match landed on, out of how many
+    last_substitute: Option<Substitute>,               // Repeated by `:s` on its own
+    substitution: Option<PendingSubstitution>,
+    in_global: bool, // Whether `:g` is running, which it cannot do within itself
+    scroll_off: usize,
+    backup: bool,              // Whether writes first copy the file to `file~`
+    last_error: Option<Error>, // Last ex command to fail, until taken
+    history: History,
+    undo_tree_selection: usize, // Row selected in the undo tree browser
 }
 
 impl Editor {
     pub fn new(terminal: Terminal, mut buffer: FrameBuffer) -> Self {
-        buffer.viewable_rows = Span {
+        let viewable_rows = Span {
             start: 0,
             end: terminal.size.as_ref().borrow().1 as usize - 2,
         };
+        Self::configure_buffer(&terminal, &mut buffer, viewable_rows);
 
+        let scroll_off = terminal.config().scroll_off;
+        let backup = terminal.config().backup;
         let mode = Rc::new(RefCell::new(Mode::Normal));
+        let message = Rc::new(RefCell::new(None));
+        let command_line = Rc::new(RefCell::new(CommandLine::default()));
+        let recording = Rc::new(RefCell::new(None));
+        let search_count = Rc::new(RefCell::new(None));
         let status_bar = StatusBar::new(
             Rc::clone(&terminal.size),
             Rc::clone(&mode),
-            Rc::clone(&buffer.entry),
-            Rc::clone(&buffer.position),
+            Rc::clone(&message),
+            Rc::clone(&command_line),
+            Rc::clone(&recording),
+            Rc::clone(&search_count),
+            &buffer,
         );
 
         Self {
             terminal,
             buffer,
             status_bar,
+            buffers: BufferList::new(),
             mode,
-            history: vec![],
+            message,
+            command_line,
+            pending: None,
+            count: None,
+            operator: None,
+            last_find: None,
+            registers: Registers::default(),
+            register: None,
+            visual_anchor: (0, 0),
+            visual_to_end: false,
+            block_insert: None,
+            changes: ChangeRecord::default(),
+            recording,
+            recorded_keys: vec![],
+            playback: None,
+            last_macro: None,
+            failed: false,
+            file_marks: HashMap::new(),
+            last_search: None,
+            incremental: None,
+            search_count,
+            last_substitute: None,
+            substitution: None,
+            in_global: false,
+            scroll_off,
+            backup,
+            last_error: None,
+            history: History::new(),
+            undo_tree_selection: 0,
+        }
+    }
+
+    fn configure_buffer(terminal: &Terminal, buffer: &mut FrameBuffer, viewable_rows: Span) {
+        buffer.viewable_rows = viewable_rows;
+        buffer.tab_width = terminal.config().tab_width;
+        if let Some(undo_dir) = &terminal.config().undo_dir {
+            buffer.load_undo_history(&util::expand_home(undo_dir));
         }
     }
 
+    /// Replaces the buffer being edited, pointing the status bar at it.
+    fn open_buffer(&mut self, mut buffer: FrameBuffer) -> Result<()> {
+        let viewable_rows = self.buffer.viewable_rows.clone();
+        Self::configure_buffer(&self.terminal, &mut buffer, viewable_rows);
+
+        self.store_file_marks();
+        self.status_bar.attach(&buffer);
+        self.buffer = buffer;
+        self.restore_file_marks();
+
+        self.rerender()
+    }
+
     pub fn run(&mut self) -> Result<()> {
         loop {
-            let event = event::read()?;
+            let event = self.terminal.read_event()?;
             if let Message::Exit = self.handle_event(&event)? {
                 break;
             }
-
-            self.draw_status_bar()?;
         }
 
         Ok(())
@@ -88,27 +218,40 @@ impl Editor {
 
         self.draw_status_bar()?;
 
-        // TEMP
-        self.execute(Action::Newline)?;
-        self.execute(Action::Write(KeyCode::Char(':')))?;
-        self.execute(Action::Write(KeyCode::Char(')')))?;
-        self.execute(Action::ScrollDown(1))?;
-        // TEMP
-
-        Ok(())
+        self.terminal.flush()
     }
 
-    pub fn format_history(&mut self) -> String {
-        self.history
-            .iter()
-            .map(|node| format!("{node}\n"))
-            .collect()
+    /// Handles `event`, then draws the frame it leaves behind.
+    pub fn handle_event(&mut self, event: &Event) -> Result<Message> {
+        // A message of several lines covers the bottom of the view until
+        // dismissed
+        let message = self.message.replace(None);
+        if message.is_some_and(|message| message.contains('\n')) {
+            self.rerender()?;
+        }
+        self.search_count.replace(None);
+        let message = self.dispatch_event(event);
+        self.history.push_event(*event);
+        if let Message::Exit = message? {
+            return Ok(Message::Exit);
+        }
+
+        self.draw_status_bar()?;
+        self.terminal.flush()?;
+
+        Ok(Message::Continue)
     }
 
     #[inline]
-    pub fn handle_event(&mut self, event: &Event) -> Result<Message> {
+    fn dispatch_event(&mut self, event: &Event) -> Result<Message> {
         match *event {
-            Event::Key(event) => self.handle_key_event(event),
+            Event::Key(event) => {
+                if self.recording.borrow().is_some() {
+                    self.recorded_keys.push(event);
+                }
+
+                self.handle_key_event(event)
+            }
             Event::Mouse(event) => self.handle_mouse_event(event),
             Event::Resize(width, height) => self.handle_resize_event(width, height),
         }
@@ -120,24 +263,207 @@ impl Editor {
         match mode {
             Mode::Normal => self.handle_normal_mode_key_event(event),
             Mode::Insert => self.handle_insert_mode_key_event(event),
-            Mode::Visual => Ok(Message::Continue),
+            Mode::Visual(_) => self.handle_visual_mode_key_event(event),
+            Mode::Command => self.handle_command_mode_key_event(event),
+            Mode::Confirm => self.handle_confirm_key_event(event),
+            Mode::UndoTree => self.handle_undo_tree_key_event(event),
         }
     }
 
     #[inline]
     fn handle_normal_mode_key_event(&mut self, event: KeyEvent) -> Result<Message> {
+        match self.normal_mode_action(event) {
+            Some(action) => self.execute(action),
+            None => Ok(Message::Continue),
+        }
+    }
+
+    /// Action bound to `event` in Normal mode, or `None` when the key only
+    /// begins a command, as a count or `g` does.
+    fn normal_mode_action(&mut self, event: KeyEvent) -> Option<Action> {
+        if let Some(prefix) = self.pending.take() {
+            return Some(Self::pending_action(prefix, event));
+        }
+
+        let pending_operator = self.operator.map(|pending| pending.operator);
         let action = match (event.code, event.modifiers) {
+            (KeyCode::Char(digit @ '0'..='9'), KeyModifiers::NONE)
+                if digit != '0' || self.count.is_some() =>
+            {
+                // SAFETY: matched as a decimal digit
+                let digit = digit.to_digit(10).unwrap() as usize;
+                let count = self.count.unwrap_or(0).saturating_mul(10);
+                self.count = Some(count.saturating_add(digit));
+
+                return None;
+            }
+            (KeyCode::Esc, KeyModifiers::NONE) => {
+                self.count = None;
+                self.operator = None;
+
+                return None;
+            }
+            (KeyCode::Char('d'), KeyModifiers::NONE) => Action::Operator(Operator::Delete),
+            (KeyCode::Char('c'), KeyModifiers::NONE) => Action::Operator(Operator::Change),
+            (KeyCode::Char('y'), KeyModifiers::NONE) => Action::Operator(Operator::Yank),
+            (KeyCode::Char('>'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::Indent)
+            }
+            (KeyCode::Char('<'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::Outdent)
+            }
+            // `guu` and `gUU` apply to the current line
+            (KeyCode::Char('u'), KeyModifiers::NONE)
+                if pending_operator == Some(Operator::Lowercase) =>
+            {
+                Action::Operator(Operator::Lowercase)
+            }
+            (KeyCode::Char('U'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                if pending_operator == Some(Operator::Uppercase) =>
+            {
+                Action::Operator(Operator::Uppercase)
+            }
+            // Within an operator `i` and `a` begin a text object
+            (KeyCode::Char(prefix @ ('i' | 'a')), KeyModifiers::NONE)
+                if self.operator.is_some() =>
+            {
+                self.pending = Some(prefix);
+                return None;
+            }
             (KeyCode::Char('i'), KeyModifiers::NONE) => Action::ChangeMode(Mode::Insert),
+            (KeyCode::Char('a'), KeyModifiers::NONE) => Action::Append,
+            (KeyCode::Char('A'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::AppendLineEnd,
+            (KeyCode::Char('I'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::InsertFirstNonBlank
+            }
+            (KeyCode::Char('v'), KeyModifiers::NONE) => {
+                Action::ChangeMode(Mode::Visual(VisualMode::Charwise))
+            }
+            (KeyCode::Char('V'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::ChangeMode(Mode::Visual(VisualMode::Linewise))
+            }
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+                Action::ChangeMode(Mode::Visual(VisualMode::Blockwise))
+            }
             (KeyCode::Char('c'), KeyModifiers::CONTROL) => Action::Exit,
             (KeyCode::Left | KeyCode::Char('h'), KeyModifiers::NONE) => Action::MoveLeft,
             (KeyCode::Right | KeyCode::Char('l'), KeyModifiers::NONE) => Action::MoveRight,
             (KeyCode::Up | KeyCode::Char('k'), KeyModifiers::NONE) => Action::MoveUp,
             (KeyCode::Down | KeyCode::Char('j'), KeyModifiers::NONE) => Action::MoveDown,
-            (KeyCode::Char('d'), KeyModifiers::NONE) => Action::DeleteCurrent,
+            (KeyCode::Delete | KeyCode::Char('x'), KeyModifiers::NONE) => Action::DeleteCurrent,
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => Action::Save,
+            (KeyCode::Char('o'), KeyModifiers::CONTROL) => Action::JumpBack,
+            (KeyCode::Char('e'), KeyModifiers::CONTROL) => Action::ScrollDown(1),
+            (KeyCode::Char('y'), KeyModifiers::CONTROL) => Action::ScrollUp(1),
+            (KeyCode::Char('d'), KeyModifiers::CONTROL) => Action::ScrollHalfPageDown,
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => Action::ScrollHalfPageUp,
+            (KeyCode::Char('f'), KeyModifiers::CONTROL)
+            | (KeyCode::PageDown, KeyModifiers::NONE) => Action::ScrollPageDown,
+            (KeyCode::Char('b'), KeyModifiers::CONTROL) | (KeyCode::PageUp, KeyModifiers::NONE) => {
+                Action::ScrollPageUp
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) | (KeyCode::Char('i'), KeyModifiers::CONTROL) => {
+                Action::JumpForward
+            }
+            (KeyCode::Char('u'), KeyModifiers::NONE) => Action::Undo,
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => Action::Redo,
+            (KeyCode::Char('U'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::OpenUndoTree,
+            (
+                KeyCode::Char(prefix @ (':' | '/' | '?')),
+                KeyModifiers::NONE | KeyModifiers::SHIFT,
+            ) => Action::OpenCommandLine(prefix),
+            (KeyCode::Char('n'), KeyModifiers::NONE) => Action::SearchNext,
+            (KeyCode::Char('N'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::SearchPrevious
+            }
+            (KeyCode::Char('*'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::SearchWordForward
+            }
+            (KeyCode::Char('#'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::SearchWordBackward
+            }
+            (KeyCode::Char('w'), KeyModifiers::NONE) => Action::MoveWordForward,
+            (KeyCode::Char('b'), KeyModifiers::NONE) => Action::MoveWordBackward,
+            (KeyCode::Char('e'), KeyModifiers::NONE) => Action::MoveWordEnd,
+            (KeyCode::Char('W'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveBigWordForward
+            }
+            (KeyCode::Char('B'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveBigWordBackward
+            }
+            (KeyCode::Char('E'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveBigWordEnd
+            }
+            (KeyCode::Home | KeyCode::Char('0'), KeyModifiers::NONE) => Action::MoveLineStart,
+            (KeyCode::Char('^'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveFirstNonBlank
+            }
+            (KeyCode::End, KeyModifiers::NONE)
+            | (KeyCode::Char('$'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::MoveLineEnd,
+            (KeyCode::Char('G'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::MoveLastLine,
+            (KeyCode::Char('}'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveParagraphForward
+            }
+            (KeyCode::Char('{'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveParagraphBackward
+            }
+            (KeyCode::Char('%'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::MoveMatchingBracket
+            }
+            (KeyCode::Char(';'), KeyModifiers::NONE) => Action::RepeatFind,
+            (KeyCode::Char(','), KeyModifiers::NONE) => Action::RepeatFindReversed,
+            (KeyCode::Char('.'), KeyModifiers::NONE) => Action::RepeatChange,
+            (KeyCode::Char('q'), KeyModifiers::NONE) if self.recording.borrow().is_some() => {
+                Action::StopRecording
+            }
+            (KeyCode::Char('p'), KeyModifiers::NONE) => Action::PutAfter,
+            (KeyCode::Char('P'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::PutBefore,
+            (
+                KeyCode::Char(
+                    prefix @ ('g' | 'z' | 'f' | 't' | 'F' | 'T' | '"' | 'q' | '@' | 'm' | '\''
+                    | '`'),
+                ),
+                modifiers,
+            ) if modifiers == KeyModifiers::NONE || modifiers == KeyModifiers::SHIFT => {
+                self.pending = Some(prefix);
+                return None;
+            }
             _ => Action::None,
         };
 
-        self.execute(action)
+        Some(action)
+    }
+
+    /// Completes a command begun by `prefix`, such as `gg` or `fx`. Any key
+    /// that does not complete it cancels it.
+    fn pending_action(prefix: char, event: KeyEvent) -> Action {
+        let character = match (event.code, event.modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => c,
+            _ => return Action::None,
+        };
+
+        match (prefix, character) {
+            ('g', 'g') => Action::MoveFirstLine,
+            ('g', 'u') => Action::Operator(Operator::Lowercase),
+            ('g', 'U') => Action::Operator(Operator::Uppercase),
+            ('g', '~') => Action::Operator(Operator::ToggleCase),
+            ('z', 't') => Action::ScrollCursorTop,
+            ('z', 'z') => Action::ScrollCursorCenter,
+            ('z', 'b') => Action::ScrollCursorBottom,
+            ('f', c) => Action::FindForward(c),
+            ('t', c) => Action::TillForward(c),
+            ('F', c) => Action::FindBackward(c),
+            ('T', c) => Action::TillBackward(c),
+            ('"', c) if Registers::is_valid(c) => Action::SelectRegister(c),
+            ('m', c) => Action::SetMark(c),
+            ('\'', c) => Action::JumpToMarkLine(c),
+            ('`', c) => Action::JumpToMark(c),
+            ('q', c) => Action::RecordMacro(c),
+            ('@', c) => Action::PlayMacro(c),
+            ('i', c) => TextObject::from_key(c).map_or(Action::None, Action::SelectInner),
+            ('a', c) => TextObject::from_key(c).map_or(Action::None, Action::SelectAround),
+            _ => Action::None,
+        }
     }
 
     #[inline]
@@ -159,37 +485,133 @@ impl Editor {
     }
 
     fn handle_mouse_event(&mut self, event: MouseEvent) -> Result<Message> {
-        if let MouseEventKind::Down(MouseButton::Left) = event.kind {
-            self.buffer
-                .position
-                .replace((event.row as usize, event.row as usize));
-            self.execute(Action::MoveTo(event.column as usize, event.row as usize))?;
-        };
+        // Moving would cut short the change a confirmed substitution makes
+        if let Mode::Confirm = *self.mode.borrow() {
+            return Ok(Message::Continue);
+        }
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let position = (event.column as usize, event.row as usize);
+                let (column, row) = self.buffer_position(position);
+                self.execute(Action::MoveTo(column, row))?;
+            }
+            MouseEventKind::ScrollUp => {
+                self.execute(Action::ScrollUp(MOUSE_SCROLL_ROWS))?;
+            }
+            MouseEventKind::ScrollDown => {
+                self.execute(Action::ScrollDown(MOUSE_SCROLL_ROWS))?;
+            }
+            _ => {}
+        }
 
         Ok(Message::Continue)
     }
 
     fn handle_resize_event(&mut self, width: u16, height: u16) -> Result<Message> {
-        let dy = height as i16 - self.terminal.size.borrow().1 as i16;
-        match dy.is_positive() {
-            true => self.buffer.viewable_rows.end += dy as usize,
-            false => self.buffer.viewable_rows.end -= dy as usize,
-        }
+        // Every row but the status bar's shows the buffer
+        let top = self.buffer.viewable_rows.start;
+        self.buffer.viewable_rows = top..top + (height as usize).saturating_sub(2);
         self.terminal.size.replace((width, height));
+        self.rerender()?;
 
         Ok(Message::Continue)
     }
 
+    /// Writes the buffer to `path`, or to its entry when none is given.
+    ///
+    /// A scratch buffer takes on the path it is first written to, and only
+    /// writing to the buffer's own entry marks it as unmodified.
+    pub fn save(&mut self, path: Option<PathBuf>) -> Result<()> {
+        let entry = self.buffer.entry.borrow().clone();
+        let path = match path.or_else(|| entry.clone()) {
+            Some(path) => path,
+            None => return Err(Error::NoFileName),
+        };
+
+        self.buffer.save(&path, self.backup)?;
+        if entry.is_none() {
+            self.buffer.entry.replace(Some(path.clone()));
+        }
+
+        self.set_message(format!(
+            "\"{}\" {}L written",
+            path.display(),
+            self.buffer.len()
+        ));
+
+        if entry.is_none() || entry.as_ref() == Some(&path) {
+            self.buffer.mark_saved();
+            if let Some(undo_dir) = &self.terminal.config().undo_dir {
+                let undo_dir = util::expand_home(undo_dir);
+                if let Err(err) = self.buffer.save_undo_history(&undo_dir) {
+                    self.set_message(format!("Undo history not saved: {err}"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Error the last ex command to fail reported, since last taken.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.last_error.take()
+    }
+
+    fn set_message<T: ToString>(&mut self, message: T) {
+        self.message.replace(Some(message.to_string()));
+    }
+
     fn draw_status_bar(&mut self) -> Result<()> {
         let rendered_bar = self.status_bar.to_string();
-        let previous_position = self.buffer.position.borrow();
+        let position = *self.buffer.position.borrow();
         let size = self.terminal.size.borrow().1;
 
+        // Lines of a longer message are drawn above the last, shown in place
+        // of the status bar
+        let message = self.message.borrow().clone().unwrap_or_default();
+        let lines: Vec<_> = message.lines().collect();
+        let width = self.terminal.size.borrow().0 as usize;
+        let first_row = (size as usize).saturating_sub(lines.len());
+        for (row, line) in (first_row..).zip(lines.iter().take(lines.len().saturating_sub(1))) {
+            self.terminal.cursor_move_to((0, row))?;
+            self.terminal.write(format!("{line:<width$}"))?;
+        }
+
         self.terminal.cursor_move_to((0, size as usize - 1))?;
         self.terminal.write(&rendered_bar)?;
-        self.terminal
-            .cursor_move_to((previous_position.0 + GUTTER_WIDTH, previous_position.1))?;
+        match *self.mode.borrow() {
+            Mode::Command => {
+                let column = self.command_line.borrow().cursor_column();
+                self.terminal.cursor_move_to((column, size as usize - 1))?;
+            }
+            _ => self
+                .terminal
+                .cursor_move_to(self.screen_position(position))?,
+        }
 
         Ok(())
     }
+
+    /// Maps a buffer `(column, row)` in graphemes to the terminal cell the
+    /// cursor is drawn at.
+    fn screen_position(&self, (column, row): (usize, usize)) -> (usize, usize) {
+        let display_column = self.buffer.display_column(Line::Index(row), column);
+
+        (
+            display_column + GUTTER_WIDTH,
+            row.saturating_sub(self.buffer.viewable_rows.start),
+        )
+    }
+
+    /// Maps a terminal cell back to the buffer `(column, row)` drawn there,
+    /// clamped to the contents of the buffer.
+    fn buffer_position(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let row = (y + self.buffer.viewable_rows.start).min(self.buffer.len().saturating_sub(1));
+        let column = self
+            .buffer
+            .column_at_display(Line::Index(row), x.saturating_sub(GUTTER_WIDTH));
+
+        (column, row)
+    }
 }
@@ -0,0 +1,513 @@
+use crate::{
+    editor::{visual::BlockInsert, Mode},
+    error::Result,
+    frame_buffer::Line,
+    motion::{Motion, MotionKind},
+    register::{Register, RegisterKind},
+    text_object::TextObject,
+    unicode, Editor, Span,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+    Join,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::Delete => "Delete",
+            Self::Change => "Change",
+            Self::Yank => "Yank",
+            Self::Indent => "Indent",
+            Self::Outdent => "Outdent",
+            Self::Lowercase => "Lowercase",
+            Self::Uppercase => "Uppercase",
+            Self::ToggleCase => "Toggle Case",
+            Self::Join => "Join",
+        };
+
+        write!(f, "{message}")
+    }
+}
+
+/// Operator awaiting the motion or text object it applies to, along with
+/// the count typed before it.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingOperator {
+    pub operator: Operator,
+    pub count: Option<usize>,
+}
+
+/// Text an operator applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Region {
+    Chars(Span),                         // Char offsets into the rope
+    Lines(Span),                         // Rows
+    Block { rows: Span, columns: Span }, // Display columns within each row
+}
+
+/// `text` as `operator` changes its case.
+fn convert_case(operator: Operator, text: &str) -> String {
+    match operator {
+        Operator::Lowercase => text.to_lowercase(),
+        Operator::Uppercase => text.to_uppercase(),
+        _ => text
+            .chars()
+            .flat_map(|c| match c.is_lowercase() {
+                true => c.to_uppercase().collect::<Vec<_>>(),
+                false => c.to_lowercase().collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Combines a count typed before an operator with one typed after it, as in
+/// `2d3w`.
+pub fn multiply_counts(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.saturating_mul(second)),
+        (count, None) | (None, count) => count,
+    }
+}
+
+impl Editor {
+    /// Starts `operator`, or applies it to whole lines when it repeats the
+    /// pending one, as `dd` and `>>` do.
+    pub(super) fn begin_operator(&mut self, operator: Operator) -> Result<()> {
+        let mode = *self.mode.borrow();
+        if let Mode::Visual(visual) = mode {
+            return self.operate_on_selection(operator, visual);
+        }
+
+        let count = self.count.take();
+        match self.operator.take() {
+            Some(pending) if pending.operator == operator => {
+                let count = multiply_counts(pending.count, count).unwrap_or(1);
+                let row = self.buffer.position.borrow().1;
+                let end = (row + count).min(self.buffer.len());
+
+                self.apply_operator(operator, Region::Lines(row..end.max(row + 1)))
+            }
+            _ => {
+                self.operator = Some(PendingOperator { operator, count });
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies the pending operator over `motion`, returning false when there
+    /// is none.
+    pub(super) fn operate_over(&mut self, motion: Motion) -> Result<bool> {
+        let pending = match self.operator.take() {
+            Some(pending) => pending,
+            None => return Ok(false),
+        };
+
+        let count = multiply_counts(pending.count, self.count.take());
+        let position = *self.buffer.position.borrow();
+        let on_blank = self
+            .buffer
+            .get(Line::Index(position.1))
+            .and_then(|line| unicode::grapheme_at(&line, position.0).map(str::to_owned))
+            .filter(|grapheme| !grapheme.trim().is_empty())
+            .is_none();
+
+        // `cw` changes up to the end of the word, sparing the blanks after it
+        let (target, kind) = match (pending.operator, motion) {
+            (Operator::Change, Motion::WordForward | Motion::BigWordForward) if !on_blank => {
+                let big = motion == Motion::BigWordForward;
+                let target = self.buffer.change_word_target(position, big, count);
+                (Some(target), MotionKind::Inclusive)
+            }
+            _ => (
+                self.buffer.motion_target(motion, position, count),
+                motion.kind(),
+            ),
+        };
+
+        // A word motion stops at the end of the last line it leaves, so `dw`
+        // on a line's last word keeps the line break
+        let target = match (motion, target) {
+            (Motion::WordForward | Motion::BigWordForward, Some((0, row))) if row > position.1 => {
+                Some((self.buffer.line_len(Line::Index(row - 1)), row - 1))
+            }
+            _ => target,
+        };
+
+        // An operator whose motion fails is dropped altogether
+        match target {
+            Some(target) => {
+                let region = self.motion_region(position, target, kind);
+                self.apply_operator(pending.operator, region)?;
+            }
+            None => self.failed = true,
+        }
+
+        Ok(true)
+    }
+
+    /// Applies the pending operator to `object` at the cursor, as in `ciw`
+    /// or `da(`.
+    pub(super) fn operate_on_object(&mut self, object: TextObject, around: bool) -> Result<()> {
+        let pending = match self.operator.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let position = *self.buffer.position.borrow();
+        match self.buffer.text_object(object, around, position) {
+            Some((chars, true)) => {
+                let start = self.buffer.char_position(chars.start).1;
+                let end = self
+                    .buffer
+                    .char_position(chars.end.max(chars.start + 1) - 1)
+                    .1;
+                self.apply_operator(pending.operator, Region::Lines(start..end + 1))
+            }
+            Some((chars, false)) => self.apply_operator(pending.operator, Region::Chars(chars)),
+            None => {
+                self.failed = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Region covered by moving from `from` to `to` with a motion of `kind`.
+    pub(super) fn motion_region(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+        kind: MotionKind,
+    ) -> Region {
+        let (start, mut end) = match (from.1, from.0) <= (to.1, to.0) {
+            true => (from, to),
+            false => (to, from),
+        };
+
+        if let MotionKind::Linewise = kind {
+            return Region::Lines(start.1..end.1 + 1);
+        }
+
+        // An exclusive motion ending at the start of a line stops at the end
+        // of the line before it instead, and covers whole lines when it
+        // starts within the indent, so `d}` leaves no blank line behind
+        if kind == MotionKind::Exclusive && end.1 > start.1 && end.0 == 0 {
+            if start.0 <= self.buffer.first_non_blank(start.1) {
+                return Region::Lines(start.1..end.1);
+            }
+
+            end = (self.buffer.line_len(Line::Index(end.1 - 1)), end.1 - 1);
+        }
+
+        let start = self.buffer.char_index(start);
+        let end = match kind {
+            MotionKind::Inclusive => {
+                let line_len = self.buffer.line_len(Line::Index(end.1));
+                self.buffer.char_index(((end.0 + 1).min(line_len), end.1))
+            }
+            _ => self.buffer.char_index(end),
+        };
+
+        Region::Chars(start..end)
+    }
+
+    pub(super) fn apply_operator(&mut self, operator: Operator, region: Region) -> Result<()> {
+        let (chars, linewise) = match &region {
+            Region::Chars(chars) => (chars.clone(), false),
+            Region::Lines(rows) => (self.buffer.rows_chars(rows), true),
+            Region::Block { rows, columns } => {
+                return self.apply_block_operator(operator, rows.clone(), columns.clone())
+            }
+        };
+        let start = self.position_of(&region);
+        let text = self.buffer.slice_chars(chars.clone());
+        let kind = match linewise {
+            true => RegisterKind::Linewise,
+            false => RegisterKind::Charwise,
+        };
+
+        // Text that cannot be stored where it was asked to go is left alone
+        let name = self.register.take();
+        if matches!(
+            operator,
+            Operator::Delete | Operator::Change | Operator::Yank
+        ) && !self.store_register(
+            name,
+            Register::new(text.clone(), kind),
+            operator == Operator::Yank,
+        ) {
+            return Ok(());
+        }
+
+        match operator {
+            Operator::Delete => {
+                self.buffer.remove_chars(chars);
+                if linewise {
+                    let row = start.1.min(self.buffer.len().saturating_sub(1));
+                    self.buffer
+                        .position
+                        .replace((self.buffer.first_non_blank(row), row));
+                } else {
+                    self.buffer.position.replace(start);
+                }
+            }
+            Operator::Change => {
+                // Changing lines keeps an empty line to type into
+                let chars = match linewise && !chars.is_empty() {
+                    true => chars.start..chars.end - 1,
+                    false => chars,
+                };
+                self.buffer.remove_chars(chars);
+                self.buffer.position.replace(start);
+                self.change_mode(Mode::Insert);
+            }
+            Operator::Yank => {
+                let lines = text.matches('\n').count();
+                if linewise && lines > 2 {
+                    self.set_message(format!("{lines} lines yanked"));
+                }
+
+                let (column, _) = *self.buffer.position.borrow();
+                let column = match linewise {
+                    true => column.min(self.buffer.line_len(Line::Index(start.1))),
+                    false => start.0,
+                };
+                self.buffer.position.replace((column, start.1));
+            }
+            Operator::Indent | Operator::Outdent => {
+                let rows = self.rows_of(&region);
+                for row in rows.clone() {
+                    match operator {
+                        Operator::Indent => self.indent(row),
+                        _ => self.outdent(row),
+                    }
+                }
+
+                self.buffer
+                    .position
+                    .replace((self.buffer.first_non_blank(rows.start), rows.start));
+            }
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                let converted = convert_case(operator, &text);
+                if converted != text {
+                    self.buffer.remove_chars(chars.clone());
+                    self.buffer.insert_chars(chars.start, &converted);
+                }
+
+                let (column, _) = *self.buffer.position.borrow();
+                self.buffer.position.replace(match linewise {
+                    true => (column, start.1),
+                    false => start,
+                });
+            }
+            Operator::Join => self.join_lines(self.rows_of(&region)),
+        }
+
+        self.rerender()
+    }
+
+    /// Applies `operator` to the display `columns` of each of `rows`, as
+    /// Visual block mode does. Lines too short to reach the block are left
+    /// alone.
+    fn apply_block_operator(
+        &mut self,
+        operator: Operator,
+        rows: Span,
+        columns: Span,
+    ) -> Result<()> {
+        let spans: Vec<Span> = rows
+            .clone()
+            .map(|row| self.block_span(row, &columns))
+            .collect();
+        let text = rows
+            .clone()
+            .zip(&spans)
+            .map(|(row, span)| {
+                let line = self.buffer.get(Line::Index(row)).unwrap_or_default();
+                let start = unicode::grapheme_to_byte(&line, span.start);
+                let end = unicode::grapheme_to_byte(&line, span.end);
+                line[start..end].to_owned()
+            })
+            .collect::<Vec<_>>();
+        let start = (spans[0].start, rows.start);
+
+        let name = self.register.take();
+        if matches!(
+            operator,
+            Operator::Delete | Operator::Change | Operator::Yank
+        ) && !self.store_register(
+            name,
+            Register::new(text.join("\n"), RegisterKind::Blockwise),
+            operator == Operator::Yank,
+        ) {
+            return Ok(());
+        }
+
+        match operator {
+            Operator::Delete | Operator::Change | Operator::Yank => {
+                if operator != Operator::Yank {
+                    for (row, span) in rows.clone().zip(spans) {
+                        self.buffer.line_remove_span(Line::Index(row), span);
+                    }
+                }
+
+                self.buffer.position.replace(start);
+                if operator == Operator::Change {
+                    self.block_insert =
+                        Some(BlockInsert::new(rows, Some(columns.start), false, start));
+                    self.change_mode(Mode::Insert);
+                }
+            }
+            Operator::Indent | Operator::Outdent | Operator::Join => {
+                return self.apply_operator(operator, Region::Lines(rows))
+            }
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                for ((row, span), text) in rows.zip(spans).zip(text) {
+                    let converted = convert_case(operator, &text);
+                    if converted != text {
+                        let column = span.start;
+                        self.buffer.line_remove_span(Line::Index(row), span);
+                        self.buffer
+                            .line_insert_str(Line::Index(row), column, &converted);
+                    }
+                }
+
+                self.buffer.position.replace(start);
+            }
+        }
+
+        self.rerender()
+    }
+
+    /// Graphemes of `row` drawn within the display `columns`, counting any
+    /// that straddle their edges.
+    pub(super) fn block_span(&self, row: usize, columns: &Span) -> Span {
+        let line = Line::Index(row);
+        let line_len = self.buffer.line_len(line);
+        let start = self.buffer.column_at_display(line, columns.start);
+        let end = match columns.end > columns.start {
+            true => self.buffer.column_at_display(line, columns.end - 1) + 1,
+            false => start,
+        };
+
+        start.min(line_len)..end.min(line_len)
+    }
+
+    /// Position at which `region` starts.
+    fn position_of(&self, region: &Region) -> (usize, usize) {
+        match region {
+            Region::Lines(rows) | Region::Block { rows, .. } => (0, rows.start),
+            Region::Chars(chars) => self.buffer.char_position(chars.start),
+        }
+    }
+
+    /// Rows `region` touches.
+    fn rows_of(&self, region: &Region) -> Span {
+        match region {
+            Region::Lines(rows) | Region::Block { rows, .. } => rows.clone(),
+            Region::Chars(chars) => {
+                let start = self.buffer.char_position(chars.start).1;
+                let end = self
+                    .buffer
+                    .char_position(chars.end.max(chars.start + 1) - 1)
+                    .1;
+                start..end + 1
+            }
+        }
+    }
+
+    /// Joins `rows` into one line, or the first of them with the line below
+    /// when there is only one. The line break and leading blanks of each
+    /// joined line become a single space, which is left out after a blank
+    /// or before an empty line or a `)`.
+    fn join_lines(&mut self, rows: Span) {
+        let row = rows.start;
+        let mut column = self.buffer.position.borrow().0;
+        for _ in 1..rows.len().max(2) {
+            if row + 1 >= self.buffer.len() {
+                break;
+            }
+
+            let line = self.buffer.get(Line::Index(row)).unwrap_or_default();
+            let next = self.buffer.get(Line::Index(row + 1)).unwrap_or_default();
+            let trimmed = next.trim_start();
+            let blanks = unicode::byte_to_grapheme(&next, next.len() - trimmed.len());
+            let separator = match line.ends_with([' ', '\t'])
+                || line.is_empty()
+                || trimmed.is_empty()
+                || trimmed.starts_with(')')
+            {
+                true => "",
+                false => " ",
+            };
+
+            column = unicode::grapheme_len(&line);
+            let start = self.buffer.char_index((column, row));
+            let end = self.buffer.char_index((blanks, row + 1));
+            self.buffer.remove_chars(start..end);
+            self.buffer.insert_chars(start, separator);
+        }
+
+        self.buffer.position.replace((column, row));
+    }
+
+    fn indent(&mut self, row: usize) {
+        if !self.buffer.line_is_empty(Line::Index(row)) {
+            self.buffer.line_insert(Line::Index(row), 0, '\t');
+        }
+    }
+
+    /// Removes one tab, or up to a tab's width of spaces, from the start of
+    /// `row`.
+    fn outdent(&mut self, row: usize) {
+        let line = self.buffer.get(Line::Index(row)).unwrap_or_default();
+        let width = match line.starts_with('\t') {
+            true => 1,
+            false => line
+                .chars()
+                .take(self.buffer.tab_width)
+                .take_while(|c| *c == ' ')
+                .count(),
+        };
+
+        if width > 0 {
+            self.buffer.line_remove_span(
+                Line::Index(row),
+                Span {
+                    start: 0,
+                    end: width,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{convert_case, multiply_counts, Operator};
+
+    #[test]
+    fn counts_and_case() {
+        assert_eq!(multiply_counts(Some(2), Some(3)), Some(6));
+        assert_eq!(multiply_counts(None, Some(3)), Some(3));
+        assert_eq!(multiply_counts(None, None), None);
+
+        assert_eq!(
+            convert_case(Operator::ToggleCase, "Hello, Wörld"),
+            "hELLO, wÖRLD"
+        );
+        assert_eq!(convert_case(Operator::Uppercase, "straße"), "STRASSE");
+        assert_eq!(convert_case(Operator::Lowercase, "ABC"), "abc");
+    }
+}
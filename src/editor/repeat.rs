@@ -0,0 +1,180 @@
+use crate::{
+    editor::{action::Action, Mode},
+    error::Result,
+    frame_buffer::Line,
+    Editor, CHAR_MAP,
+};
+use crossterm::event::KeyCode;
+
+/// Part of a change, as `.` replays it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Action(Action, Option<usize>), // Along with the count it was given
+    Insert(String),                // Text typed during an insert session
+}
+
+/// Commands making up the change being made, and the last change to have
+/// been completed.
+///
+/// A change starts with the first command given from rest in Normal mode
+/// and ends once the editor comes back to rest, which for an insert session
+/// is when it is left. It is kept only when it edited the buffer.
+#[derive(Debug, Default)]
+pub struct ChangeRecord {
+    steps: Vec<Step>,
+    last: Vec<Step>,
+    replaying: bool,
+}
+
+impl ChangeRecord {
+    /// Adds `action`, given `count`, to the change being made. Characters
+    /// typed in a row are gathered into a single step.
+    pub fn record(&mut self, action: Action, count: Option<usize>) {
+        if self.replaying {
+            return;
+        }
+
+        let character = match action {
+            Action::Write(code) => CHAR_MAP.get(&code).copied(),
+            Action::Tab => CHAR_MAP.get(&KeyCode::Tab).copied(),
+            Action::Newline => Some('\n'),
+            Action::DeleteLast => {
+                if let Some(Step::Insert(text)) = self.steps.last_mut() {
+                    if text.pop().is_some() {
+                        return;
+                    }
+                }
+
+                None
+            }
+            _ => None,
+        };
+
+        match (character, self.steps.last_mut()) {
+            (Some(character), Some(Step::Insert(text))) => text.push(character),
+            (Some(character), _) => self.steps.push(Step::Insert(character.to_string())),
+            (None, _) => self.steps.push(Step::Action(action, count)),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Ends the change being made, keeping it for `.` when `changed` the
+    /// buffer.
+    pub fn finish(&mut self, changed: bool) {
+        let steps = std::mem::take(&mut self.steps);
+        if changed && !self.replaying {
+            self.last = steps;
+        }
+    }
+
+    /// The last change, with `count` in place of the counts it was given.
+    fn last_with_count(&mut self, count: Option<usize>) -> Vec<Step> {
+        if let Some(count) = count {
+            let mut first = true;
+            for step in self.last.iter_mut() {
+                if let Step::Action(_, step_count) = step {
+                    *step_count = match first {
+                        true => Some(count),
+                        false => None,
+                    };
+                    first = false;
+                }
+            }
+        }
+
+        self.last.clone()
+    }
+}
+
+impl Editor {
+    /// Whether no command is under way, so the next one starts afresh.
+    pub(super) fn at_rest(&self) -> bool {
+        *self.mode.borrow() == Mode::Normal && self.operator.is_none() && self.register.is_none()
+    }
+
+    /// Replays the last change, `count` replacing the count it was made
+    /// with, as `.` does.
+    pub(super) fn repeat_change(&mut self) -> Result<()> {
+        let count = self.count.take();
+        let steps = self.changes.last_with_count(count);
+
+        self.changes.replaying = true;
+        let result = self.replay_steps(&steps);
+        self.changes.replaying = false;
+
+        result
+    }
+
+    fn replay_steps(&mut self, steps: &[Step]) -> Result<()> {
+        for step in steps {
+            match step {
+                Step::Action(action, count) => {
+                    self.count = *count;
+                    self.execute(*action)?;
+                }
+                Step::Insert(text) => self.insert_text(text)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts `text` at the cursor as though it were typed, leaving the
+    /// cursor after it.
+    fn insert_text(&mut self, text: &str) -> Result<()> {
+        let (column, row) = *self.buffer.position.borrow();
+        if self.buffer.is_empty() {
+            self.buffer.append("");
+        }
+
+        self.buffer.line_insert_str(Line::Index(row), column, text);
+        let end = self.buffer.char_index((column, row)) + text.chars().count();
+        self.buffer.position.replace(self.buffer.char_position(end));
+
+        self.rerender()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChangeRecord, Step};
+    use crate::editor::{action::Action, Mode, Operator};
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn gathers_typed_text() {
+        let mut changes = ChangeRecord::default();
+        changes.record(Action::Operator(Operator::Change), Some(2));
+        changes.record(Action::MoveWordForward, None);
+        for character in "foox".chars() {
+            changes.record(Action::Write(KeyCode::Char(character)), None);
+        }
+        changes.record(Action::DeleteLast, None);
+        changes.record(Action::Newline, None);
+        changes.record(Action::ChangeMode(Mode::Normal), None);
+        changes.finish(true);
+
+        let expected = vec![
+            Step::Action(Action::Operator(Operator::Change), Some(2)),
+            Step::Action(Action::MoveWordForward, None),
+            Step::Insert("foo\n".to_owned()),
+            Step::Action(Action::ChangeMode(Mode::Normal), None),
+        ];
+        assert_eq!(changes.last, expected);
+
+        // Commands that leave the buffer alone keep the last change
+        changes.record(Action::Operator(Operator::Yank), None);
+        changes.finish(false);
+        assert_eq!(changes.last, expected);
+
+        let steps = changes.last_with_count(Some(5));
+        assert_eq!(
+            steps[0],
+            Step::Action(Action::Operator(Operator::Change), Some(5))
+        );
+        assert_eq!(steps[1], Step::Action(Action::MoveWordForward, None));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{editor::action::Action, error::Result, frame_buffer::Line, Editor};
+
+impl Editor {
+    /// Scrolls the view `rows` down, or up when negative, for each of the
+    /// count, as `Ctrl-e`, `Ctrl-y` and the mouse wheel do.
+    pub(super) fn scroll_view(&mut self, rows: isize) -> Result<()> {
+        let count = self.count.take().unwrap_or(1) as isize;
+        self.buffer.scroll_by(rows * count, self.scroll_off);
+
+        self.follow_view()
+    }
+
+    /// Scrolls the view and the cursor half a page, or the count in rows, as
+    /// `Ctrl-d` and `Ctrl-u` do. The cursor moves on at either end of the
+    /// buffer, where the view cannot.
+    pub(super) fn scroll_half_page(&mut self, down: bool) -> Result<()> {
+        let rows = self
+            .count
+            .take()
+            .unwrap_or(self.buffer.view_height() / 2)
+            .max(1);
+        let (column, row) = *self.buffer.position.borrow();
+        let last = self.buffer.len().saturating_sub(1);
+        if (down && row >= last) || (!down && row == 0) {
+            self.failed = true;
+            return Ok(());
+        }
+
+        // Scrolling down stops once the last line is in view
+        let top = self.buffer.viewable_rows.start;
+        let (top, row) = match down {
+            true => {
+                let bottom_top = (last + 1).saturating_sub(self.buffer.view_height());
+                (
+                    (top + rows).min(bottom_top.max(top)),
+                    (row + rows).min(last),
+                )
+            }
+            false => (top.saturating_sub(rows), row.saturating_sub(rows)),
+        };
+        self.buffer.scroll_to(top, self.scroll_off);
+        let column = column.min(self.buffer.line_len(Line::Index(row)));
+        self.buffer.position.replace((column, row));
+
+        self.follow_view()
+    }
+
+    /// Scrolls the view a page, keeping two rows of the last one in view, as
+    /// `Ctrl-f` and `Ctrl-b` do.
+    pub(super) fn scroll_page(&mut self, down: bool) -> Result<()> {
+        let count = self.count.take().unwrap_or(1);
+        let page = (self.buffer.view_height().saturating_sub(2).max(1) * count) as isize;
+        let top = self.buffer.viewable_rows.start;
+        match down {
+            true => self.buffer.scroll_by(page, self.scroll_off),
+            false => self.buffer.scroll_by(-page, self.scroll_off),
+        }
+        if self.buffer.viewable_rows.start == top {
+            self.failed = true;
+            return Ok(());
+        }
+
+        self.follow_view()
+    }
+
+    /// Scrolls the cursor's row to the top, middle or bottom of the view, as
+    /// `zt`, `zz` and `zb` do, leaving the cursor where it is.
+    pub(super) fn scroll_cursor(&mut self, action: Action) -> Result<()> {
+        let row = self.buffer.position.borrow().1;
+        let context = self.buffer.scroll_context(self.scroll_off);
+        let height = self.buffer.view_height();
+        let top = match action {
+            Action::ScrollCursorTop => row.saturating_sub(context),
+            Action::ScrollCursorCenter => row.saturating_sub((height - 1) / 2),
+            _ => {
+                let last = self.buffer.len().saturating_sub(1);
+                (row + context.min(last - row) + 1).saturating_sub(height)
+            }
+        };
+        self.buffer.scroll_to(top, self.scroll_off);
+
+        self.rerender()
+    }
+
+    /// Moves the cursor into view after scrolling, on the nearest row clear of
+    /// the context at the edges of the view.
+    fn follow_view(&mut self) -> Result<()> {
+        let (column, row) = *self.buffer.position.borrow();
+        let row = self.buffer.row_in_view(row, self.scroll_off);
+        let column = column.min(self.buffer.line_len(Line::Index(row)));
+        self.buffer.position.replace((column, row));
+
+        self.rerender()
+    }
+}
@@ -0,0 +1,198 @@
+use crate::{
+    editor::{action::Action, operator::multiply_counts, Mode},
+    error::{Error, Result},
+    frame_buffer::Line,
+    motion::MotionKind,
+    search::Search,
+    unicode, Editor,
+};
+use std::ops::Range;
+
+/// Search being typed at a `/` or `?` prompt, previewed as it changes.
+#[derive(Debug)]
+pub struct IncrementalSearch {
+    origin: (usize, usize), // Cursor position the prompt was opened at
+    count: Option<usize>,
+    search: Option<Search>, // Pattern typed so far, once it compiles
+}
+
+impl Editor {
+    /// Starts a search prompt, remembering where to return to if it is left.
+    pub(super) fn begin_search(&mut self) {
+        self.incremental = Some(IncrementalSearch {
+            origin: *self.buffer.position.borrow(),
+            count: self.count,
+            search: None,
+        });
+    }
+
+    /// Moves the cursor to the first match of the pattern typed so far,
+    /// highlighting every match on screen.
+    pub(super) fn update_search(&mut self) -> Result<()> {
+        let incremental = match self.incremental.as_mut() {
+            Some(incremental) => incremental,
+            None => return Ok(()),
+        };
+
+        let command_line = self.command_line.borrow();
+        let forward = command_line.prefix() == '/';
+        let search = Search::new(command_line.text(), forward).ok();
+        drop(command_line);
+
+        let origin = incremental.origin;
+        let count = incremental.count.unwrap_or(1);
+        incremental.search = search.filter(|search| !search.pattern.is_empty());
+
+        let target = incremental
+            .search
+            .as_ref()
+            .and_then(|search| self.buffer.find_match(search, origin, forward, count))
+            .map_or(origin, |found| self.buffer.match_position(&found));
+        self.buffer.position.replace(target);
+
+        self.rerender()
+    }
+
+    /// Leaves the search prompt, putting the cursor back where it was.
+    pub(super) fn end_search(&mut self) -> Result<()> {
+        if let Some(incremental) = self.incremental.take() {
+            self.buffer.position.replace(incremental.origin);
+            self.count = incremental.count;
+            self.rerender()?;
+        }
+
+        Ok(())
+    }
+
+    /// Searches for the pattern entered at a `/` or `?` prompt. An empty
+    /// pattern searches for the last one again, in the prompt's direction.
+    pub(super) fn submit_search(&mut self, prefix: char, pattern: &str) -> Result<()> {
+        let forward = prefix == '/';
+        let search = match (pattern, self.last_search.as_ref()) {
+            ("", Some(last)) => {
+                let mut search = last.clone();
+                search.forward = forward;
+                search
+            }
+            ("", None) => return Err(Error::NoPreviousPattern),
+            (pattern, _) => Search::new(pattern, forward)?,
+        };
+
+        self.registers.set_last_search(&search.pattern);
+        self.last_search = Some(search);
+        self.execute(Action::SearchNext)?;
+
+        Ok(())
+    }
+
+    /// Searches for the word under the cursor, as `*` and `#` do.
+    pub(super) fn search_word(&mut self, forward: bool) -> Result<()> {
+        let position = *self.buffer.position.borrow();
+        let search = match self.buffer.word_at(position) {
+            Some(word) => Search::word(&word, forward)?,
+            None => {
+                self.set_message(Error::NoStringUnderCursor);
+                self.failed = true;
+                self.operator = None;
+                return Ok(());
+            }
+        };
+
+        self.registers.set_last_search(&search.pattern);
+        self.last_search = Some(search);
+        self.search_next(false)
+    }
+
+    /// Moves to the next match of the last search, in the opposite direction
+    /// when `reverse`d, as `n` and `N` do. Operators apply up to the match.
+    pub(super) fn search_next(&mut self, reverse: bool) -> Result<()> {
+        let search = match self.last_search.as_ref() {
+            Some(search) => search,
+            None => {
+                self.set_message(Error::NoPreviousPattern);
+                self.failed = true;
+                self.operator = None;
+                return Ok(());
+            }
+        };
+
+        let forward = search.forward != reverse;
+        let pending = self.operator.take();
+        let count = multiply_counts(pending.and_then(|pending| pending.count), self.count.take());
+        let position = *self.buffer.position.borrow();
+        let found = match self
+            .buffer
+            .find_match(search, position, forward, count.unwrap_or(1))
+        {
+            Some(found) => found,
+            None => {
+                self.set_message(Error::PatternNotFound(search.pattern.clone()));
+                self.failed = true;
+                return Ok(());
+            }
+        };
+
+        self.search_count
+            .replace(Some(self.buffer.search_count(search, &found)));
+        let target = self.buffer.match_position(&found);
+        match pending {
+            Some(pending) => {
+                let region = self.motion_region(position, target, MotionKind::Exclusive);
+                self.apply_operator(pending.operator, region)
+            }
+            None => self.move_to(target),
+        }
+    }
+
+    /// Highlights the matches of the search being typed within view.
+    pub(super) fn draw_search_matches(&mut self) -> Result<()> {
+        let search = match (self.incremental.as_ref(), *self.mode.borrow()) {
+            (Some(incremental), Mode::Command) => incremental.search.as_ref(),
+            _ => None,
+        };
+        let search = match search {
+            Some(search) => search,
+            None => return Ok(()),
+        };
+
+        // A match starting above the view may still reach into it, so the
+        // search starts a screenful above
+        let viewable_rows = &self.buffer.viewable_rows;
+        let height = viewable_rows.end + 1 - viewable_rows.start;
+        let rows = viewable_rows.start.saturating_sub(height)..viewable_rows.end + 1;
+        let first = self.buffer.rows_chars(viewable_rows).start;
+        let matches = self.buffer.matches(search, &rows);
+        for found in matches.iter().filter(|found| found.end >= first) {
+            self.highlight_chars(found)?;
+        }
+
+        Ok(())
+    }
+
+    /// Highlights the part of char range `chars` within view, showing an
+    /// empty range as a single cell.
+    pub(super) fn highlight_chars(&mut self, chars: &Range<usize>) -> Result<()> {
+        let viewable_rows = self.buffer.viewable_rows.clone();
+        let start = self.buffer.char_position(chars.start);
+        let end = self.buffer.char_position(chars.end.max(chars.start + 1));
+        for row in start.1.max(viewable_rows.start)..=end.1.min(viewable_rows.end) {
+            let line_len = match self.buffer.get(Line::Index(row)) {
+                Some(line) => unicode::grapheme_len(&line),
+                None => break,
+            };
+
+            // One past the end of the line stands for its line break
+            let from = match row == start.1 {
+                true => start.0,
+                false => 0,
+            };
+            let to = match row == end.1 {
+                true => end.0,
+                false => line_len + 1,
+            };
+            self.highlight(row, from..to)?;
+        }
+
+        Ok(())
+    }
+}
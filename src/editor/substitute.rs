@@ -0,0 +1,229 @@
+use crate::{
+    editor::{
+        action::Message,
+        command::{LineRange, Substitute},
+        Mode,
+    },
+    error::{Error, Result},
+    search::{Replacement, Search, Substitution},
+    Editor,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    ops::Range,
+};
+
+/// Substitution under way, which when confirming each match waits on a key
+/// for every one of them.
+///
+/// Matches are found before any are replaced, so each is moved along by
+/// however much the replacements ahead of it have changed the text.
+#[derive(Debug)]
+pub struct PendingSubstitution {
+    remaining: VecDeque<Substitution>,
+    shift: isize, // Chars added ahead of the remaining matches
+    replaced: usize,
+    rows: BTreeSet<usize>,      // Rows replacements were made on
+    last_change: Option<usize>, // Char the last replacement was made at
+}
+
+impl PendingSubstitution {
+    fn new(substitutions: Vec<Substitution>) -> Self {
+        Self {
+            remaining: substitutions.into(),
+            shift: 0,
+            replaced: 0,
+            rows: BTreeSet::new(),
+            last_change: None,
+        }
+    }
+
+    /// Chars of the next match as the text now stands.
+    fn current(&self) -> Option<Range<usize>> {
+        let chars = &self.remaining.front()?.chars;
+        let shift = |index: usize| (index as isize + self.shift) as usize;
+
+        Some(shift(chars.start)..shift(chars.end))
+    }
+
+    fn skip(&mut self) {
+        self.remaining.pop_front();
+    }
+}
+
+impl Editor {
+    /// Runs `:s` over `range`, repeating the last substitution when none is
+    /// given. Every replacement it makes is undone together.
+    pub(super) fn substitute(
+        &mut self,
+        range: Option<LineRange>,
+        substitute: Option<Substitute>,
+    ) -> Result<()> {
+        let substitute = substitute
+            .or_else(|| self.last_substitute.clone())
+            .ok_or(Error::NoPreviousPattern)?;
+        let rows = self.resolve_range(range)?;
+
+        // An empty pattern stands for the last one searched for
+        let pattern = match (substitute.pattern.as_str(), self.last_search.as_ref()) {
+            ("", Some(last)) => last.pattern.clone(),
+            ("", None) => return Err(Error::NoPreviousPattern),
+            (pattern, _) => pattern.to_owned(),
+        };
+        let ignore_case = substitute.flags.ignore_case.then_some(true);
+        let search = Search::reusing(self.last_search.as_ref(), &pattern, true, ignore_case)?;
+        let replacement = Replacement::parse(&substitute.replacement);
+        let substitutions =
+            self.buffer
+                .substitutions(&search, &rows, &replacement, substitute.flags.global);
+
+        self.registers.set_last_search(&pattern);
+        self.last_search = Some(search);
+        self.last_substitute = Some(substitute.clone());
+
+        if substitutions.is_empty() {
+            return Err(Error::PatternNotFound(pattern));
+        }
+
+        if substitute.flags.count_only {
+            let rows: BTreeSet<_> = substitutions.iter().map(|found| found.row).collect();
+            self.set_message(format!(
+                "{} on {}",
+                plural(substitutions.len(), "match", "matches"),
+                plural(rows.len(), "line", "lines")
+            ));
+            return Ok(());
+        }
+
+        let mut pending = PendingSubstitution::new(substitutions);
+        if substitute.flags.confirm {
+            self.substitution = Some(pending);
+            self.change_mode(Mode::Confirm);
+            return self.show_substitution();
+        }
+
+        while !pending.remaining.is_empty() {
+            self.replace_next(&mut pending);
+        }
+        self.finish_substitution(pending)
+    }
+
+    /// Answers whether to replace the match shown: `y` replaces it, `n`
+    /// skips it, `a` replaces it along with every match after it and `l`
+    /// replaces it before stopping, as `q` and `Esc` stop straight away.
+    pub(super) fn handle_confirm_key_event(&mut self, event: KeyEvent) -> Result<Message> {
+        let mut pending = match self.substitution.take() {
+            Some(pending) => pending,
+            None => return Ok(Message::Continue),
+        };
+
+        match (event.code, event.modifiers) {
+            (KeyCode::Char('y'), KeyModifiers::NONE) => self.replace_next(&mut pending),
+            (KeyCode::Char('n'), KeyModifiers::NONE) => pending.skip(),
+            (KeyCode::Char('a'), KeyModifiers::NONE) => {
+                while !pending.remaining.is_empty() {
+                    self.replace_next(&mut pending);
+                }
+            }
+            (KeyCode::Char('l'), KeyModifiers::NONE) => {
+                self.replace_next(&mut pending);
+                pending.remaining.clear();
+            }
+            (KeyCode::Char('q'), KeyModifiers::NONE)
+            | (KeyCode::Esc, KeyModifiers::NONE)
+            | (KeyCode::Char('c'), KeyModifiers::CONTROL) => pending.remaining.clear(),
+            _ => {}
+        }
+
+        match pending.remaining.is_empty() {
+            true => {
+                self.change_mode(Mode::Normal);
+                self.finish_substitution(pending)?;
+            }
+            false => {
+                self.substitution = Some(pending);
+                self.show_substitution()?;
+            }
+        }
+
+        Ok(Message::Continue)
+    }
+
+    /// Replaces the next match of `pending`.
+    fn replace_next(&mut self, pending: &mut PendingSubstitution) {
+        let chars = match pending.current() {
+            Some(chars) => chars,
+            None => return,
+        };
+        // SAFETY: there is a current match
+        let substitution = pending.remaining.pop_front().unwrap();
+
+        self.buffer.remove_chars(chars.clone());
+        self.buffer.insert_chars(chars.start, &substitution.text);
+        pending.shift += substitution.text.chars().count() as isize - chars.len() as isize;
+        pending.replaced += 1;
+        pending.rows.insert(substitution.row);
+        pending.last_change = Some(chars.start);
+    }
+
+    /// Moves to the next match awaiting confirmation, showing it highlighted.
+    fn show_substitution(&mut self) -> Result<()> {
+        let (chars, text) = match self.substitution.as_ref() {
+            Some(pending) => match (pending.current(), pending.remaining.front()) {
+                (Some(chars), Some(substitution)) => (chars, substitution.text.clone()),
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        let target = self.buffer.match_position(&chars);
+        self.buffer.position.replace(target);
+        self.rerender()?;
+        self.set_message(format!("replace with {text} (y/n/a/q/l)?"));
+
+        Ok(())
+    }
+
+    /// Highlights the match awaiting confirmation.
+    pub(super) fn draw_substitution(&mut self) -> Result<()> {
+        let chars = match (self.substitution.as_ref(), *self.mode.borrow()) {
+            (Some(pending), Mode::Confirm) => pending.current(),
+            _ => None,
+        };
+
+        match chars {
+            Some(chars) => self.highlight_chars(&chars),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the replacements of `pending` a single change, leaving the
+    /// cursor on the last line changed.
+    fn finish_substitution(&mut self, pending: PendingSubstitution) -> Result<()> {
+        self.buffer.commit();
+        if let Some(index) = pending.last_change {
+            let row = self.buffer.char_position(index).1;
+            let column = self.buffer.first_non_blank(row);
+            self.buffer.position.replace((column, row));
+        }
+        self.rerender()?;
+
+        if pending.replaced > 0 {
+            self.set_message(format!(
+                "{} on {}",
+                plural(pending.replaced, "substitution", "substitutions"),
+                plural(pending.rows.len(), "line", "lines")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    match count {
+        1 => format!("1 {one}"),
+        count => format!("{count} {many}"),
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    editor::{
+        action::{Action, Message},
+        Mode,
+    },
+    error::Result,
+    util, Editor,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Browser listing every undo state, newest first, that jumps to the
+// selected state across branches.
+impl Editor {
+    pub(super) fn handle_undo_tree_key_event(&mut self, event: KeyEvent) -> Result<Message> {
+        let last = self.buffer.undo_tree.nodes().len() - 1;
+        match (event.code, event.modifiers) {
+            (KeyCode::Esc | KeyCode::Char('q'), KeyModifiers::NONE) => {
+                let message = self.execute(Action::ChangeMode(Mode::Normal))?;
+                self.rerender()?;
+
+                return Ok(message);
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let seq = last - self.undo_tree_selection;
+
+                return self.execute(Action::UndoJump(seq));
+            }
+            (KeyCode::Down | KeyCode::Char('j'), KeyModifiers::NONE) => {
+                self.undo_tree_selection = (self.undo_tree_selection + 1).min(last);
+            }
+            (KeyCode::Up | KeyCode::Char('k'), KeyModifiers::NONE) => {
+                self.undo_tree_selection = self.undo_tree_selection.saturating_sub(1);
+            }
+            _ => return Ok(Message::Continue),
+        }
+
+        self.draw_undo_tree()?;
+
+        Ok(Message::Continue)
+    }
+
+    pub(super) fn open_undo_tree(&mut self) -> Result<()> {
+        self.buffer.commit();
+        let tree = &self.buffer.undo_tree;
+        self.undo_tree_selection = tree.nodes().len() - 1 - tree.current();
+        self.change_mode(Mode::UndoTree);
+
+        self.draw_undo_tree()
+    }
+
+    fn draw_undo_tree(&mut self) -> Result<()> {
+        let tree = &self.buffer.undo_tree;
+        let height = self.buffer.viewable_rows.len() + 1;
+        let skip = (self.undo_tree_selection + 1).saturating_sub(height);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let listing: String = tree
+            .nodes()
+            .iter()
+            .rev()
+            .enumerate()
+            .skip(skip)
+            .take(height)
+            .map(|(i, node)| {
+                let selected = match i == self.undo_tree_selection {
+                    true => '>',
+                    false => ' ',
+                };
+                let current = match node.seq == tree.current() {
+                    true => '*',
+                    false => ' ',
+                };
+                let origin = match node.parent {
+                    Some(parent) => format!("from {parent}"),
+                    None => "original".to_owned(),
+                };
+                let edits = node.change.edits.len();
+                let age = format_age(now.saturating_sub(node.time));
+
+                format!(
+                    "{selected}{current} {:>5}  {edits:>4} edits  {age:>8}  {origin}{}",
+                    node.seq,
+                    util::newline()
+                )
+            })
+            .collect();
+
+        self.terminal.cursor_hide()?;
+        self.terminal.clear()?;
+        self.terminal.cursor_reset()?;
+        self.terminal.write(listing)?;
+        self.terminal
+            .cursor_move_to((0, self.undo_tree_selection - skip))?;
+
+        self.terminal.cursor_show()
+    }
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        seconds if seconds < 60 => format!("{seconds}s ago"),
+        seconds if seconds < 60 * 60 => format!("{}m ago", seconds / 60),
+        seconds if seconds < 60 * 60 * 24 => format!("{}h ago", seconds / (60 * 60)),
+        seconds => format!("{}d ago", seconds / (60 * 60 * 24)),
+    }
+}
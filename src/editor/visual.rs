@@ -0,0 +1,469 @@
+use crate::{
+    editor::{
+        action::{Action, Message},
+        operator::{Operator, Region},
+        Mode,
+    },
+    error::Result,
+    frame_buffer::{Line, GUTTER_WIDTH},
+    text_object::TextObject,
+    unicode, Editor, Span,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+/// Shape of the text a Visual mode selection covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum VisualMode {
+    Charwise,  // `v`
+    Linewise,  // `V`
+    Blockwise, // `Ctrl-v`
+}
+
+/// Insert begun on the first row of a block with `I` or `A`, whose text is
+/// copied to the rest of the rows once the insert ends.
+#[derive(Debug, Clone)]
+pub struct BlockInsert {
+    rows: Span,
+    column: Option<usize>, // Display column, or the end of each line
+    append: bool,
+    start: (usize, usize), // Where typing began
+}
+
+impl BlockInsert {
+    pub fn new(rows: Span, column: Option<usize>, append: bool, start: (usize, usize)) -> Self {
+        Self {
+            rows,
+            column,
+            append,
+            start,
+        }
+    }
+}
+
+impl Editor {
+    pub(super) fn handle_visual_mode_key_event(&mut self, event: KeyEvent) -> Result<Message> {
+        let action = match self.pending {
+            Some(_) => None,
+            None => self.visual_mode_action(event),
+        };
+
+        // Everything else moves the cursor as it does in Normal mode,
+        // extending the selection
+        let action = match action {
+            Some(action) => action,
+            None => match self.normal_mode_action(event) {
+                Some(action)
+                    if action.motion().is_some()
+                        || matches!(
+                            action,
+                            Action::MoveTo(..)
+                                | Action::RepeatFind
+                                | Action::RepeatFindReversed
+                                | Action::SearchNext
+                                | Action::SearchPrevious
+                                | Action::SearchWordForward
+                                | Action::SearchWordBackward
+                                | Action::ScrollUp(_)
+                                | Action::ScrollDown(_)
+                                | Action::ScrollHalfPageUp
+                                | Action::ScrollHalfPageDown
+                                | Action::ScrollPageUp
+                                | Action::ScrollPageDown
+                                | Action::ScrollCursorTop
+                                | Action::ScrollCursorCenter
+                                | Action::ScrollCursorBottom
+                                | Action::Operator(_)
+                                | Action::SelectInner(_)
+                                | Action::SelectAround(_)
+                                | Action::SelectRegister(_)
+                                | Action::StopRecording
+                                | Action::PlayMacro(_)
+                        ) =>
+                {
+                    // `$` keeps a block reaching the end of each line until
+                    // the cursor moves along a line again
+                    self.visual_to_end = match action {
+                        Action::MoveLineEnd => true,
+                        Action::MoveUp
+                        | Action::MoveDown
+                        | Action::ScrollUp(_)
+                        | Action::ScrollDown(_)
+                        | Action::ScrollHalfPageUp
+                        | Action::ScrollHalfPageDown
+                        | Action::ScrollPageUp
+                        | Action::ScrollPageDown
+                        | Action::Operator(_)
+                        | Action::SelectRegister(_) => self.visual_to_end,
+                        _ => false,
+                    };
+
+                    action
+                }
+                _ => return Ok(Message::Continue),
+            },
+        };
+
+        // Redrawn whether the selection changed or went away
+        let message = self.execute(action)?;
+        self.rerender()?;
+
+        Ok(message)
+    }
+
+    /// Action bound to `event` in Visual mode alone.
+    fn visual_mode_action(&mut self, event: KeyEvent) -> Option<Action> {
+        let action = match (event.code, event.modifiers) {
+            (KeyCode::Esc, KeyModifiers::NONE) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                self.count = None;
+                Action::ChangeMode(Mode::Normal)
+            }
+            (KeyCode::Char('v'), KeyModifiers::NONE) => {
+                Action::ChangeMode(Mode::Visual(VisualMode::Charwise))
+            }
+            (KeyCode::Char('V'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::ChangeMode(Mode::Visual(VisualMode::Linewise))
+            }
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+                Action::ChangeMode(Mode::Visual(VisualMode::Blockwise))
+            }
+            (KeyCode::Char('o'), KeyModifiers::NONE) => Action::SwapSelectionEnds,
+            (KeyCode::Char(':'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::OpenCommandLine(':')
+            }
+            (KeyCode::Char('d' | 'x'), KeyModifiers::NONE) | (KeyCode::Delete, _) => {
+                Action::Operator(Operator::Delete)
+            }
+            (KeyCode::Char('c' | 's'), KeyModifiers::NONE) => Action::Operator(Operator::Change),
+            (KeyCode::Char('y'), KeyModifiers::NONE) => Action::Operator(Operator::Yank),
+            (KeyCode::Char('>'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::Indent)
+            }
+            (KeyCode::Char('<'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::Outdent)
+            }
+            (KeyCode::Char('~'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::ToggleCase)
+            }
+            (KeyCode::Char('u'), KeyModifiers::NONE) => Action::Operator(Operator::Lowercase),
+            (KeyCode::Char('U'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::Uppercase)
+            }
+            (KeyCode::Char('J'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                Action::Operator(Operator::Join)
+            }
+            (KeyCode::Char('I'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::BlockInsert,
+            (KeyCode::Char('A'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Action::BlockAppend,
+            // `i` and `a` begin a text object to select
+            (KeyCode::Char(prefix @ ('i' | 'a')), KeyModifiers::NONE) => {
+                self.pending = Some(prefix);
+                return Some(Action::None);
+            }
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    /// Starts a `visual` selection at the cursor, switches an existing
+    /// selection to it, or ends the selection when it is already `visual`.
+    pub(super) fn toggle_visual(&mut self, visual: VisualMode) -> Result<()> {
+        let mode = *self.mode.borrow();
+        match mode {
+            Mode::Visual(current) if current == visual => self.change_mode(Mode::Normal),
+            Mode::Visual(_) => self.change_mode(Mode::Visual(visual)),
+            _ => {
+                self.visual_anchor = *self.buffer.position.borrow();
+                self.visual_to_end = false;
+                self.change_mode(Mode::Visual(visual));
+            }
+        }
+
+        self.rerender()
+    }
+
+    pub(super) fn swap_selection_ends(&mut self) -> Result<()> {
+        let position = *self.buffer.position.borrow();
+        let anchor = std::mem::replace(&mut self.visual_anchor, position);
+
+        self.move_to(anchor)
+    }
+
+    /// Sets the `'<` and `'>` marks to the ends of the selection, as it is
+    /// left.
+    pub(super) fn set_visual_marks(&mut self) {
+        let (start, end) = self.selection_ends();
+        self.buffer.set_mark('<', start);
+        self.buffer.set_mark('>', end);
+    }
+
+    /// Ends of the selection in buffer order.
+    fn selection_ends(&self) -> ((usize, usize), (usize, usize)) {
+        let position = *self.buffer.position.borrow();
+        let anchor = self.visual_anchor;
+        match (anchor.1, anchor.0) <= (position.1, position.0) {
+            true => (anchor, position),
+            false => (position, anchor),
+        }
+    }
+
+    /// Display columns a block selection spans, from the leftmost of its
+    /// corners to the far edge of the rightmost, or on to the end of every
+    /// line after `$`.
+    fn selection_columns(&self) -> Span {
+        let edges = |(column, row): (usize, usize)| {
+            let line = Line::Index(row);
+            let start = self.buffer.display_column(line, column);
+            let end = match column < self.buffer.line_len(line) {
+                true => self.buffer.display_column(line, column + 1),
+                false => start + 1,
+            };
+            (start, end)
+        };
+
+        let (anchor_start, anchor_end) = edges(self.visual_anchor);
+        let (cursor_start, cursor_end) = edges(*self.buffer.position.borrow());
+
+        let end = match self.visual_to_end {
+            true => usize::MAX,
+            false => anchor_end.max(cursor_end),
+        };
+
+        anchor_start.min(cursor_start)..end
+    }
+
+    /// Region the selection covers. A charwise selection reaching past the
+    /// end of a line takes its line break along, except on the last line.
+    fn selection(&self, visual: VisualMode) -> Region {
+        let (start, end) = self.selection_ends();
+        match visual {
+            VisualMode::Charwise => {
+                let line_len = self.buffer.line_len(Line::Index(end.1));
+                let end = match end.0 < line_len || end.1 + 1 >= self.buffer.len() {
+                    true => self.buffer.char_index(((end.0 + 1).min(line_len), end.1)),
+                    false => self.buffer.char_index((0, end.1 + 1)),
+                };
+                Region::Chars(self.buffer.char_index(start)..end)
+            }
+            VisualMode::Linewise => Region::Lines(start.1..end.1 + 1),
+            VisualMode::Blockwise => Region::Block {
+                rows: start.1..end.1 + 1,
+                columns: self.selection_columns(),
+            },
+        }
+    }
+
+    /// Applies `operator` to the selection, ending Visual mode.
+    pub(super) fn operate_on_selection(
+        &mut self,
+        operator: Operator,
+        visual: VisualMode,
+    ) -> Result<()> {
+        let region = self.selection(visual);
+        self.count = None;
+        self.change_mode(Mode::Normal);
+
+        self.apply_operator(operator, region)
+    }
+
+    /// Extends the selection over `object` at the cursor, or applies the
+    /// pending operator to it outside of Visual mode.
+    pub(super) fn select_object(&mut self, object: TextObject, around: bool) -> Result<()> {
+        let mode = *self.mode.borrow();
+        let visual = match mode {
+            Mode::Visual(visual) => visual,
+            _ => return self.operate_on_object(object, around),
+        };
+
+        let position = *self.buffer.position.borrow();
+        let (chars, linewise) = match self.buffer.text_object(object, around, position) {
+            Some((chars, linewise)) if !chars.is_empty() => (chars, linewise),
+            _ => {
+                self.failed = true;
+                return Ok(());
+            }
+        };
+
+        let (start, end) = self.selection_ends();
+        let object_start = self.buffer.char_position(chars.start);
+        let object_end = self.buffer.char_position(chars.end - 1);
+        self.visual_anchor = match (start.1, start.0) < (object_start.1, object_start.0) {
+            true if start != end => start,
+            _ => object_start,
+        };
+        let position = match (end.1, end.0) > (object_end.1, object_end.0) {
+            true if start != end => end,
+            _ => object_end,
+        };
+
+        // Objects made of whole lines make the selection linewise
+        if linewise && visual == VisualMode::Charwise {
+            self.change_mode(Mode::Visual(VisualMode::Linewise));
+        }
+
+        self.move_to(position)
+    }
+
+    /// Starts inserting in front of the selection with `I`, or after it with
+    /// `A`. Text typed into the first row of a block or of a linewise
+    /// selection is repeated on the others.
+    pub(super) fn begin_block_insert(&mut self, append: bool) -> Result<()> {
+        let visual = match *self.mode.borrow() {
+            Mode::Visual(visual) => visual,
+            _ => return Ok(()),
+        };
+
+        let (start, end) = self.selection_ends();
+        let rows = start.1..end.1 + 1;
+        let column = match (visual, append) {
+            (VisualMode::Blockwise, false) => Some(self.selection_columns().start),
+            (VisualMode::Blockwise, true) if self.visual_to_end => None,
+            (VisualMode::Blockwise, true) => Some(self.selection_columns().end),
+            (VisualMode::Linewise, false) => Some(0),
+            (VisualMode::Linewise, true) => None,
+            (VisualMode::Charwise, _) => {
+                let position = match append {
+                    true => (end.0 + 1).min(self.buffer.line_len(Line::Index(end.1))),
+                    false => start.0,
+                };
+                let row = match append {
+                    true => end.1,
+                    false => start.1,
+                };
+
+                self.change_mode(Mode::Insert);
+                self.rerender()?;
+                return self.move_to((position, row));
+            }
+        };
+
+        let row = rows.start;
+        let start = match column {
+            Some(column) => (self.column_at_display(row, column), row),
+            None => (self.buffer.line_len(Line::Index(row)), row),
+        };
+        self.block_insert = Some(BlockInsert::new(rows, column, append, start));
+        self.buffer.position.replace(start);
+        self.change_mode(Mode::Insert);
+
+        self.rerender()
+    }
+
+    /// Copies the text typed by a block insert to the rest of its rows. Text
+    /// that spans lines, or that was typed anywhere but after where the
+    /// insert began, is not repeated.
+    pub(super) fn finish_block_insert(&mut self) -> Result<()> {
+        let insert = match self.block_insert.take() {
+            Some(insert) => insert,
+            None => return Ok(()),
+        };
+
+        let (column, row) = *self.buffer.position.borrow();
+        if row != insert.start.1 || column <= insert.start.0 {
+            return Ok(());
+        }
+
+        let line = self.buffer.get(Line::Index(row)).unwrap_or_default();
+        let start = unicode::grapheme_to_byte(&line, insert.start.0);
+        let end = unicode::grapheme_to_byte(&line, column);
+        let text = &line[start..end];
+
+        for row in insert.rows.start + 1..insert.rows.end {
+            let line = Line::Index(row);
+            let line_len = self.buffer.line_len(line);
+            let column = match insert.column {
+                // `I` passes over lines that end before the block, while `A`
+                // pads them out to it
+                Some(column)
+                    if !insert.append && self.buffer.display_column(line, line_len) < column =>
+                {
+                    continue
+                }
+                Some(column) => self.column_at_display(row, column),
+                None => line_len,
+            };
+
+            self.buffer.line_insert_str(line, column, text);
+        }
+
+        self.buffer.position.replace(insert.start);
+
+        self.rerender()
+    }
+
+    /// Grapheme column of `row` drawn at display `column`, counting the
+    /// spaces a line too short to reach it would be padded with.
+    fn column_at_display(&self, row: usize, column: usize) -> usize {
+        let line = Line::Index(row);
+        let line_len = self.buffer.line_len(line);
+        let width = self.buffer.display_column(line, line_len);
+        match column >= width {
+            true => line_len + column - width,
+            false => self.buffer.column_at_display(line, column),
+        }
+    }
+
+    /// Draws the selection over the rendered buffer in reverse video.
+    pub(super) fn draw_selection(&mut self) -> Result<()> {
+        let visual = match *self.mode.borrow() {
+            Mode::Visual(visual) => visual,
+            _ => return Ok(()),
+        };
+
+        let (start, end) = self.selection_ends();
+        let columns = self.selection_columns();
+        let viewable_rows = self.buffer.viewable_rows.clone();
+        for row in start.1.max(viewable_rows.start)..=end.1.min(viewable_rows.end) {
+            let line = match self.buffer.get(Line::Index(row)) {
+                Some(line) => line,
+                None => break,
+            };
+            let line_len = unicode::grapheme_len(&line);
+
+            // Graphemes selected on the row, where one past the end stands
+            // for the line break
+            let span = match visual {
+                VisualMode::Charwise => {
+                    let from = match row == start.1 {
+                        true => start.0,
+                        false => 0,
+                    };
+                    let to = match row == end.1 {
+                        true => end.0 + 1,
+                        false => line_len + 1,
+                    };
+                    from.min(line_len)..to.min(line_len + 1)
+                }
+                VisualMode::Linewise => 0..line_len.max(1),
+                VisualMode::Blockwise => self.block_span(row, &columns),
+            };
+            self.highlight(row, span)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws graphemes `span` of `row` highlighted, where one past the end of
+    /// the line stands for its line break.
+    pub(super) fn highlight(&mut self, row: usize, span: Span) -> Result<()> {
+        let line = match self.buffer.get(Line::Index(row)) {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        let line_len = unicode::grapheme_len(&line);
+        if span.is_empty() || span.start > line_len {
+            return Ok(());
+        }
+
+        let byte = unicode::grapheme_to_byte(&line, span.end.min(line_len));
+        let mut text = unicode::expand_tabs_from(&line[..byte], span.start, self.buffer.tab_width);
+        if span.end > line_len {
+            text.push(' ');
+        }
+
+        let column = self.buffer.display_column(Line::Index(row), span.start);
+        let y = row - self.buffer.viewable_rows.start;
+        self.terminal.cursor_move_to((GUTTER_WIDTH + column, y))?;
+        self.terminal.write_highlighted(text)
+    }
+}
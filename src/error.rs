@@ -10,6 +10,60 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Serde(#[from] SerdeError),
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error("E32: No file name")]
+    NoFileName,
+    #[error("E354: Invalid register name: '{0}'")]
+    InvalidRegister(char),
+    #[error("E353: Nothing in register {0}")]
+    EmptyRegister(char),
+    #[error("E191: Argument must be a letter or forward/backward quote")]
+    InvalidMark,
+    #[error("E383: Invalid search string: {0}")]
+    InvalidPattern(String),
+    #[error("E486: Pattern not found: {0}")]
+    PatternNotFound(String),
+    #[error("E35: No previous regular expression")]
+    NoPreviousPattern,
+    #[error("E348: No string under cursor")]
+    NoStringUnderCursor,
+}
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("E492: Not an editor command: {0}")]
+    NotAnEditorCommand(String),
+    #[error("E16: Invalid range")]
+    InvalidRange,
+    #[error("E20: Mark not set")]
+    MarkNotSet,
+    #[error("E488: Trailing characters: {0}")]
+    TrailingCharacters(String),
+    #[error("E518: Unknown option: {0}")]
+    UnknownOption(String),
+    #[error("E474: Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("E13: File exists (add ! to override)")]
+    FileExists,
+    #[error("E37: No write since last change (add ! to override)")]
+    NoWriteSinceLastChange,
+    #[error("E89: No write since last change for buffer {0} (add ! to override)")]
+    NoWriteSinceLastBufferChange(usize),
+    #[error("E162: No write since last change for buffer \"{0}\"")]
+    HiddenBufferModified(String),
+    #[error("E86: Buffer {0} does not exist")]
+    NoSuchBuffer(usize),
+    #[error("E93: More than one match for {0}")]
+    MultipleMatchingBuffers(String),
+    #[error("E94: No matching buffer for {0}")]
+    NoMatchingBuffer(String),
+    #[error("E146: Regular expressions can't be delimited by letters")]
+    InvalidDelimiter,
+    #[error("E147: Cannot do :global recursive")]
+    GlobalRecursive,
+    #[error("E148: Regular expression missing from :global")]
+    GlobalPatternMissing,
 }
 
 #[derive(Debug, Error)]
@@ -1,8 +1,24 @@
 use crate::{
+    config::DEFAULT_TAB_WIDTH,
     error::{Error, Result},
-    util, Span,
+    line_ending::{self, LineEnding},
+    mark::Marks,
+    undo::{Edit, UndoTree},
+    unicode,
+    util::{self, ContentHash},
+    Span,
+};
+use ropey::{iter::Chars, Rope, RopeBuilder};
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
 };
-use std::{cell::RefCell, fmt::Display, fs, iter, path::PathBuf, rc::Rc};
 
 pub const GUTTER_WIDTH: usize = 5;
 
@@ -14,63 +30,166 @@ pub enum Line {
     Index(usize),
 }
 
+/// Line oriented view over a rope.
+///
+/// Every line stored in `text_buffer` is terminated by a `\n`, so a buffer of
+/// `n` lines holds `n` line breaks and an empty rope holds no lines at all.
 #[derive(Debug, Clone)]
 pub struct FrameBuffer {
-    text_buffer: Vec<String>,
+    text_buffer: Rope,
     pub entry: Rc<RefCell<Option<PathBuf>>>, // Directory entry being edited
-    pub position: Rc<RefCell<(/*column*/ usize, /*row*/ usize)>>,
+    pub position: Rc<RefCell<(/*column*/ usize, /*row*/ usize)>>, // Column in graphemes
     pub viewable_rows: Span,
+    pub tab_width: usize,
+    pub line_ending: Rc<RefCell<LineEnding>>, // Written back in place of each `\n`
+    saved_line_ending: LineEnding,            // Line ending last read or written
+    pub final_newline: bool,                  // Whether the last line is terminated on save
+    pub modified: Rc<RefCell<bool>>,
+    pub undo_tree: UndoTree,
+    pub marks: Marks,
 }
 
 impl FrameBuffer {
     pub fn new(text_buffer: Vec<String>, entry: Option<PathBuf>, viewable_rows: Span) -> Self {
-        let entry = Rc::new(RefCell::new(entry));
-        let position = Rc::new(RefCell::new((0, 0)));
+        let text_buffer = Self::text_buffer_from_lines(text_buffer.iter().map(String::as_str));
 
-        Self {
-            text_buffer,
-            entry,
-            position,
-            viewable_rows,
-        }
+        Self::from_rope(text_buffer, entry, LineEnding::NATIVE, true, viewable_rows)
     }
 
     pub fn try_from_path(path: PathBuf, viewable_rows: Span) -> Result<Self> {
         let data = fs::read_to_string(path.clone())?;
-        let text_buffer = Self::text_buffer_from_str(&data);
+        let line_ending = LineEnding::detect(&data);
+        let (text_buffer, final_newline) = Self::text_buffer_from_str(&data);
 
-        Ok(Self::new(text_buffer, Some(path), viewable_rows))
+        Ok(Self::from_rope(
+            text_buffer,
+            Some(path),
+            line_ending,
+            final_newline,
+            viewable_rows,
+        ))
     }
 
-    fn text_buffer_from_str(data: &str) -> Vec<String> {
-        data.split(util::newline())
-            .map(|line| match line.len() {
-                0 => "".to_owned(),
-                _ => line.to_owned(),
-            })
-            .collect()
+    fn from_rope(
+        text_buffer: Rope,
+        entry: Option<PathBuf>,
+        line_ending: LineEnding,
+        final_newline: bool,
+        viewable_rows: Span,
+    ) -> Self {
+        Self {
+            text_buffer,
+            entry: Rc::new(RefCell::new(entry)),
+            position: Rc::new(RefCell::new((0, 0))),
+            viewable_rows,
+            tab_width: DEFAULT_TAB_WIDTH,
+            line_ending: Rc::new(RefCell::new(line_ending)),
+            saved_line_ending: line_ending,
+            final_newline,
+            modified: Rc::new(RefCell::new(false)),
+            undo_tree: UndoTree::new(),
+            marks: Marks::default(),
+        }
     }
 
-    pub fn save(&self, path: PathBuf) -> Result<()> {
-        fs::write(path, self.format()).map_err(Error::from)
+    /// Normalizes every line ending within `data` to `\n`, returning whether
+    /// the data was terminated by a line ending. Lines typed into empty data
+    /// are terminated, as they are in a new file.
+    fn text_buffer_from_str(data: &str) -> (Rope, bool) {
+        let final_newline = data.is_empty() || data.ends_with(['\n', '\r']);
+        let lines = line_ending::split(data)
+            .filter(|(line, ending)| !(line.is_empty() && ending.is_none()));
+
+        (
+            Self::text_buffer_from_lines(lines.map(|(line, _)| line)),
+            final_newline,
+        )
     }
 
-    pub fn get(&self, line: Line) -> Option<&String> {
-        match line {
-            Line::Previous => self.text_buffer.get(self.position.borrow().1 - 1),
-            Line::Current => self.text_buffer.get(self.position.borrow().1),
-            Line::Next => self.text_buffer.get(self.position.borrow().1 + 1),
-            Line::Index(i) => self.text_buffer.get(i),
+    fn text_buffer_from_lines<'a>(lines: impl Iterator<Item = &'a str>) -> Rope {
+        let mut builder = RopeBuilder::new();
+        lines.for_each(|line| {
+            builder.append(line);
+            builder.append("\n");
+        });
+
+        builder.finish()
+    }
+
+    /// Writes the buffer to `path` without ever leaving it truncated.
+    ///
+    /// The contents are written to a temporary file beside the target, which
+    /// takes over the original's permissions and is then renamed over it.
+    /// When `backup` is set the previous contents are first copied to `path~`.
+    pub fn save(&self, path: &Path, backup: bool) -> Result<()> {
+        // Write through symlinks rather than replacing them
+        let path = match fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => path.to_owned(),
+        };
+        let original = fs::metadata(&path).ok();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_path = path.with_file_name(format!(".{file_name}.{}.tmp", process::id()));
+
+        let write = || -> Result<()> {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+            self.write_to(&mut writer)?;
+            writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .sync_all()?;
+
+            if let Some(metadata) = &original {
+                fs::set_permissions(&temp_path, metadata.permissions())?;
+            }
+
+            if backup && original.is_some() {
+                let mut backup_path = path.clone().into_os_string();
+                backup_path.push("~");
+                fs::copy(&path, backup_path)?;
+            }
+
+            fs::rename(&temp_path, &path).map_err(Error::from)
+        };
+
+        let result = write();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
         }
+
+        result
     }
 
-    pub fn get_mut(&mut self, line: Line) -> Option<&mut String> {
-        match line {
-            Line::Previous => self.text_buffer.get_mut(self.position.borrow().1 - 1),
-            Line::Current => self.text_buffer.get_mut(self.position.borrow().1),
-            Line::Next => self.text_buffer.get_mut(self.position.borrow().1 + 1),
-            Line::Index(i) => self.text_buffer.get_mut(i),
+    /// Streams the buffer to `writer` with its own line ending, terminating
+    /// the last line only when `final_newline` is set.
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        let line_ending = *self.line_ending.borrow();
+        let len = self.text_buffer.len_chars();
+        let end = match self.final_newline {
+            true => len,
+            false => len.saturating_sub(1),
+        };
+
+        for chunk in self.text_buffer.slice(..end).chunks() {
+            match line_ending {
+                LineEnding::Lf => writer.write_all(chunk.as_bytes())?,
+                line_ending => {
+                    writer.write_all(chunk.replace('\n', line_ending.as_str()).as_bytes())?
+                }
+            }
         }
+
+        Ok(())
+    }
+
+    /// Copy of `line`, without its line break.
+    ///
+    /// The rope holds no `String` per line to lend out, so lines are handed
+    /// back owned, and edited in place through the `line_*` methods rather
+    /// than through a mutable borrow.
+    pub fn get(&self, line: Line) -> Option<String> {
+        self.line_chars(self.get_row(line))
+            .map(|range| self.text_buffer.slice(range).to_string())
     }
 
     pub fn get_row(&self, line: Line) -> usize {
@@ -83,23 +202,24 @@ impl FrameBuffer {
     }
 
     pub fn len(&self) -> usize {
-        self.text_buffer.len()
+        self.text_buffer.len_lines() - 1
     }
 
+    /// Length of `line` in grapheme clusters.
     pub fn line_len(&self, line: Line) -> usize {
         match self.get(line) {
-            Some(line) => line.len(),
+            Some(data) => unicode::grapheme_len(&data),
             None => 0,
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.text_buffer.is_empty()
+        self.len() == 0
     }
 
     pub fn line_is_empty(&self, line: Line) -> bool {
-        match self.get(line) {
-            Some(line) => line.is_empty(),
+        match self.line_chars(self.get_row(line)) {
+            Some(range) => range.is_empty(),
             None => true,
         }
     }
@@ -108,115 +228,392 @@ impl FrameBuffer {
         let buffer_len = self.len();
         let row = self.get_row(line);
         if row < buffer_len {
-            self.text_buffer.insert(row, data.to_owned());
+            let start = self.text_buffer.line_to_char(row);
+            self.insert_chars(start, &format!("{data}\n"));
 
             return;
         }
 
-        if row > buffer_len {
-            (buffer_len..row).for_each(|_| self.text_buffer.push("".to_owned()));
-        }
-
-        self.text_buffer.push(data.to_owned());
+        let padding = "\n".repeat(row - buffer_len);
+        self.append(&format!("{padding}{data}"));
     }
 
     pub fn append(&mut self, data: &str) {
-        self.text_buffer.push(data.to_owned());
+        let end = self.text_buffer.len_chars();
+        self.insert_chars(end, &format!("{data}\n"));
     }
 
     pub fn remove(&mut self, line: Line) -> Option<String> {
         let row = self.get_row(line);
-        if row < self.len() {
-            return Some(self.text_buffer.remove(row));
-        }
+        let removed = self.get(Line::Index(row))?;
+        let start = self.text_buffer.line_to_char(row);
+        let end = self.text_buffer.line_to_char(row + 1);
+        self.remove_chars(start..end);
 
-        None
+        Some(removed)
     }
 
     pub fn remove_span(&mut self, span: Span) -> Vec<String> {
         if span.start <= span.end && span.end < self.len() {
-            return self.text_buffer.drain(span).collect();
+            let removed = span
+                .clone()
+                .filter_map(|i| self.get(Line::Index(i)))
+                .collect();
+            let start = self.text_buffer.line_to_char(span.start);
+            let end = self.text_buffer.line_to_char(span.end);
+            self.remove_chars(start..end);
+
+            return removed;
         }
 
         vec![]
     }
 
     pub fn line_insert(&mut self, line: Line, column: usize, character: char) {
-        match self.get_mut(line) {
-            Some(data) => {
-                let line_len = data.len();
-                if column <= line_len {
-                    data.insert(column, character);
-                    return;
-                }
+        let mut buffer = [0; 4];
 
-                let indent: String = iter::repeat(' ').take(column - line_len).collect();
-                data.push_str(&format!("{indent}{character}"));
-            }
-            None => {
-                let indent: String = iter::repeat(' ').take(column).collect();
-                self.insert(line, &format!("{indent}{character}"));
-            }
-        }
+        self.line_insert_str(line, column, character.encode_utf8(&mut buffer))
     }
 
+    /// Inserts `segment` before grapheme `column`, padding the line with
+    /// spaces when the column lies past its end.
     pub fn line_insert_str(&mut self, line: Line, column: usize, segment: &str) {
-        match self.get_mut(line) {
-            Some(data) => {
-                let line_len = data.len();
+        let row = self.get_row(line);
+        match self.line_chars(row) {
+            Some(range) => {
+                let data = self.text_buffer.slice(range.clone()).to_string();
+                let line_len = unicode::grapheme_len(&data);
                 if column <= line_len {
-                    data.insert_str(column, segment);
+                    let byte = unicode::grapheme_to_byte(&data, column);
+                    let index = range.start + data[..byte].chars().count();
+                    self.insert_chars(index, segment);
                     return;
                 }
 
-                let indent: String = iter::repeat(' ').take(column - line_len).collect();
-                data.push_str(&format!("{indent}{segment}"));
+                let indent = " ".repeat(column - line_len);
+                self.insert_chars(range.end, &format!("{indent}{segment}"));
             }
             None => {
-                let indent: String = iter::repeat(' ').take(column).collect();
+                let indent = " ".repeat(column);
                 self.insert(line, &format!("{indent}{segment}"));
             }
         }
     }
 
     pub fn line_append(&mut self, line: Line, character: char) {
-        match self.get_mut(line) {
-            Some(data) => data.push(character),
-            None => self.insert(line, character.to_string().as_str()),
-        }
+        let mut buffer = [0; 4];
+
+        self.line_append_str(line, character.encode_utf8(&mut buffer))
     }
 
     pub fn line_append_str(&mut self, line: Line, segment: &str) {
-        match self.get_mut(line) {
-            Some(data) => data.push_str(segment),
+        match self.line_chars(self.get_row(line)) {
+            Some(range) => self.insert_chars(range.end, segment),
             None => self.insert(line, segment),
         }
     }
 
-    pub fn line_remove(&mut self, line: Line, column: usize) -> Option<char> {
-        if column < self.line_len(line) {
-            return self.get_mut(line).map(|data| data.remove(column));
+    /// Removes the grapheme at `column`, returned as a string since it may
+    /// be made of more than one char.
+    pub fn line_remove(&mut self, line: Line, column: usize) -> Option<String> {
+        self.line_remove_span(
+            line,
+            Span {
+                start: column,
+                end: column + 1,
+            },
+        )
+    }
+
+    /// Removes the graphemes within `span`, truncating it to the line length.
+    pub fn line_remove_span(&mut self, line: Line, mut span: Span) -> Option<String> {
+        let range = self.line_chars(self.get_row(line))?;
+        let data = self.text_buffer.slice(range.clone()).to_string();
+        let len = unicode::grapheme_len(&data);
+        if len == 0 || span.start >= len {
+            return None;
+        }
+
+        if span.end >= len {
+            span.end = len;
         }
 
-        None
+        let start_byte = unicode::grapheme_to_byte(&data, span.start);
+        let end_byte = unicode::grapheme_to_byte(&data, span.end);
+        let start = range.start + data[..start_byte].chars().count();
+        let end = start + data[start_byte..end_byte].chars().count();
+
+        Some(self.remove_chars(start..end))
     }
 
-    pub fn line_remove_span(&mut self, line: Line, mut span: Span) -> Option<String> {
-        match self.get_mut(line) {
+    /// Display column at which grapheme `column` of `line` is drawn.
+    pub fn display_column(&self, line: Line, column: usize) -> usize {
+        match self.get(line) {
             Some(data) => {
-                let len = data.len();
-                if len == 0 || span.start >= len {
-                    return None;
-                }
+                let line_len = unicode::grapheme_len(&data);
+                let clamped = column.min(line_len);
+
+                unicode::grapheme_to_display(&data, clamped, self.tab_width) + (column - clamped)
+            }
+            None => column,
+        }
+    }
+
+    /// Grapheme column of `line` drawn at `display_column`, clamped to the
+    /// line length.
+    pub fn column_at_display(&self, line: Line, display_column: usize) -> usize {
+        match self.get(line) {
+            Some(data) => unicode::display_to_grapheme(&data, display_column, self.tab_width)
+                .min(unicode::grapheme_len(&data)),
+            None => 0,
+        }
+    }
+
+    /// Inserts `text` before char `index` of the rope.
+    ///
+    /// Every edit made to the buffer ends up here or in
+    /// [`Self::remove_chars`].
+    pub fn insert_chars(&mut self, index: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.text_buffer.insert(index, text);
+        self.marks.inserted(index, text.chars().count());
+        self.record(Edit::Insert {
+            index,
+            text: text.to_owned(),
+        });
+    }
+
+    /// Removes and returns the chars of the rope within `range`.
+    pub fn remove_chars(&mut self, range: Range<usize>) -> String {
+        if range.is_empty() {
+            return String::new();
+        }
+
+        let removed = self.text_buffer.slice(range.clone()).to_string();
+        let lines = self.whole_lines(&range);
+        self.text_buffer.remove(range.clone());
+        self.marks.removed(range.clone(), lines);
+        self.record(Edit::Remove {
+            index: range.start,
+            text: removed.clone(),
+        });
+
+        removed
+    }
+
+    /// Chars of the lines lying wholly within `range`, line breaks and all.
+    fn whole_lines(&self, range: &Range<usize>) -> Range<usize> {
+        let row = self.text_buffer.char_to_line(range.start);
+        let start = match self.text_buffer.line_to_char(row) == range.start {
+            true => range.start,
+            false => self.text_buffer.line_to_char(row + 1),
+        };
+        let end = self
+            .text_buffer
+            .line_to_char(self.text_buffer.char_to_line(range.end));
+
+        start..end.max(start)
+    }
 
-                if span.end >= len {
-                    span.end = len;
+    /// Char index of grapheme `column` on `row`, clamped to the end of the
+    /// line and of the rope.
+    pub fn char_index(&self, (column, row): (usize, usize)) -> usize {
+        match self.line_chars(row) {
+            Some(range) => {
+                let data = self.text_buffer.slice(range.clone()).to_string();
+                let byte = unicode::grapheme_to_byte(&data, column);
+
+                range.start + data[..byte].chars().count()
+            }
+            None => self.text_buffer.len_chars(),
+        }
+    }
+
+    /// Grapheme `(column, row)` of char `index`, the inverse of
+    /// [`Self::char_index`].
+    pub fn char_position(&self, index: usize) -> (usize, usize) {
+        let index = index.min(self.text_buffer.len_chars());
+        let row = self.text_buffer.char_to_line(index);
+        let line = self.get(Line::Index(row)).unwrap_or_default();
+        let offset = index - self.text_buffer.line_to_char(row);
+        let byte = line
+            .char_indices()
+            .nth(offset)
+            .map_or(line.len(), |(byte, _)| byte);
+
+        (unicode::byte_to_grapheme(&line, byte), row)
+    }
+
+    /// Char range spanning `rows`, line breaks included.
+    pub fn rows_chars(&self, rows: &Span) -> Range<usize> {
+        let line_to_char = |row: usize| {
+            self.text_buffer
+                .line_to_char(row.min(self.text_buffer.len_lines() - 1))
+        };
+
+        line_to_char(rows.start)..line_to_char(rows.end)
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.text_buffer.len_chars()
+    }
+
+    pub fn char_at(&self, index: usize) -> Option<char> {
+        self.text_buffer.get_char(index)
+    }
+
+    /// Chars of the rope from `index` on, or before it once reversed.
+    pub fn chars_at(&self, index: usize) -> Chars<'_> {
+        self.text_buffer
+            .chars_at(index.min(self.text_buffer.len_chars()))
+    }
+
+    pub fn slice_chars(&self, range: Range<usize>) -> String {
+        self.text_buffer.slice(range).to_string()
+    }
+
+    /// The whole rope, line breaks included.
+    pub fn text(&self) -> String {
+        self.text_buffer.to_string()
+    }
+
+    /// Hash of the buffer exactly as it would be saved.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = ContentHash::new();
+        // Hashing cannot fail
+        self.write_to(&mut hash).unwrap();
+
+        hash.finish()
+    }
+
+    /// Restores the undo history persisted for the buffer's entry within
+    /// `undo_dir`, provided it was saved for the contents now loaded.
+    pub fn load_undo_history(&mut self, undo_dir: &Path) {
+        let entry = self.entry.borrow().clone();
+        let tree = entry
+            .and_then(|entry| UndoTree::file_path(undo_dir, &entry))
+            .and_then(|path| UndoTree::load(&path, self.content_hash()));
+
+        if let Some(tree) = tree {
+            self.undo_tree = tree;
+        }
+    }
+
+    /// Persists the undo history for the buffer's entry within `undo_dir`.
+    pub fn save_undo_history(&self, undo_dir: &Path) -> Result<()> {
+        let entry = self.entry.borrow().clone();
+        match entry.and_then(|entry| UndoTree::file_path(undo_dir, &entry)) {
+            Some(path) => self.undo_tree.save(&path, self.content_hash()),
+            None => Ok(()),
+        }
+    }
+
+    fn record(&mut self, edit: Edit) {
+        let position = *self.position.borrow();
+        self.undo_tree.record(edit, position);
+        self.modified.replace(true);
+    }
+
+    /// Closes the edits made since the last commit into a single undo step,
+    /// returning whether there were any.
+    pub fn commit(&mut self) -> bool {
+        let position = *self.position.borrow();
+        self.undo_tree.commit(position)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.undo_tree.mark_saved();
+        self.saved_line_ending = *self.line_ending.borrow();
+        self.modified.replace(false);
+    }
+
+    /// Writes the buffer back with `line_ending`, which modifies it unless
+    /// it is the one last read or written.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending.replace(line_ending);
+        self.modified.replace(self.is_changed());
+    }
+
+    /// Whether the text or its line ending differs from the last saved.
+    fn is_changed(&self) -> bool {
+        self.undo_tree.is_modified() || *self.line_ending.borrow() != self.saved_line_ending
+    }
+
+    /// Reverts the last change, returning the cursor position preceding it.
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        self.commit();
+        let undo = self.undo_tree.undo();
+
+        self.apply_history(undo)
+    }
+
+    /// Reapplies the last undone change, returning the cursor position
+    /// following it.
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        self.commit();
+        let redo = self.undo_tree.redo();
+
+        self.apply_history(redo)
+    }
+
+    /// Moves to the state following undo node `seq`, on any branch.
+    pub fn undo_jump(&mut self, seq: usize) -> Option<(usize, usize)> {
+        self.commit();
+        let jump = self.undo_tree.jump(seq);
+
+        self.apply_history(jump)
+    }
+
+    fn apply_history(
+        &mut self,
+        history: Option<(Vec<Edit>, (usize, usize))>,
+    ) -> Option<(usize, usize)> {
+        let (edits, position) = history?;
+        for edit in edits {
+            let len = self.text_buffer.len_chars();
+            match edit {
+                Edit::Insert { index, text } if index <= len => {
+                    self.text_buffer.insert(index, &text);
+                    self.marks.inserted(index, text.chars().count());
+                }
+                Edit::Remove { index, text }
+                    if index + text.chars().count() <= len
+                        && self.text_buffer.slice(index..index + text.chars().count()) == text =>
+                {
+                    let range = index..index + text.chars().count();
+                    let lines = self.whole_lines(&range);
+                    self.text_buffer.remove(range.clone());
+                    self.marks.removed(range, lines);
                 }
+                // History that no longer lines up with the text, as a persisted
+                // one might, is dropped rather than trusted
+                _ => {
+                    self.undo_tree = UndoTree::new();
+                    self.modified.replace(true);
 
-                Some(data.drain(span).collect())
+                    return None;
+                }
             }
-            None => None,
         }
+        self.modified.replace(self.is_changed());
+
+        Some(position)
+    }
+
+    /// Char range of `row` within the rope, excluding its line break.
+    fn line_chars(&self, row: usize) -> Option<Range<usize>> {
+        if row >= self.len() {
+            return None;
+        }
+
+        let start = self.text_buffer.line_to_char(row);
+        let end = self.text_buffer.line_to_char(row + 1) - 1;
+
+        Some(start..end)
     }
 
     pub fn format_viewable(&self) -> String {
@@ -229,9 +626,11 @@ impl FrameBuffer {
         };
 
         (view_span.start..=view_span.end)
-            .zip(self.format_span(view_span))
-            .into_iter()
-            .map(|(i, line)| format!("{} {line}", format_number(i + 1)))
+            .zip(self.format_span(view_span, util::newline()))
+            .map(|(i, line)| {
+                let line = unicode::expand_tabs(&line, self.tab_width);
+                format!("{} {line}", format_number(i + 1))
+            })
             .collect()
     }
 
@@ -241,13 +640,19 @@ impl FrameBuffer {
             end: self.len(),
         };
 
-        self.format_span(&span).collect()
+        let line_ending = self.line_ending.borrow().as_str();
+
+        self.format_span(&span, line_ending).collect()
     }
 
-    fn format_span(&self, span: &Span) -> impl Iterator<Item = String> + '_ {
-        (span.start..=span.end).map(|i| match self.get(Line::Index(i)) {
-            Some(line) => format!("{line}{}", util::newline()),
-            None => util::newline().to_owned(),
+    fn format_span<'a>(
+        &'a self,
+        span: &Span,
+        newline: &'a str,
+    ) -> impl Iterator<Item = String> + 'a {
+        (span.start..=span.end).map(move |i| match self.get(Line::Index(i)) {
+            Some(line) => format!("{line}{newline}"),
+            None => newline.to_owned(),
         })
     }
 }
@@ -276,7 +681,7 @@ impl Display for FrameBuffer {
 #[cfg(test)]
 mod test {
     use super::{FrameBuffer, Line};
-    use crate::{error::Result, Span};
+    use crate::{error::Result, line_ending::LineEnding, Span};
     use std::path::PathBuf;
 
     #[test]
@@ -287,10 +692,10 @@ mod test {
         buffer.insert(Line::Index(1), "my name is");
         buffer.insert(Line::Index(3), ":)");
 
-        assert_eq!(buffer.text_buffer[0], "hello world");
-        assert_eq!(buffer.text_buffer[1], "my name is");
-        assert_eq!(buffer.text_buffer[2], "xiu");
-        assert_eq!(buffer.text_buffer[3], ":)");
+        assert_eq!(buffer.get(Line::Index(0)).unwrap(), "hello world");
+        assert_eq!(buffer.get(Line::Index(1)).unwrap(), "my name is");
+        assert_eq!(buffer.get(Line::Index(2)).unwrap(), "xiu");
+        assert_eq!(buffer.get(Line::Index(3)).unwrap(), ":)");
     }
 
     #[test]
@@ -336,4 +741,141 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn remove_span() {
+        let mut buffer = FrameBuffer::new(
+            vec![
+                "a".to_owned(),
+                "b".to_owned(),
+                "c".to_owned(),
+                "d".to_owned(),
+            ],
+            None,
+            Span { start: 0, end: 5 },
+        );
+
+        let removed = buffer.remove_span(Span { start: 1, end: 3 });
+        assert_eq!(removed, vec!["b".to_owned(), "c".to_owned()]);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.get(Line::Index(1)).unwrap(), "d");
+    }
+
+    #[test]
+    fn insert_past_end() {
+        let mut buffer = FrameBuffer::new(vec![], None, Span { start: 0, end: 5 });
+        buffer.line_insert_str(Line::Index(2), 2, "xiu");
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.get(Line::Index(0)).unwrap(), "");
+        assert_eq!(buffer.get(Line::Index(2)).unwrap(), "  xiu");
+    }
+
+    #[test]
+    fn multibyte_editing() {
+        let mut buffer =
+            FrameBuffer::new(vec!["héllo 界".to_owned()], None, Span { start: 0, end: 5 });
+        buffer.line_insert(Line::Current, 2, 'ü');
+        buffer.line_insert_str(Line::Current, 8, "🦀!");
+
+        assert_eq!(buffer.get(Line::Current).unwrap(), "héüllo 界🦀!");
+        assert_eq!(buffer.line_len(Line::Current), 10);
+        assert_eq!(buffer.line_remove(Line::Current, 7), Some("界".to_owned()));
+        assert_eq!(
+            buffer.line_remove_span(Line::Current, Span { start: 1, end: 3 }),
+            Some("éü".to_owned())
+        );
+        assert_eq!(buffer.get(Line::Current).unwrap(), "hllo 🦀!");
+        assert_eq!(buffer.display_column(Line::Current, 6), 7);
+    }
+
+    #[test]
+    fn char_positions() {
+        let buffer = FrameBuffer::new(
+            vec!["héllo".to_owned(), "界!".to_owned()],
+            None,
+            Span { start: 0, end: 5 },
+        );
+
+        assert_eq!(buffer.char_index((1, 1)), 7);
+        assert_eq!(buffer.char_index((9, 0)), 5);
+        assert_eq!(buffer.char_position(7), (1, 1));
+        assert_eq!(buffer.char_position(5), (5, 0));
+        assert_eq!(buffer.rows_chars(&(1..2)), 6..9);
+        assert_eq!(buffer.slice_chars(buffer.rows_chars(&(0..1))), "héllo\n");
+    }
+
+    #[test]
+    fn line_endings() -> Result<()> {
+        let path = std::env::temp_dir().join("cherry_line_endings.txt");
+        std::fs::write(&path, "a\r\nb\r\nc")?;
+        let buffer = FrameBuffer::try_from_path(path.clone(), Span { start: 0, end: 5 })?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(*buffer.line_ending.borrow(), LineEnding::Crlf);
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.get(Line::Index(1)).unwrap(), "b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_round_trip() -> Result<()> {
+        let path = std::env::temp_dir().join("cherry_save_round_trip.txt");
+        let mut backup_path = path.clone().into_os_string();
+        backup_path.push("~");
+        std::fs::write(&path, "a\r\nb\r\nc")?;
+
+        let mut buffer = FrameBuffer::try_from_path(path.clone(), Span { start: 0, end: 5 })?;
+        buffer.line_append_str(Line::Index(2), "d");
+        buffer.save(&path, true)?;
+
+        assert_eq!(std::fs::read_to_string(&path)?, "a\r\nb\r\ncd");
+        assert_eq!(std::fs::read_to_string(&backup_path)?, "a\r\nb\r\nc");
+        std::fs::remove_file(path)?;
+        std::fs::remove_file(backup_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_empty_round_trip() -> Result<()> {
+        let path = std::env::temp_dir().join("cherry_save_empty_round_trip.txt");
+        std::fs::write(&path, "")?;
+
+        let mut buffer = FrameBuffer::try_from_path(path.clone(), Span { start: 0, end: 5 })?;
+        buffer.line_append_str(Line::Index(0), "hello");
+        buffer.save(&path, false)?;
+        assert_eq!(std::fs::read_to_string(&path)?, "hello\n");
+        std::fs::remove_file(&path)?;
+
+        let mut buffer = FrameBuffer::new(vec![], Some(path.clone()), Span { start: 0, end: 5 });
+        buffer.line_append_str(Line::Index(0), "hello");
+        buffer.save(&path, false)?;
+        assert_eq!(std::fs::read_to_string(&path)?, "hello\n");
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut buffer =
+            FrameBuffer::new(vec!["hello".to_owned()], None, Span { start: 0, end: 5 });
+        buffer.line_append_str(Line::Current, " world");
+        buffer.commit();
+        buffer.remove(Line::Current);
+        buffer.commit();
+
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.undo(), Some((0, 0)));
+        assert_eq!(buffer.get(Line::Current).unwrap(), "hello world");
+        assert_eq!(buffer.undo(), Some((0, 0)));
+        assert_eq!(buffer.get(Line::Current).unwrap(), "hello");
+        assert!(!*buffer.modified.borrow());
+        assert_eq!(buffer.undo(), None);
+        buffer.redo();
+        assert_eq!(buffer.get(Line::Current).unwrap(), "hello world");
+        assert!(*buffer.modified.borrow());
+    }
 }
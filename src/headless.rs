@@ -0,0 +1,33 @@
+//! Editing without a terminal, for batch edits: keys from a script and then
+//! ex commands are fed to the editor as though typed, after which whatever
+//! they changed is written.
+
+use crate::{error::Result, keymap, Editor, Message};
+use crossterm::event::Event;
+
+/// Types the keys of `script`, in the notation of vim's key codes, then runs
+/// each of `commands` with or without its leading `:`. Every buffer they
+/// leave modified is written, unless they quit first.
+///
+/// The first ex command to fail, typed or not, ends the run with its error.
+pub fn run(editor: &mut Editor, script: &str, commands: &[String]) -> Result<()> {
+    for key in keymap::parse_keys(script) {
+        let message = editor.handle_event(&Event::Key(key))?;
+        if let Some(err) = editor.take_error() {
+            return Err(err);
+        }
+
+        if let Message::Exit = message {
+            return Ok(());
+        }
+    }
+
+    for command in commands {
+        let command = command.strip_prefix(':').unwrap_or(command);
+        if let Message::Exit = editor.run_command(command)? {
+            return Ok(());
+        }
+    }
+
+    editor.save_modified()
+}
@@ -1,4 +1,4 @@
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
@@ -116,3 +116,157 @@ lazy_static! {
        KeyCode::Tab => '\t'
     ]);
 }
+
+/// Names of the keys written as `<Name>`, as vim writes them.
+const KEY_NAMES: [(&str, KeyCode); 15] = [
+    ("lt", KeyCode::Char('<')),
+    ("Esc", KeyCode::Esc),
+    ("CR", KeyCode::Enter),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Tab", KeyCode::Tab),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+];
+
+/// Writes `events` as register text, in the notation of vim's key codes:
+/// characters stand for themselves, while other keys and modified ones are
+/// written as `<Esc>` or `<C-r>`.
+pub fn format_keys(events: &[KeyEvent]) -> String {
+    events.iter().map(format_key).collect()
+}
+
+fn format_key(event: &KeyEvent) -> String {
+    let name = match event.code {
+        KeyCode::Char(c) if c != '<' => c.to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        code => match KEY_NAMES.iter().find(|(_, key)| *key == code) {
+            Some((name, _)) => name.to_string(),
+            None => return String::new(),
+        },
+    };
+
+    let mut modifiers = String::new();
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        modifiers.push_str("C-");
+    }
+    if event.modifiers.contains(KeyModifiers::ALT) {
+        modifiers.push_str("M-");
+    }
+    // Shifted characters are already written in their case
+    if event.modifiers.contains(KeyModifiers::SHIFT) && !matches!(event.code, KeyCode::Char(_)) {
+        modifiers.push_str("S-");
+    }
+
+    match name.chars().count() == 1 && modifiers.is_empty() {
+        true => name,
+        false => format!("<{modifiers}{name}>"),
+    }
+}
+
+/// Reads register text written by [`format_keys`] back into key events. A
+/// line break is read as `<CR>`, and a `<` that begins no key name stands
+/// for itself.
+pub fn parse_keys(text: &str) -> Vec<KeyEvent> {
+    let mut events = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let parsed = match c {
+            '<' => rest
+                .find('>')
+                .and_then(|end| parse_key_name(&rest[1..end]).map(|event| (event, end + 1))),
+            _ => None,
+        };
+
+        match parsed {
+            Some((event, len)) => {
+                events.push(event);
+                rest = &rest[len..];
+            }
+            None => {
+                let code = match c {
+                    '\n' => KeyCode::Enter,
+                    '\t' => KeyCode::Tab,
+                    c => KeyCode::Char(c),
+                };
+                events.push(KeyEvent::new(code, KeyModifiers::NONE));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    events
+}
+
+/// Key written as `name` within `<` and `>`, such as `Esc` or `C-r`.
+fn parse_key_name(name: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = name;
+    while let Some((prefix, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+        modifiers |= match prefix {
+            "C" | "c" => KeyModifiers::CONTROL,
+            "M" | "m" | "A" | "a" => KeyModifiers::ALT,
+            "S" | "s" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        name = rest;
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        // A lone character only needs brackets to go with a modifier
+        (Some(c), None) if modifiers != KeyModifiers::NONE => KeyCode::Char(c),
+        (Some(_), None) => return None,
+        _ => match KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        {
+            Some((_, code)) => *code,
+            None => KeyCode::F(name.strip_prefix(['F', 'f'])?.parse().ok()?),
+        },
+    };
+
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_keys, parse_keys};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn key_notation() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let events = vec![
+            key(KeyCode::Char('d'), KeyModifiers::NONE),
+            key(KeyCode::Char('W'), KeyModifiers::SHIFT),
+            key(KeyCode::Char('<'), KeyModifiers::NONE),
+            key(KeyCode::Esc, KeyModifiers::NONE),
+            key(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            key(KeyCode::Left, KeyModifiers::SHIFT),
+        ];
+
+        let text = format_keys(&events);
+        assert_eq!(text, "dW<lt><Esc><C-r><S-Left>");
+        assert_eq!(parse_keys(&text), events);
+
+        assert_eq!(
+            parse_keys("<b>\n<cr>"),
+            vec![
+                key(KeyCode::Char('<'), KeyModifiers::NONE),
+                key(KeyCode::Char('b'), KeyModifiers::NONE),
+                key(KeyCode::Char('>'), KeyModifiers::NONE),
+                key(KeyCode::Enter, KeyModifiers::NONE),
+                key(KeyCode::Enter, KeyModifiers::NONE),
+            ]
+        );
+        assert_eq!(parse_keys("<x-y>").len(), 5);
+    }
+}
@@ -1,18 +1,31 @@
 #![allow(dead_code)]
 
+pub mod backend;
 mod config;
 mod editor;
 pub mod error;
 mod frame_buffer;
+pub mod headless;
 mod keymap;
+mod line_ending;
+mod mark;
+mod motion;
+mod register;
+mod screen;
+mod search;
 mod status_bar;
 mod terminal;
-mod util;
+mod text_object;
+mod undo;
+mod unicode;
+pub mod util;
+mod viewport;
 
 pub use config::Config;
-pub use editor::Editor;
+pub use editor::{Editor, History, Message};
 pub use frame_buffer::FrameBuffer;
 pub(crate) use keymap::CHAR_MAP;
+pub use screen::Style;
 pub use status_bar::StatusBar;
 pub use terminal::Terminal;
 
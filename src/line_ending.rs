@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    #[cfg(windows)]
+    pub const NATIVE: Self = Self::Crlf;
+    #[cfg(not(windows))]
+    pub const NATIVE: Self = Self::Lf;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+
+    /// Most frequent line ending within `data`, falling back to the native
+    /// ending when there are none.
+    ///
+    /// Files mixing several endings are normalized to the dominant one, ties
+    /// going to LF, then CRLF.
+    pub fn detect(data: &str) -> Self {
+        let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+        split(data).for_each(|(_, ending)| match ending {
+            Some(Self::Lf) => lf += 1,
+            Some(Self::Crlf) => crlf += 1,
+            Some(Self::Cr) => cr += 1,
+            None => {}
+        });
+
+        match (lf, crlf, cr) {
+            (0, 0, 0) => Self::NATIVE,
+            (lf, crlf, cr) if lf >= crlf && lf >= cr => Self::Lf,
+            (_, crlf, cr) if crlf >= cr => Self::Crlf,
+            _ => Self::Cr,
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+            Self::Cr => "CR",
+        };
+
+        write!(f, "{message}")
+    }
+}
+
+/// Splits `data` into lines on any of LF, CRLF or CR, yielding each line
+/// along with the ending that terminated it.
+pub fn split(data: &str) -> impl Iterator<Item = (&str, Option<LineEnding>)> {
+    let mut remainder = Some(data);
+
+    std::iter::from_fn(move || {
+        let data = remainder?;
+        match data.find(['\n', '\r']) {
+            Some(i) if data[i..].starts_with("\r\n") => {
+                remainder = Some(&data[i + 2..]);
+                Some((&data[..i], Some(LineEnding::Crlf)))
+            }
+            Some(i) => {
+                let ending = match &data[i..=i] {
+                    "\n" => LineEnding::Lf,
+                    _ => LineEnding::Cr,
+                };
+                remainder = Some(&data[i + 1..]);
+                Some((&data[..i], Some(ending)))
+            }
+            None => {
+                remainder = None;
+                Some((data, None))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split, LineEnding};
+
+    #[test]
+    fn detect() {
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\rb\r"), LineEnding::Cr);
+        assert_eq!(LineEnding::detect("a\r\nb\nc\r\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a"), LineEnding::NATIVE);
+    }
+
+    #[test]
+    fn split_mixed() {
+        let lines: Vec<&str> = split("a\r\nb\nc\rd").map(|(line, _)| line).collect();
+
+        assert_eq!(lines, vec!["a", "b", "c", "d"]);
+    }
+}
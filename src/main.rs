@@ -1,51 +1,107 @@
 #![deny(unsafe_code)]
 
 use cherry::{
+    backend::MemoryBackend,
     error::{Error, Result, SerdeError},
-    Config, Editor, FrameBuffer, Span, Terminal,
+    headless, util, Config, Editor, FrameBuffer, History, Message, Span, Terminal,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
 };
-use std::{fs, path::PathBuf};
 use structopt::StructOpt;
 
 const DEFAULT_CONFIG: &str = "~/.config/cherry/config.ron";
+const HISTORY_LOG: &str = "log/history.ron";
+
+/// Size of the screen a headless editor draws to, which nothing shows.
+const HEADLESS_SIZE: (u16, u16) = (80, 24);
 
 #[derive(Debug, StructOpt)]
 struct Options {
     #[structopt(short, long, parse(from_os_str), about = "Alternate config path")]
     pub config: Option<PathBuf>,
-    #[structopt(parse(from_os_str), about = "Entry to be edited")]
-    pub path: Option<PathBuf>,
+    #[structopt(long, about = "Edit without a terminal, then write and exit")]
+    pub headless: bool,
+    #[structopt(
+        short,
+        long,
+        parse(from_os_str),
+        requires = "headless",
+        about = "Keys to type when headless"
+    )]
+    pub script: Option<PathBuf>,
+    #[structopt(
+        short = "e",
+        long = "command",
+        number_of_values = 1,
+        requires = "headless",
+        about = "Ex command to run when headless, after the script"
+    )]
+    pub commands: Vec<String>,
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with = "headless",
+        about = "History log of a session to step through"
+    )]
+    pub replay: Option<PathBuf>,
+    #[structopt(parse(from_os_str), about = "Entries to be edited, each in a buffer")]
+    pub paths: Vec<PathBuf>,
 }
 
 fn main() -> Result<()> {
     let options = Options::from_args();
-    let config = {
-        let path = match options.config {
-            Some(path) => path,
-            None => PathBuf::from(DEFAULT_CONFIG),
+    let default_config = util::expand_home(Path::new(DEFAULT_CONFIG));
+    let config = match options.config {
+        Some(path) => load_config(path)?,
+        // Batch edits need not be set up for a terminal
+        None if options.headless && !default_config.exists() => Config::default(),
+        None => load_config(default_config)?,
+    };
+
+    if options.headless {
+        let (width, height) = HEADLESS_SIZE;
+        let backend = MemoryBackend::new(width, height);
+        let terminal = Terminal::with_backend(config, Box::new(backend))?;
+        let mut editor = open_editor(terminal, options.paths, height)?;
+        let script = match options.script {
+            Some(path) => fs::read_to_string(path)?,
+            None => String::new(),
         };
 
-        load_config(path)
-    }?;
+        let result = headless::run(&mut editor, &script, &options.commands);
+        drop(editor);
+        if let Err(err) = result {
+            eprintln!("{err}");
+            process::exit(1);
+        }
 
-    let terminal = Terminal::new(config)?;
-    let view_span = Span {
-        start: 0,
-        end: terminal.size()?.1 as usize,
-    };
-    let buffer = match options.path {
-        Some(path) => FrameBuffer::try_from_path(path, view_span)?,
-        None => FrameBuffer::new(vec![], None, view_span),
+        return Ok(());
+    }
+
+    let replay = match options.replay {
+        Some(path) => Some(History::load(&path)?),
+        None => None,
     };
 
-    let mut editor = Editor::new(terminal, buffer);
+    let terminal = Terminal::new(config)?;
+    let height = terminal.size()?.1;
+
+    let mut editor = open_editor(terminal, options.paths, height)?;
     editor.initialize()?;
-    editor.run()?;
+    let message = match replay {
+        Some(history) => editor.replay(&history)?,
+        None => Message::Continue,
+    };
+    if let Message::Continue = message {
+        editor.run()?;
+    }
 
     let buffer = editor.buffer.clone();
 
-    editor.buffer.save(PathBuf::from("log/file.log"))?;
-    fs::write("log/history.log", editor.format_history())?;
+    editor.history().save(Path::new(HISTORY_LOG))?;
     drop(editor);
 
     println!("{:#?}", buffer);
@@ -53,6 +109,31 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Editor editing the first of `paths`, with the rest opened hidden.
+fn open_editor(terminal: Terminal, paths: Vec<PathBuf>, height: u16) -> Result<Editor> {
+    let mut paths = paths.into_iter();
+    let buffer = open_buffer(paths.next(), height)?;
+    let mut editor = Editor::new(terminal, buffer);
+    for path in paths {
+        editor.add_buffer(open_buffer(Some(path), height)?);
+    }
+
+    Ok(editor)
+}
+
+fn open_buffer(path: Option<PathBuf>, height: u16) -> Result<FrameBuffer> {
+    let view_span = Span {
+        start: 0,
+        end: height as usize,
+    };
+
+    // A file that does not exist yet is created when first written
+    match path {
+        Some(path) if path.exists() => FrameBuffer::try_from_path(path, view_span),
+        path => Ok(FrameBuffer::new(vec![], path, view_span)),
+    }
+}
+
 fn load_config(path: PathBuf) -> Result<Config> {
     let contents = std::fs::read_to_string(path)?;
 
@@ -0,0 +1,233 @@
+//! Marks and the jump list of a [`FrameBuffer`], kept as char offsets so
+//! that they move along with the text they were set on as it is edited.
+
+use crate::{editor::MarkedLines, frame_buffer::FrameBuffer, Span};
+use std::collections::HashMap;
+
+/// Mark left where the cursor jumped from, returned to by `''`.
+pub const CONTEXT_MARK: char = '\'';
+
+/// Mark set at the last change made to the buffer.
+pub const CHANGE_MARK: char = '.';
+
+#[derive(Debug, Clone, Default)]
+pub struct Marks {
+    named: HashMap<char, usize>,
+    jumps: Vec<usize>,
+    jump_index: usize,  // Into `jumps`, at its end unless moving through it
+    lines: MarkedLines, // Line breaks of the lines `:g` has yet to visit
+}
+
+impl Marks {
+    /// Moves marks at or after `index` along by the `len` chars inserted
+    /// there.
+    pub fn inserted(&mut self, index: usize, len: usize) {
+        for offset in self.offsets_mut() {
+            if *offset >= index {
+                *offset += len;
+            }
+        }
+        self.lines.inserted(index, len);
+
+        self.named.insert(CHANGE_MARK, index);
+    }
+
+    /// Moves marks after `range` back over the chars removed from it. Letter
+    /// marks on the `lines` removed whole go with them, and the rest of the
+    /// marks within it end up where it began.
+    pub fn removed(&mut self, range: Span, lines: Span) {
+        self.named
+            .retain(|name, offset| !(name.is_ascii_alphabetic() && lines.contains(offset)));
+        for offset in self.offsets_mut() {
+            if *offset >= range.end {
+                *offset -= range.len();
+            } else if *offset > range.start {
+                *offset = range.start;
+            }
+        }
+        self.lines.removed(range.clone());
+
+        self.named.insert(CHANGE_MARK, range.start);
+    }
+
+    fn offsets_mut(&mut self) -> impl Iterator<Item = &mut usize> {
+        self.named.values_mut().chain(self.jumps.iter_mut())
+    }
+
+    /// Whether `name` can be set with `m`.
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphabetic() || matches!(name, '\'' | '`' | '<' | '>')
+    }
+}
+
+/// `` ` `` and `'` name the same mark.
+fn normalize(name: char) -> char {
+    match name {
+        '`' => CONTEXT_MARK,
+        name => name,
+    }
+}
+
+impl FrameBuffer {
+    pub fn set_mark(&mut self, name: char, position: (usize, usize)) {
+        let offset = self.char_index(position);
+        self.marks.named.insert(normalize(name), offset);
+    }
+
+    pub fn mark(&self, name: char) -> Option<(usize, usize)> {
+        let offset = *self.marks.named.get(&normalize(name))?;
+
+        Some(self.char_position(offset))
+    }
+
+    /// Records a jump away from `position`, which also becomes the context
+    /// mark. Earlier jumps from the same line are dropped, so each line
+    /// appears in the list once.
+    pub fn push_jump(&mut self, position: (usize, usize)) {
+        let row = position.1;
+        let jumps = std::mem::take(&mut self.marks.jumps);
+        self.marks.jumps = jumps
+            .into_iter()
+            .filter(|&offset| self.char_position(offset).1 != row)
+            .collect();
+
+        let offset = self.char_index(position);
+        self.marks.jumps.push(offset);
+        self.marks.jump_index = self.marks.jumps.len();
+        self.marks.named.insert(CONTEXT_MARK, offset);
+    }
+
+    /// Position `count` jumps back from `position`, as `Ctrl-o` goes. The
+    /// first step back records `position` so `Ctrl-i` can return to it.
+    pub fn jump_back(&mut self, position: (usize, usize), count: usize) -> Option<(usize, usize)> {
+        if self.marks.jump_index >= self.marks.jumps.len() {
+            self.push_jump(position);
+            self.marks.jump_index = self.marks.jumps.len() - 1;
+        }
+
+        let index = self.marks.jump_index.checked_sub(count)?;
+        self.marks.jump_index = index;
+
+        Some(self.char_position(self.marks.jumps[index]))
+    }
+
+    /// Position `count` jumps forward, as `Ctrl-i` goes.
+    pub fn jump_forward(&mut self, count: usize) -> Option<(usize, usize)> {
+        let index = self.marks.jump_index + count;
+        if index >= self.marks.jumps.len() {
+            return None;
+        }
+
+        self.marks.jump_index = index;
+
+        Some(self.char_position(self.marks.jumps[index]))
+    }
+
+    /// Marks `rows` to be visited in turn by [`Self::next_marked_line`].
+    /// Each line is marked by its line break, so it keeps its mark however
+    /// the lines around it change until it is itself removed.
+    pub fn mark_lines(&mut self, rows: impl IntoIterator<Item = usize>) {
+        let offsets: Vec<_> = rows
+            .into_iter()
+            .map(|row| self.rows_chars(&(row..row + 1)).end - 1)
+            .collect();
+        self.marks.lines.set(offsets);
+    }
+
+    /// Row of the first marked line still to be visited, unmarking it.
+    pub fn next_marked_line(&mut self) -> Option<usize> {
+        let offset = self.marks.lines.pop_front()?;
+
+        Some(self.char_position(offset).1)
+    }
+
+    pub fn clear_marked_lines(&mut self) {
+        self.marks.lines = MarkedLines::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::FrameBuffer;
+
+    fn buffer() -> FrameBuffer {
+        let lines = ["one", "two", "three", "four"];
+
+        FrameBuffer::new(lines.map(str::to_owned).to_vec(), None, 0..10)
+    }
+
+    #[test]
+    fn marks_follow_edits() {
+        let mut buffer = buffer();
+        buffer.set_mark('a', (1, 2));
+        buffer.set_mark('b', (2, 1));
+
+        // A line inserted above both
+        buffer.insert_chars(0, "zero\n");
+        assert_eq!(buffer.mark('a'), Some((1, 3)));
+
+        // The line holding `b` removed
+        let start = buffer.char_index((0, 2));
+        let end = buffer.char_index((0, 3));
+        buffer.remove_chars(start..end);
+        assert_eq!(buffer.mark('a'), Some((1, 2)));
+        assert_eq!(buffer.mark('b'), None);
+        assert_eq!(buffer.mark('.'), Some((0, 2)));
+
+        buffer.undo();
+        assert_eq!(buffer.mark('a'), Some((1, 2)));
+        assert_eq!(buffer.mark('c'), None);
+
+        // Text removed from within the line holding `a` leaves it there
+        buffer.set_mark('b', (3, 2));
+        let start = buffer.char_index((0, 2));
+        buffer.remove_chars(start..start + 2);
+        assert_eq!(buffer.mark('a'), Some((0, 2)));
+        assert_eq!(buffer.mark('b'), Some((1, 2)));
+    }
+
+    #[test]
+    fn jump_list() {
+        let mut buffer = buffer();
+        buffer.push_jump((0, 0));
+        buffer.push_jump((1, 2));
+        buffer.push_jump((0, 0));
+        assert_eq!(buffer.mark('`'), Some((0, 0)));
+
+        // Back from the last line through both jumps, then forward again
+        assert_eq!(buffer.jump_back((0, 3), 1), Some((0, 0)));
+        assert_eq!(buffer.jump_back((0, 0), 1), Some((1, 2)));
+        assert_eq!(buffer.jump_back((1, 2), 1), None);
+        assert_eq!(buffer.jump_forward(1), Some((0, 0)));
+        assert_eq!(buffer.jump_forward(1), Some((0, 3)));
+        assert_eq!(buffer.jump_forward(1), None);
+    }
+
+    #[test]
+    fn marked_lines() {
+        let mut buffer = buffer();
+        buffer.mark_lines([0, 1, 3]);
+
+        // Removing the first line, then the one after the next visited
+        let end = buffer.char_index((0, 1));
+        buffer.remove_chars(0..end);
+        assert_eq!(buffer.next_marked_line(), Some(0));
+        let start = buffer.char_index((0, 1));
+        buffer.remove_chars(start..start + 6);
+        assert_eq!(buffer.next_marked_line(), Some(1));
+
+        // Text typed on a line, or lines added above, leave its mark alone
+        buffer.mark_lines([1]);
+        buffer.insert_chars(0, "zero\n");
+        buffer.insert_chars(buffer.char_index((4, 2)), "!");
+        assert_eq!(buffer.next_marked_line(), Some(2));
+        assert_eq!(buffer.next_marked_line(), None);
+
+        // Lines added below the first marked, moving those after them alone
+        let mut buffer = self::buffer();
+        buffer.mark_lines([0, 1, 2, 3]);
+        buffer.insert_chars(buffer.char_index((0, 3)), "new\nnew\n");
+        let rows: Vec<_> = std::iter::from_fn(|| buffer.next_marked_line()).collect();
+        assert_eq!(rows, [0, 1, 2, 5]);
+    }
+}
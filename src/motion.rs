@@ -0,0 +1,562 @@
+//! Cursor motions over a [`FrameBuffer`], shared by normal mode movement and
+//! the operators applied across them.
+
+use crate::frame_buffer::{FrameBuffer, Line};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    BigWordForward,
+    BigWordBackward,
+    BigWordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    ParagraphForward,
+    ParagraphBackward,
+    MatchingBracket,
+    FindForward(char),
+    FindBackward(char),
+    TillForward(char),
+    TillBackward(char),
+    Mark(char),     // `` `a ``
+    MarkLine(char), // `'a`
+}
+
+/// How much of the text between the cursor and a motion's target an
+/// operator covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionKind {
+    Exclusive, // Up to, but not including, the target
+    Inclusive, // Up to and including the target
+    Linewise,  // Every line from the cursor's to the target's
+}
+
+impl Motion {
+    pub fn kind(&self) -> MotionKind {
+        match self {
+            Self::WordEnd
+            | Self::BigWordEnd
+            | Self::LineEnd
+            | Self::MatchingBracket
+            | Self::FindForward(_)
+            | Self::TillForward(_) => MotionKind::Inclusive,
+            Self::Up | Self::Down | Self::FirstLine | Self::LastLine | Self::MarkLine(_) => {
+                MotionKind::Linewise
+            }
+            _ => MotionKind::Exclusive,
+        }
+    }
+
+    /// The same find in the opposite direction, as `,` repeats it.
+    pub fn reversed(&self) -> Option<Self> {
+        match *self {
+            Self::FindForward(c) => Some(Self::FindBackward(c)),
+            Self::FindBackward(c) => Some(Self::FindForward(c)),
+            Self::TillForward(c) => Some(Self::TillBackward(c)),
+            Self::TillBackward(c) => Some(Self::TillForward(c)),
+            _ => None,
+        }
+    }
+
+    pub fn is_find(&self) -> bool {
+        self.reversed().is_some()
+    }
+
+    /// Whether moving by the motion is recorded in the jump list.
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Self::FirstLine
+                | Self::LastLine
+                | Self::ParagraphForward
+                | Self::ParagraphBackward
+                | Self::MatchingBracket
+                | Self::Mark(_)
+                | Self::MarkLine(_)
+        )
+    }
+
+    /// Whether ending up where it started counts as the motion failing.
+    fn fails_in_place(&self) -> bool {
+        matches!(self, Self::Left | Self::Right | Self::Up | Self::Down) || self.is_find()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Class {
+    Blank,
+    LineBreak,
+    Word,
+    Punctuation,
+}
+
+impl Class {
+    /// Class of `grapheme`, where `None` stands for a line break. Within a
+    /// WORD (`big`) every non-blank grapheme is alike.
+    pub(crate) fn of(grapheme: Option<&str>, big: bool) -> Self {
+        match grapheme.and_then(|grapheme| grapheme.chars().next()) {
+            None => Self::LineBreak,
+            Some(c) if c.is_whitespace() => Self::Blank,
+            Some(_) if big => Self::Word,
+            Some(c) if c.is_alphanumeric() || c == '_' => Self::Word,
+            Some(_) => Self::Punctuation,
+        }
+    }
+}
+
+/// Steps through a buffer a grapheme at a time, with each line break taking
+/// up the column just past the end of its line.
+struct Walker<'a> {
+    buffer: &'a FrameBuffer,
+    column: usize,
+    row: usize,
+    line: Vec<String>, // Graphemes of `row`
+}
+
+impl<'a> Walker<'a> {
+    fn new(buffer: &'a FrameBuffer, (column, row): (usize, usize)) -> Self {
+        let line = graphemes(buffer, row);
+
+        Self {
+            buffer,
+            column: column.min(line.len()),
+            row,
+            line,
+        }
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.column, self.row)
+    }
+
+    fn grapheme(&self) -> Option<&str> {
+        self.line.get(self.column).map(String::as_str)
+    }
+
+    fn class(&self, big: bool) -> Class {
+        Class::of(self.grapheme(), big)
+    }
+
+    fn on_empty_line(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    fn forward(&mut self) -> bool {
+        if self.column < self.line.len() {
+            self.column += 1;
+            return true;
+        }
+
+        if self.row + 1 >= self.buffer.len() {
+            return false;
+        }
+
+        self.row += 1;
+        self.column = 0;
+        self.line = graphemes(self.buffer, self.row);
+
+        true
+    }
+
+    fn backward(&mut self) -> bool {
+        if self.column > 0 {
+            self.column -= 1;
+            return true;
+        }
+
+        if self.row == 0 {
+            return false;
+        }
+
+        self.row -= 1;
+        self.line = graphemes(self.buffer, self.row);
+        self.column = self.line.len();
+
+        true
+    }
+}
+
+fn graphemes(buffer: &FrameBuffer, row: usize) -> Vec<String> {
+    buffer
+        .get(Line::Index(row))
+        .unwrap_or_default()
+        .graphemes(true)
+        .map(str::to_owned)
+        .collect()
+}
+
+const BRACKETS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+
+impl FrameBuffer {
+    /// Position `motion` takes the cursor to from `position`, applied `count`
+    /// times, or `None` when it cannot move at all.
+    ///
+    /// `gg` and `G` instead treat `count` as the line to go to.
+    pub fn motion_target(
+        &self,
+        motion: Motion,
+        position: (usize, usize),
+        count: Option<usize>,
+    ) -> Option<(usize, usize)> {
+        let (column, row) = position;
+        let repeat = count.unwrap_or(1).max(1);
+        let last_row = self.len().saturating_sub(1);
+        let repeated = |step: &dyn Fn((usize, usize)) -> (usize, usize)| {
+            let target = (0..repeat).fold(position, |position, _| step(position));
+            (target != position).then_some(target)
+        };
+
+        match motion {
+            Motion::Left => Some((column.saturating_sub(repeat), row)),
+            Motion::Right => {
+                let line_len = self.line_len(Line::Index(row));
+                Some(((column + repeat).min(line_len), row))
+            }
+            Motion::Up => {
+                let row = row.saturating_sub(repeat);
+                Some((column.min(self.line_len(Line::Index(row))), row))
+            }
+            Motion::Down => {
+                let row = (row + repeat).min(last_row);
+                Some((column.min(self.line_len(Line::Index(row))), row))
+            }
+            Motion::WordForward => repeated(&|position| self.word_forward(position, false)),
+            Motion::WordBackward => repeated(&|position| self.word_backward(position, false)),
+            Motion::WordEnd => repeated(&|position| self.word_end(position, false)),
+            Motion::BigWordForward => repeated(&|position| self.word_forward(position, true)),
+            Motion::BigWordBackward => repeated(&|position| self.word_backward(position, true)),
+            Motion::BigWordEnd => repeated(&|position| self.word_end(position, true)),
+            Motion::LineStart => Some((0, row)),
+            Motion::FirstNonBlank => Some((self.first_non_blank(row), row)),
+            Motion::LineEnd => {
+                let row = (row + repeat - 1).min(last_row);
+                Some((self.line_len(Line::Index(row)).saturating_sub(1), row))
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let row = match (motion, count) {
+                    (_, Some(line)) => line.saturating_sub(1).min(last_row),
+                    (Motion::FirstLine, None) => 0,
+                    _ => last_row,
+                };
+                Some((self.first_non_blank(row), row))
+            }
+            Motion::ParagraphForward => repeated(&|position| self.paragraph_forward(position)),
+            Motion::ParagraphBackward => repeated(&|position| self.paragraph_backward(position)),
+            Motion::MatchingBracket => self.matching_bracket(position),
+            Motion::FindForward(c) => self
+                .find_in_line(position, c, true, repeat)
+                .map(|i| (i, row)),
+            Motion::TillForward(c) => self
+                .find_in_line(position, c, true, repeat)
+                .map(|i| (i - 1, row)),
+            Motion::FindBackward(c) => self
+                .find_in_line(position, c, false, repeat)
+                .map(|i| (i, row)),
+            Motion::TillBackward(c) => self
+                .find_in_line(position, c, false, repeat)
+                .map(|i| (i + 1, row)),
+            Motion::Mark(name) => self.mark(name),
+            Motion::MarkLine(name) => self
+                .mark(name)
+                .map(|(_, row)| (self.first_non_blank(row), row)),
+        }
+        .filter(|&target| target != (column, row) || !motion.fails_in_place())
+    }
+
+    /// Target of `cw`, which rather than moving past the blanks following a
+    /// word stops at the end of the word under the cursor.
+    pub fn change_word_target(
+        &self,
+        position: (usize, usize),
+        big: bool,
+        count: Option<usize>,
+    ) -> (usize, usize) {
+        let mut walker = Walker::new(self, position);
+        let class = walker.class(big);
+        while walker.forward() {
+            if walker.class(big) != class {
+                walker.backward();
+                break;
+            }
+        }
+
+        (1..count.unwrap_or(1).max(1)).fold(walker.position(), |position, _| {
+            self.word_end(position, big)
+        })
+    }
+
+    /// Start of the next word, stopping at empty lines along the way.
+    fn word_forward(&self, position: (usize, usize), big: bool) -> (usize, usize) {
+        let mut walker = Walker::new(self, position);
+        let start = walker.class(big);
+        if matches!(start, Class::Word | Class::Punctuation) {
+            while walker.class(big) == start {
+                if !walker.forward() {
+                    return walker.position();
+                }
+            }
+        }
+
+        loop {
+            match walker.class(big) {
+                Class::LineBreak if walker.on_empty_line() && walker.position() != position => {
+                    break
+                }
+                Class::Blank | Class::LineBreak => {}
+                _ => break,
+            }
+
+            if !walker.forward() {
+                break;
+            }
+        }
+
+        walker.position()
+    }
+
+    /// Start of the current or previous word, stopping at empty lines.
+    fn word_backward(&self, position: (usize, usize), big: bool) -> (usize, usize) {
+        let mut walker = Walker::new(self, position);
+        if !walker.backward() {
+            return walker.position();
+        }
+
+        loop {
+            match walker.class(big) {
+                Class::LineBreak if walker.on_empty_line() => return walker.position(),
+                Class::Blank | Class::LineBreak => {}
+                _ => break,
+            }
+
+            if !walker.backward() {
+                return walker.position();
+            }
+        }
+
+        let class = walker.class(big);
+        while walker.backward() {
+            if walker.class(big) != class {
+                walker.forward();
+                break;
+            }
+        }
+
+        walker.position()
+    }
+
+    /// End of the current or next word.
+    fn word_end(&self, position: (usize, usize), big: bool) -> (usize, usize) {
+        let mut walker = Walker::new(self, position);
+        if !walker.forward() {
+            return walker.position();
+        }
+
+        while let Class::Blank | Class::LineBreak = walker.class(big) {
+            if !walker.forward() {
+                return walker.position();
+            }
+        }
+
+        let class = walker.class(big);
+        while walker.forward() {
+            if walker.class(big) != class {
+                walker.backward();
+                break;
+            }
+        }
+
+        walker.position()
+    }
+
+    /// Next empty line past the current paragraph, or the end of the buffer.
+    fn paragraph_forward(&self, (_, mut row): (usize, usize)) -> (usize, usize) {
+        let last_row = self.len().saturating_sub(1);
+        while row < last_row && self.line_is_empty(Line::Index(row)) {
+            row += 1;
+        }
+        while row < last_row && !self.line_is_empty(Line::Index(row)) {
+            row += 1;
+        }
+
+        match self.line_is_empty(Line::Index(row)) {
+            true => (0, row),
+            false => (self.line_len(Line::Index(row)), row),
+        }
+    }
+
+    /// Previous empty line before the current paragraph, or the start of the
+    /// buffer.
+    fn paragraph_backward(&self, (_, mut row): (usize, usize)) -> (usize, usize) {
+        while row > 0 && self.line_is_empty(Line::Index(row)) {
+            row -= 1;
+        }
+        while row > 0 && !self.line_is_empty(Line::Index(row)) {
+            row -= 1;
+        }
+
+        (0, row)
+    }
+
+    /// Bracket paired with the first one at or after the cursor on its line.
+    fn matching_bracket(&self, (column, row): (usize, usize)) -> Option<(usize, usize)> {
+        let line = graphemes(self, row);
+        let (start, same, other, forward) =
+            line.iter()
+                .enumerate()
+                .skip(column)
+                .find_map(|(i, grapheme)| {
+                    BRACKETS
+                        .iter()
+                        .find_map(|&(open, close)| match grapheme.as_str() {
+                            g if g == open => Some((i, open, close, true)),
+                            g if g == close => Some((i, close, open, false)),
+                            _ => None,
+                        })
+                })?;
+
+        let mut walker = Walker::new(self, (start, row));
+        let mut depth = 0;
+        loop {
+            match walker.grapheme() {
+                Some(grapheme) if grapheme == same => depth += 1,
+                Some(grapheme) if grapheme == other => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(walker.position());
+                    }
+                }
+                _ => {}
+            }
+
+            let moved = match forward {
+                true => walker.forward(),
+                false => walker.backward(),
+            };
+            if !moved {
+                return None;
+            }
+        }
+    }
+
+    /// Column of the `repeat`th occurrence of `character` on the cursor's
+    /// line, searching away from the cursor.
+    fn find_in_line(
+        &self,
+        (column, row): (usize, usize),
+        character: char,
+        forward: bool,
+        repeat: usize,
+    ) -> Option<usize> {
+        let line = graphemes(self, row);
+        let matches = |i: &usize| line[*i].chars().eq(std::iter::once(character));
+
+        (0..repeat).try_fold(column, |found, _| match forward {
+            true => (found + 1..line.len()).find(matches),
+            false => (0..found.min(line.len())).rev().find(matches),
+        })
+    }
+
+    /// Column of the first non-blank grapheme of `row`.
+    pub fn first_non_blank(&self, row: usize) -> usize {
+        graphemes(self, row)
+            .iter()
+            .position(|grapheme| Class::of(Some(grapheme), false) != Class::Blank)
+            .unwrap_or_else(|| self.line_len(Line::Index(row)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Motion;
+    use crate::{FrameBuffer, Span};
+
+    fn buffer(lines: &[&str]) -> FrameBuffer {
+        let lines = lines.iter().map(|line| line.to_string()).collect();
+
+        FrameBuffer::new(lines, None, Span { start: 0, end: 5 })
+    }
+
+    #[test]
+    fn words() {
+        let buffer = buffer(&["foo.bar  baz", "", "  qux"]);
+        let target = |motion, position| buffer.motion_target(motion, position, None);
+
+        assert_eq!(target(Motion::WordForward, (0, 0)), Some((3, 0)));
+        assert_eq!(target(Motion::WordForward, (3, 0)), Some((4, 0)));
+        assert_eq!(target(Motion::BigWordForward, (0, 0)), Some((9, 0)));
+        assert_eq!(target(Motion::WordForward, (9, 0)), Some((0, 1)));
+        assert_eq!(target(Motion::WordForward, (0, 1)), Some((2, 2)));
+        assert_eq!(target(Motion::WordEnd, (0, 0)), Some((2, 0)));
+        assert_eq!(target(Motion::BigWordEnd, (0, 0)), Some((6, 0)));
+        assert_eq!(target(Motion::WordBackward, (2, 2)), Some((0, 1)));
+        assert_eq!(target(Motion::WordBackward, (0, 1)), Some((9, 0)));
+        assert_eq!(target(Motion::BigWordBackward, (9, 0)), Some((0, 0)));
+        assert_eq!(target(Motion::WordBackward, (0, 0)), None);
+        assert_eq!(
+            buffer.motion_target(Motion::WordForward, (0, 0), Some(3)),
+            Some((9, 0))
+        );
+    }
+
+    #[test]
+    fn change_word() {
+        let buffer = buffer(&["foo bar baz"]);
+
+        assert_eq!(buffer.change_word_target((1, 0), false, None), (2, 0));
+        assert_eq!(buffer.change_word_target((2, 0), false, None), (2, 0));
+        assert_eq!(buffer.change_word_target((0, 0), false, Some(2)), (6, 0));
+    }
+
+    #[test]
+    fn lines_and_paragraphs() {
+        let buffer = buffer(&["  one", "two", "", "three", "four"]);
+        let target = |motion, position, count| buffer.motion_target(motion, position, count);
+
+        assert_eq!(target(Motion::Down, (4, 0), Some(3)), Some((4, 3)));
+        assert_eq!(target(Motion::Up, (4, 0), None), None);
+        assert_eq!(target(Motion::FirstNonBlank, (4, 0), None), Some((2, 0)));
+        assert_eq!(target(Motion::LineEnd, (0, 0), None), Some((4, 0)));
+        assert_eq!(target(Motion::LineEnd, (0, 0), Some(2)), Some((2, 1)));
+        assert_eq!(target(Motion::LastLine, (0, 0), None), Some((0, 4)));
+        assert_eq!(target(Motion::FirstLine, (0, 4), None), Some((2, 0)));
+        assert_eq!(target(Motion::LastLine, (0, 4), Some(2)), Some((0, 1)));
+        assert_eq!(target(Motion::ParagraphForward, (0, 0), None), Some((0, 2)));
+        assert_eq!(target(Motion::ParagraphForward, (0, 2), None), Some((4, 4)));
+        assert_eq!(
+            target(Motion::ParagraphBackward, (0, 4), None),
+            Some((0, 2))
+        );
+    }
+
+    #[test]
+    fn brackets_and_finds() {
+        let buffer = buffer(&["if (a[0]) {", "  f(x);", "}"]);
+        let target = |motion, position, count| buffer.motion_target(motion, position, count);
+
+        assert_eq!(target(Motion::MatchingBracket, (0, 0), None), Some((8, 0)));
+        assert_eq!(target(Motion::MatchingBracket, (8, 0), None), Some((3, 0)));
+        assert_eq!(target(Motion::MatchingBracket, (9, 0), None), Some((0, 2)));
+        assert_eq!(target(Motion::MatchingBracket, (0, 2), None), Some((10, 0)));
+        assert_eq!(target(Motion::FindForward('a'), (0, 0), None), Some((4, 0)));
+        assert_eq!(target(Motion::TillForward(')'), (0, 0), None), Some((7, 0)));
+        assert_eq!(target(Motion::FindForward(')'), (0, 1), Some(2)), None);
+        assert_eq!(
+            target(Motion::FindBackward('i'), (4, 0), None),
+            Some((0, 0))
+        );
+        assert_eq!(
+            target(Motion::TillBackward('('), (5, 1), None),
+            Some((4, 1))
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+
+/// Shape of the text held in a register, which decides how it is put back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Charwise,
+    Linewise,  // Whole lines, each terminated by `\n`
+    Blockwise, // A column of text, one `\n` separated row per line
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    pub fn new(text: impl Into<String>, kind: RegisterKind) -> Self {
+        Self {
+            text: text.into(),
+            kind,
+        }
+    }
+
+    /// Adds `other` to the end of the register, as writing to an uppercase
+    /// register does. Appending lines to text, or text to lines, leaves
+    /// whole lines.
+    fn append(&mut self, other: Register) {
+        match (self.kind, other.kind) {
+            (RegisterKind::Linewise, RegisterKind::Linewise) => self.text.push_str(&other.text),
+            (RegisterKind::Linewise, _) => {
+                self.text.push_str(&other.text);
+                self.text.push('\n');
+            }
+            (_, RegisterKind::Linewise) => {
+                self.text.push('\n');
+                self.text.push_str(&other.text);
+                self.kind = RegisterKind::Linewise;
+            }
+            (RegisterKind::Blockwise, _) | (_, RegisterKind::Blockwise) => {
+                self.text.push('\n');
+                self.text.push_str(&other.text);
+            }
+            _ => self.text.push_str(&other.text),
+        }
+    }
+}
+
+/// Every register text is yanked to, deleted to and put from.
+///
+/// Yanks land in `"0` and deletes of whole lines shift through `"1` to
+/// `"9`, while smaller deletes go to `"-`. The unnamed register `""` always
+/// holds whatever was written last, unless it went to the black hole `"_`.
+/// The read-only `"%`, `":` and `"/` registers hold the file name, the last
+/// command line and the last search pattern.
+#[derive(Debug, Default)]
+pub struct Registers {
+    unnamed: Option<Register>,
+    named: HashMap<char, Register>, // `"a` to `"z`, along with `"-`
+    numbered: [Option<Register>; 10],
+    last_command: Option<String>,
+    last_search: Option<String>,
+}
+
+impl Registers {
+    /// Contents of register `name`, or of the unnamed register when none is
+    /// given. `"%` is left to the caller, which knows the file name.
+    pub fn get(&self, name: Option<char>) -> Option<Register> {
+        match name.map(|name| name.to_ascii_lowercase()) {
+            None | Some('"') => self.unnamed.clone(),
+            Some(digit @ '0'..='9') => {
+                // SAFETY: matched as a decimal digit
+                let index = digit.to_digit(10).unwrap() as usize;
+                self.numbered[index].clone()
+            }
+            Some(':') => self
+                .last_command
+                .clone()
+                .map(|command| Register::new(command, RegisterKind::Charwise)),
+            Some('/') => self
+                .last_search
+                .clone()
+                .map(|pattern| Register::new(pattern, RegisterKind::Charwise)),
+            Some(name) => self.named.get(&name).cloned(),
+        }
+    }
+
+    /// Stores yanked text in register `name`, or in `"0` when none is given.
+    pub fn yank(&mut self, name: Option<char>, register: Register) -> Result<()> {
+        match name {
+            None | Some('"') => {
+                self.numbered[0] = Some(register.clone());
+                self.unnamed = Some(register);
+                Ok(())
+            }
+            Some(name) => self.set(name, register),
+        }
+    }
+
+    /// Stores deleted text in register `name`, or when none is given in `"1`
+    /// for deletes spanning lines and `"-` for anything smaller.
+    pub fn delete(&mut self, name: Option<char>, register: Register) -> Result<()> {
+        match name {
+            None | Some('"') => {
+                match register.kind == RegisterKind::Linewise || register.text.contains('\n') {
+                    true => {
+                        self.numbered[1..].rotate_right(1);
+                        self.numbered[1] = Some(register.clone());
+                    }
+                    false => {
+                        self.named.insert('-', register.clone());
+                    }
+                }
+
+                self.unnamed = Some(register);
+                Ok(())
+            }
+            Some(name) => self.set(name, register),
+        }
+    }
+
+    /// Writes `register` to `name`, appending to it when `name` is
+    /// uppercase.
+    pub fn set(&mut self, name: char, register: Register) -> Result<()> {
+        match name {
+            '_' => return Ok(()),
+            'a'..='z' | '-' => {
+                self.named.insert(name, register.clone());
+            }
+            'A'..='Z' => {
+                let name = name.to_ascii_lowercase();
+                match self.named.get_mut(&name) {
+                    Some(existing) => existing.append(register.clone()),
+                    None => {
+                        self.named.insert(name, register.clone());
+                    }
+                }
+            }
+            '0'..='9' => {
+                // SAFETY: matched as a decimal digit
+                let index = name.to_digit(10).unwrap() as usize;
+                self.numbered[index] = Some(register.clone());
+            }
+            name => return Err(Error::InvalidRegister(name)),
+        }
+
+        self.unnamed = Some(register);
+
+        Ok(())
+    }
+
+    pub fn set_last_command(&mut self, command: &str) {
+        self.last_command = Some(command.to_owned());
+    }
+
+    pub fn set_last_search(&mut self, pattern: &str) {
+        self.last_search = Some(pattern.to_owned());
+    }
+
+    /// Whether `name` can be put from or written to.
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphanumeric() || matches!(name, '"' | '-' | '_' | '%' | ':' | '/')
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Register, RegisterKind, Registers};
+
+    fn charwise(text: &str) -> Register {
+        Register::new(text, RegisterKind::Charwise)
+    }
+
+    fn linewise(text: &str) -> Register {
+        Register::new(text, RegisterKind::Linewise)
+    }
+
+    #[test]
+    fn yank_and_delete() {
+        let mut registers = Registers::default();
+        registers.yank(None, charwise("word")).unwrap();
+        registers.delete(None, linewise("one\n")).unwrap();
+        registers.delete(None, linewise("two\n")).unwrap();
+        registers.delete(None, charwise("x")).unwrap();
+
+        assert_eq!(registers.get(Some('0')), Some(charwise("word")));
+        assert_eq!(registers.get(Some('1')), Some(linewise("two\n")));
+        assert_eq!(registers.get(Some('2')), Some(linewise("one\n")));
+        assert_eq!(registers.get(Some('-')), Some(charwise("x")));
+        assert_eq!(registers.get(None), Some(charwise("x")));
+
+        registers.delete(Some('_'), charwise("gone")).unwrap();
+        assert_eq!(registers.get(None), Some(charwise("x")));
+    }
+
+    #[test]
+    fn named_append() {
+        let mut registers = Registers::default();
+        registers.yank(Some('a'), charwise("foo")).unwrap();
+        registers.yank(Some('A'), charwise("bar")).unwrap();
+        assert_eq!(registers.get(Some('a')), Some(charwise("foobar")));
+
+        registers.yank(Some('A'), linewise("baz\n")).unwrap();
+        assert_eq!(registers.get(Some('a')), Some(linewise("foobar\nbaz\n")));
+        assert!(registers.yank(Some('%'), charwise("file")).is_err());
+    }
+}
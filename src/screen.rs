@@ -0,0 +1,242 @@
+//! Screen model the editor draws into: a grid of styled cells, one frame of
+//! which is compared against the last to find the cells that need redrawing.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Style {
+    #[default]
+    Plain,
+    Reversed, // Reverse video, as selections and matches are drawn
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: String, // Empty for the cells a wide grapheme spills into
+    pub style: Style,
+}
+
+impl Cell {
+    fn blank() -> Self {
+        Self {
+            symbol: " ".to_owned(),
+            style: Style::Plain,
+        }
+    }
+
+    /// Cells the symbol takes up, or none for the tail of a wide one.
+    pub fn width(&self) -> usize {
+        match self.symbol.as_str() {
+            "" => 0,
+            symbol => symbol.width().max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    cursor: (usize, usize), // Where the next write begins
+    wrap: bool,             // Whether writes carry on past the last column
+}
+
+impl Grid {
+    pub fn new((width, height): (usize, usize), wrap: bool) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::blank(); width * height],
+            cursor: (0, 0),
+            wrap,
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    #[inline]
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    pub fn move_to(&mut self, position: (usize, usize)) {
+        self.cursor = position;
+    }
+
+    pub fn cell(&self, (column, row): (usize, usize)) -> Option<&Cell> {
+        if column >= self.width {
+            return None;
+        }
+
+        self.cells.get(row * self.width + column)
+    }
+
+    /// Resizes the grid, keeping what fits of its contents.
+    pub fn resize(&mut self, (width, height): (usize, usize)) {
+        let mut resized = Self::new((width, height), self.wrap);
+        for row in 0..height.min(self.height) {
+            for column in 0..width.min(self.width) {
+                resized.cells[row * width + column] = self.cells[row * self.width + column].clone();
+            }
+        }
+        resized.cursor = self.cursor;
+
+        *self = resized;
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::blank());
+    }
+
+    /// Blanks the rest of the cursor's row.
+    pub fn clear_until_newline(&mut self) {
+        let (column, row) = self.cursor;
+        if row >= self.height || column >= self.width {
+            return;
+        }
+
+        let start = row * self.width;
+        self.cells[start + column..start + self.width].fill(Cell::blank());
+    }
+
+    /// Writes `text` from the cursor, moving it along. `\n` moves to the next
+    /// row and `\r` back to the first column, as a terminal in raw mode does,
+    /// and anything falling off the grid is dropped.
+    pub fn write(&mut self, text: &str, style: Style) {
+        for grapheme in text.graphemes(true) {
+            match grapheme {
+                "\r\n" => self.cursor = (0, self.cursor.1 + 1),
+                "\n" => self.cursor.1 += 1,
+                "\r" => self.cursor.0 = 0,
+                grapheme => self.put(grapheme, style),
+            }
+        }
+    }
+
+    fn put(&mut self, grapheme: &str, style: Style) {
+        let width = grapheme.width();
+        if width == 0 {
+            // Combines with whatever was drawn before it
+            if let Some(cell) = self.previous_cell_mut() {
+                cell.symbol.push_str(grapheme);
+            }
+            return;
+        }
+
+        if self.cursor.0 + width > self.width && self.wrap {
+            self.cursor = (0, self.cursor.1 + 1);
+        }
+        let (column, row) = self.cursor;
+        self.cursor.0 += width;
+        if row >= self.height || column + width > self.width {
+            return;
+        }
+
+        let index = row * self.width + column;
+        self.cells[index] = Cell {
+            symbol: grapheme.to_owned(),
+            style,
+        };
+        for cell in &mut self.cells[index + 1..index + width] {
+            *cell = Cell {
+                symbol: String::new(),
+                style,
+            };
+        }
+    }
+
+    fn previous_cell_mut(&mut self) -> Option<&mut Cell> {
+        let (column, row) = self.cursor;
+        if row >= self.height || column == 0 || column > self.width {
+            return None;
+        }
+
+        let start = row * self.width;
+        let mut column = column - 1;
+        while column > 0 && self.cells[start + column].symbol.is_empty() {
+            column -= 1;
+        }
+
+        Some(&mut self.cells[start + column])
+    }
+
+    /// Cells that differ from `previous`, row by row, with where they are.
+    /// A grid of another size differs everywhere.
+    pub fn diff<'a>(
+        &'a self,
+        previous: &'a Self,
+    ) -> impl Iterator<Item = ((usize, usize), &'a Cell)> + 'a {
+        let resized = self.size() != previous.size();
+
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(move |&(index, cell)| resized || previous.cells[index] != *cell)
+            .map(|(index, cell)| ((index % self.width, index / self.width), cell))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Grid, Style};
+
+    fn row(grid: &Grid, row: usize) -> String {
+        (0..grid.size().0)
+            .filter_map(|column| grid.cell((column, row)))
+            .map(|cell| cell.symbol.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn writes() {
+        let mut grid = Grid::new((6, 3), false);
+        grid.write("one\r\ntwo three\r\n", Style::Plain);
+        grid.write("四e\u{301}", Style::Reversed);
+        assert_eq!(row(&grid, 0), "one   ");
+        assert_eq!(row(&grid, 1), "two th");
+        assert_eq!(row(&grid, 2), "四e\u{301}   ");
+        assert_eq!(
+            grid.cell((2, 2)).map(|cell| cell.style),
+            Some(Style::Reversed)
+        );
+
+        grid.move_to((1, 0));
+        grid.clear_until_newline();
+        assert_eq!(row(&grid, 0), "o     ");
+
+        // Rows past the bottom are dropped
+        grid.move_to((0, 2));
+        grid.write("\r\nfour", Style::Plain);
+        assert_eq!(row(&grid, 2), "四e\u{301}   ");
+    }
+
+    #[test]
+    fn wraps() {
+        let mut grid = Grid::new((4, 2), true);
+        grid.write("abc四", Style::Plain);
+        assert_eq!(row(&grid, 0), "abc ");
+        assert_eq!(row(&grid, 1), "四  ");
+    }
+
+    #[test]
+    fn diffs() {
+        let mut previous = Grid::new((4, 2), false);
+        previous.write("abcd", Style::Plain);
+
+        let mut grid = previous.clone();
+        grid.move_to((1, 0));
+        grid.write("x", Style::Plain);
+        grid.move_to((2, 1));
+        grid.write("y", Style::Reversed);
+        let changed: Vec<_> = grid.diff(&previous).map(|(position, _)| position).collect();
+        assert_eq!(changed, [(1, 0), (2, 1)]);
+
+        grid.resize((2, 2));
+        assert_eq!(grid.diff(&previous).count(), 4);
+    }
+}
@@ -0,0 +1,564 @@
+//! Regex search and substitution over a [`FrameBuffer`], run over a span of
+//! rows at a time so that a pattern may match over line breaks without the
+//! whole text being searched for each match.
+
+use crate::{
+    error::{Error, Result},
+    frame_buffer::{FrameBuffer, Line},
+    motion::Class,
+    Span,
+};
+use regex::{Captures, Regex, RegexBuilder};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Rows searched at once when looking for a match, doubled each time the
+/// search has to go further.
+const SEARCH_ROWS: usize = 64;
+
+/// Most matches counted for `match n of m`, past which the count stops.
+pub const MAX_SEARCH_COUNT: usize = 99;
+
+/// Pattern searched for, along with the direction `n` repeats it in.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub pattern: String,
+    pub forward: bool,
+    regex: Regex,
+    ignore_case: bool,
+}
+
+impl Search {
+    /// Compiles `pattern`, which matches regardless of case unless it holds
+    /// an uppercase letter. `\c` in the pattern ignores case anyway, and `\C`
+    /// always matches it.
+    pub fn new(pattern: &str, forward: bool) -> Result<Self> {
+        Self::with_case(pattern, forward, None)
+    }
+
+    /// Compiles `pattern` as [`Self::new`] does, unless `ignore_case` says
+    /// otherwise.
+    pub fn with_case(pattern: &str, forward: bool, ignore_case: Option<bool>) -> Result<Self> {
+        let (source, ignore_case) = Self::parse(pattern, ignore_case);
+
+        Self::build(pattern, &source, ignore_case, forward)
+    }
+
+    /// Compiles `pattern` as [`Self::with_case`] does, unless `previous` was
+    /// compiled to the same regex already, as it is for each line `:g` runs
+    /// `:s` on.
+    pub fn reusing(
+        previous: Option<&Search>,
+        pattern: &str,
+        forward: bool,
+        ignore_case: Option<bool>,
+    ) -> Result<Self> {
+        let (source, ignore_case) = Self::parse(pattern, ignore_case);
+        match previous {
+            Some(previous)
+                if previous.regex.as_str() == source && previous.ignore_case == ignore_case =>
+            {
+                Ok(Self {
+                    pattern: pattern.to_owned(),
+                    forward,
+                    ..previous.clone()
+                })
+            }
+            _ => Self::build(pattern, &source, ignore_case, forward),
+        }
+    }
+
+    /// Regex source of `pattern`, without its `\c` or `\C`, and whether it
+    /// ignores case.
+    fn parse(pattern: &str, ignore_case: Option<bool>) -> (String, bool) {
+        let mut source = String::with_capacity(pattern.len());
+        let mut flag = None;
+        let mut uppercase = false;
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some(case @ ('c' | 'C'))) => {
+                    flag = Some(case == 'c');
+                    chars.next();
+                }
+                // Escapes such as `\S` do not count towards the case
+                ('\\', Some(escaped)) => {
+                    source.push(c);
+                    source.push(escaped);
+                    chars.next();
+                }
+                (c, _) => {
+                    uppercase |= c.is_uppercase();
+                    source.push(c);
+                }
+            }
+        }
+
+        let ignore_case = ignore_case.or(flag).unwrap_or(!uppercase);
+
+        (source, ignore_case)
+    }
+
+    /// Search for `word` as a whole word, as `*` and `#` make, which matches
+    /// regardless of case.
+    pub fn word(word: &str, forward: bool) -> Result<Self> {
+        let boundary = |grapheme: Option<&str>| match Class::of(grapheme, false) {
+            Class::Word => "\\b",
+            _ => "",
+        };
+        let source = format!(
+            "{}{}{}",
+            boundary(word.graphemes(true).next()),
+            regex::escape(word),
+            boundary(word.graphemes(true).next_back()),
+        );
+
+        Self::build(&source, &source, true, forward)
+    }
+
+    fn build(pattern: &str, source: &str, ignore_case: bool, forward: bool) -> Result<Self> {
+        let regex = RegexBuilder::new(source)
+            .case_insensitive(ignore_case)
+            .multi_line(true)
+            .build()
+            .map_err(|_| Error::InvalidPattern(pattern.to_owned()))?;
+
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            forward,
+            regex,
+            ignore_case,
+        })
+    }
+}
+
+/// Piece of a substitution's replacement text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Group(usize), // `\1` to `\9`, with `&` and `\0` standing for the whole match
+}
+
+/// Replacement text of a substitution, filled in from each match.
+///
+/// `\n` and `\r` break the line, `\t` is a tab and any other escaped
+/// character, `\&` and `\\` included, stands for itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement(Vec<Part>);
+
+impl Replacement {
+    pub fn parse(text: &str) -> Self {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            let group = match (c, c == '\\') {
+                ('&', _) => 0,
+                (_, true) => match chars.next() {
+                    Some(digit @ '0'..='9') => digit as usize - '0' as usize,
+                    Some('n' | 'r') => {
+                        literal.push('\n');
+                        continue;
+                    }
+                    Some('t') => {
+                        literal.push('\t');
+                        continue;
+                    }
+                    Some(c) => {
+                        literal.push(c);
+                        continue;
+                    }
+                    None => {
+                        literal.push('\\');
+                        continue;
+                    }
+                },
+                (c, false) => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+
+            if !literal.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::Group(group));
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+
+        Self(parts)
+    }
+
+    /// The text replacing the match `captures` holds, where groups that did
+    /// not take part in it are left empty.
+    fn expand(&self, captures: &Captures) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.as_str(),
+                Part::Group(group) => captures.get(*group).map_or("", |group| group.as_str()),
+            })
+            .collect()
+    }
+}
+
+/// Match of a substitution, along with the text replacing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub chars: Range<usize>,
+    pub row: usize, // Where the match starts
+    pub text: String,
+}
+
+impl FrameBuffer {
+    /// Char ranges of the matches of `search` starting on `rows`, in order.
+    ///
+    /// The text searched runs on past `rows` for [`SEARCH_ROWS`] more, which
+    /// is as far as a match starting on them may continue over line breaks.
+    pub fn matches(&self, search: &Search, rows: &Span) -> Vec<Range<usize>> {
+        let chars = self.rows_chars(rows);
+        let end = self.rows_chars(&(rows.end..rows.end + SEARCH_ROWS)).end;
+        let text = self.slice_chars(chars.start..end);
+
+        // Offsets are carried over from one match to the next rather than
+        // counted from the start each time
+        let (mut byte, mut char) = (0, chars.start);
+        let mut char_index = |index: usize| {
+            char += text[byte..index].chars().count();
+            byte = index;
+            char
+        };
+
+        search
+            .regex
+            .find_iter(&text)
+            .map(|found| char_index(found.start())..char_index(found.end()))
+            .take_while(|found| found.start < chars.end)
+            .collect()
+    }
+
+    /// The match `count` matches on from `position` in the direction given,
+    /// wrapping around the ends of the buffer.
+    pub fn find_match(
+        &self,
+        search: &Search,
+        position: (usize, usize),
+        forward: bool,
+        count: usize,
+    ) -> Option<Range<usize>> {
+        let steps = count.max(1) - 1;
+        let mut seen = 0;
+        for found in self.matches_from(search, position, forward) {
+            if seen == steps {
+                return Some(found);
+            }
+            seen += 1;
+        }
+
+        // Counting past every match goes around the buffer again
+        match seen {
+            0 => None,
+            seen => self
+                .matches_from(search, position, forward)
+                .nth(steps % seen),
+        }
+    }
+
+    /// Every match in the order `n` visits them from `position`, once each,
+    /// found a few rows at a time going out from the cursor.
+    fn matches_from<'a>(
+        &'a self,
+        search: &'a Search,
+        position: (usize, usize),
+        forward: bool,
+    ) -> impl Iterator<Item = Range<usize>> + 'a {
+        let index = self.char_index(position);
+        let row = position.1;
+        // A match on the line break the cursor stops short of is where it
+        // already is
+        let ahead = move |found: &Range<usize>| match forward {
+            true => found.start > index && self.match_position(found) != position,
+            false => found.start < index,
+        };
+        let (first, rest) = match forward {
+            true => (row..self.len(), 0..row + 1),
+            false => (0..row + 1, row..self.len()),
+        };
+
+        let search_rows = move |rows: Span| {
+            let mut matches = self.matches(search, &rows);
+            if !forward {
+                matches.reverse();
+            }
+            matches
+        };
+        let first = windows(first, forward)
+            .flat_map(search_rows)
+            .filter(move |found| ahead(found));
+        let rest = windows(rest, forward)
+            .flat_map(search_rows)
+            .filter(move |found| !ahead(found));
+
+        first.chain(rest)
+    }
+
+    /// Which match `found` is of how many there are in the buffer, counting
+    /// no further than one past [`MAX_SEARCH_COUNT`] of them.
+    pub fn search_count(&self, search: &Search, found: &Range<usize>) -> (usize, usize) {
+        let (mut index, mut total) = (0, 0);
+        let matches = windows(0..self.len(), true).flat_map(|rows| self.matches(search, &rows));
+        for other in matches.take(MAX_SEARCH_COUNT + 1) {
+            total += 1;
+            if other.start <= found.start {
+                index = total;
+            }
+        }
+
+        (index, total)
+    }
+
+    /// Every match of `search` starting within `rows`, or only the first on
+    /// each row unless `global`, each with its replacement filled in.
+    ///
+    /// Only the text of `rows` is searched, so a match may run over the line
+    /// breaks within them but not past the last.
+    pub fn substitutions(
+        &self,
+        search: &Search,
+        rows: &Span,
+        replacement: &Replacement,
+        global: bool,
+    ) -> Vec<Substitution> {
+        let chars = self.rows_chars(rows);
+        let text = self.slice_chars(chars.clone());
+
+        // The text passed over to reach each match is counted once, so the
+        // matches along long lines cost no more than the lines themselves
+        let (mut byte, mut char, mut row) = (0, chars.start, rows.start);
+        let mut substitutions: Vec<Substitution> = vec![];
+        for captures in search.regex.captures_iter(&text) {
+            // SAFETY: the whole match is always captured
+            let found = captures.get(0).unwrap();
+            let skipped = &text[byte..found.start()];
+            char += skipped.chars().count();
+            row += skipped.matches('\n').count();
+            byte = found.start();
+            if char >= chars.end {
+                break;
+            }
+
+            if !global && substitutions.last().is_some_and(|last| last.row == row) {
+                continue;
+            }
+
+            substitutions.push(Substitution {
+                chars: char..char + found.as_str().chars().count(),
+                row,
+                text: replacement.expand(&captures),
+            });
+        }
+
+        substitutions
+    }
+
+    /// Position the cursor lands at for `found`, kept on the line's last
+    /// grapheme when the match starts at its line break.
+    pub fn match_position(&self, found: &Range<usize>) -> (usize, usize) {
+        let (column, row) = self.char_position(found.start);
+        let line_len = self.line_len(Line::Index(row));
+
+        (column.min(line_len.saturating_sub(1)), row)
+    }
+
+    /// Keyword under or after the cursor on its line, as `*` searches for.
+    pub fn word_at(&self, (column, row): (usize, usize)) -> Option<String> {
+        let line = self.get(Line::Index(row))?;
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        let is_word = |i: &usize| Class::of(graphemes.get(*i).copied(), false) == Class::Word;
+
+        let start = (column..graphemes.len()).find(is_word)?;
+        let start = (0..start).rev().take_while(is_word).last().unwrap_or(start);
+        let end = (start..graphemes.len())
+            .find(|i| !is_word(i))
+            .unwrap_or(graphemes.len());
+
+        Some(graphemes[start..end].concat())
+    }
+}
+
+/// `rows` in spans of [`SEARCH_ROWS`] rows and then ever more, taken from
+/// the start or, unless `forward`, from the end.
+fn windows(mut rows: Span, forward: bool) -> impl Iterator<Item = Span> {
+    let mut size = SEARCH_ROWS;
+    std::iter::from_fn(move || {
+        if rows.is_empty() {
+            return None;
+        }
+
+        let window = match forward {
+            true => rows.start..rows.end.min(rows.start + size),
+            false => rows.start.max(rows.end.saturating_sub(size))..rows.end,
+        };
+        match forward {
+            true => rows.start = window.end,
+            false => rows.end = window.start,
+        }
+        size *= 2;
+
+        Some(window)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Part, Replacement, Search, MAX_SEARCH_COUNT};
+    use crate::{error::Result, FrameBuffer};
+    use std::ops::Range;
+
+    fn buffer() -> FrameBuffer {
+        let lines = ["one Two", "three", "two one", ""];
+
+        FrameBuffer::new(lines.map(str::to_owned).to_vec(), None, 0..10)
+    }
+
+    fn all_matches(buffer: &FrameBuffer, search: &Search) -> Vec<Range<usize>> {
+        buffer.matches(search, &(0..buffer.len()))
+    }
+
+    #[test]
+    fn smartcase() -> Result<()> {
+        let buffer = buffer();
+        assert_eq!(
+            all_matches(&buffer, &Search::new("two", true)?),
+            [4..7, 14..17]
+        );
+        let matches = all_matches(&buffer, &Search::new("Two", true)?);
+        assert_eq!((matches.len(), &matches[0]), (1, &(4..7)));
+        assert_eq!(
+            all_matches(&buffer, &Search::new("Two\\c", true)?),
+            [4..7, 14..17]
+        );
+        let matches = all_matches(&buffer, &Search::new("\\Ctwo", true)?);
+        assert_eq!((matches.len(), &matches[0]), (1, &(14..17)));
+        assert!(Search::new("(", true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn across_lines() -> Result<()> {
+        let buffer = buffer();
+        let matches = all_matches(&buffer, &Search::new("two\\nthree", true)?);
+        assert_eq!((matches.len(), &matches[0]), (1, &(4..13)));
+
+        // Empty lines match `^$` without running past the end of the buffer
+        let matches = all_matches(&buffer, &Search::new("^$", true)?);
+        assert_eq!((matches.len(), &matches[0]), (1, &(22..22)));
+        assert_eq!(buffer.match_position(&matches[0]), (0, 3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cycling() -> Result<()> {
+        let buffer = buffer();
+        let search = Search::new("o", true)?;
+        let matches = all_matches(&buffer, &search);
+        let positions: Vec<_> = matches.iter().map(|m| buffer.match_position(m)).collect();
+        assert_eq!(positions, [(0, 0), (6, 0), (2, 2), (4, 2)]);
+
+        let find = |position, forward, count| buffer.find_match(&search, position, forward, count);
+        assert_eq!(find((0, 0), true, 1), Some(matches[1].clone()));
+        assert_eq!(find((0, 0), true, 3), Some(matches[3].clone()));
+        assert_eq!(find((4, 2), true, 1), Some(matches[0].clone()));
+        assert_eq!(find((0, 0), false, 1), Some(matches[3].clone()));
+        assert_eq!(find((3, 2), false, 2), Some(matches[1].clone()));
+        // Counting past the last match goes around again
+        assert_eq!(find((0, 0), true, 6), Some(matches[2].clone()));
+        assert_eq!(buffer.search_count(&search, &matches[2]), (3, 4));
+
+        // Matching the line break keeps the cursor on the line
+        let search = Search::new("$", true)?;
+        let matches = all_matches(&buffer, &search);
+        assert_eq!(
+            buffer.find_match(&search, (6, 0), true, 1),
+            Some(matches[1].clone())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn far_matches() -> Result<()> {
+        let lines: Vec<_> = (0..1000).map(|i| format!("line {i}")).collect();
+        let buffer = FrameBuffer::new(lines, None, 0..10);
+
+        // Found beyond the rows first searched, and before the cursor once
+        // wrapped around
+        let search = Search::new("^line (99|499)$", true)?;
+        let row = |position, forward| {
+            let found = buffer.find_match(&search, position, forward, 1).unwrap();
+            buffer.match_position(&found).1
+        };
+        assert_eq!(row((0, 0), true), 99);
+        assert_eq!(row((0, 999), true), 99);
+        assert_eq!(row((0, 500), false), 499);
+        assert_eq!(row((0, 50), false), 499);
+
+        // Counts stop short of going through the whole buffer
+        let search = Search::new("line", true)?;
+        let found = buffer.find_match(&search, (0, 500), true, 1).unwrap();
+        assert_eq!(
+            buffer.search_count(&search, &found),
+            (MAX_SEARCH_COUNT + 1, MAX_SEARCH_COUNT + 1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn substitutions() -> Result<()> {
+        let buffer = buffer();
+        let search = Search::new("(t)(w)?", true)?;
+        let replacement = Replacement::parse("<\\2&\\1\\&>");
+        let substitutions = buffer.substitutions(&search, &(0..3), &replacement, false);
+        let texts: Vec<_> = substitutions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["<wTwT&>", "<tt&>", "<wtwt&>"]);
+        assert_eq!(substitutions[1].chars, 8..9);
+
+        // Every match on the rows asked for
+        let substitutions = buffer.substitutions(&search, &(1..2), &replacement, true);
+        let rows: Vec<_> = substitutions
+            .iter()
+            .map(|s| (s.row, s.chars.start))
+            .collect();
+        assert_eq!(rows, [(1, 8)]);
+        let substitutions =
+            buffer.substitutions(&Search::new("e", true)?, &(1..2), &replacement, true);
+        assert_eq!(substitutions.len(), 2);
+
+        let replacement = Replacement::parse("a\\nb\\\\");
+        assert_eq!(
+            replacement,
+            Replacement(vec![Part::Text("a\nb\\".to_owned())])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn words() -> Result<()> {
+        let buffer = buffer();
+        assert_eq!(buffer.word_at((3, 0)), Some("Two".to_owned()));
+        assert_eq!(buffer.word_at((5, 0)), Some("Two".to_owned()));
+        assert_eq!(buffer.word_at((7, 0)), None);
+
+        let search = Search::word("one", true)?;
+        assert_eq!(all_matches(&buffer, &search), [0..3, 18..21]);
+
+        Ok(())
+    }
+}
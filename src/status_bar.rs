@@ -1,5 +1,10 @@
-use crate::editor::Mode;
-use std::{cell::RefCell, fmt::Display, iter, path::PathBuf, rc::Rc};
+use crate::{
+    editor::{CommandLine, Mode, VisualMode},
+    line_ending::LineEnding,
+    search::MAX_SEARCH_COUNT,
+    FrameBuffer,
+};
+use std::{cell::RefCell, fmt::Display, path::PathBuf, rc::Rc};
 
 #[derive(Debug)]
 pub struct StatusBar {
@@ -7,43 +12,101 @@ pub struct StatusBar {
     mode: Rc<RefCell<Mode>>,
     entry: Rc<RefCell<Option<PathBuf>>>,
     position: Rc<RefCell<(usize, usize)>>,
+    line_ending: Rc<RefCell<LineEnding>>,
+    modified: Rc<RefCell<bool>>,
+    message: Rc<RefCell<Option<String>>>,
+    command_line: Rc<RefCell<CommandLine>>,
+    recording: Rc<RefCell<Option<char>>>,
+    search_count: Rc<RefCell<Option<(usize, usize)>>>,
 }
 
 impl StatusBar {
     pub fn new(
         terminal_size: Rc<RefCell<(u16, u16)>>,
         mode: Rc<RefCell<Mode>>,
-        entry: Rc<RefCell<Option<PathBuf>>>,
-        position: Rc<RefCell<(usize, usize)>>,
+        message: Rc<RefCell<Option<String>>>,
+        command_line: Rc<RefCell<CommandLine>>,
+        recording: Rc<RefCell<Option<char>>>,
+        search_count: Rc<RefCell<Option<(usize, usize)>>>,
+        buffer: &FrameBuffer,
     ) -> Self {
         Self {
             terminal_size,
             mode,
-            entry,
-            position,
+            entry: Rc::clone(&buffer.entry),
+            position: Rc::clone(&buffer.position),
+            line_ending: Rc::clone(&buffer.line_ending),
+            modified: Rc::clone(&buffer.modified),
+            message,
+            command_line,
+            recording,
+            search_count,
         }
     }
+
+    /// Points the bar at `buffer` in place of the one it was showing.
+    pub fn attach(&mut self, buffer: &FrameBuffer) {
+        self.entry = Rc::clone(&buffer.entry);
+        self.position = Rc::clone(&buffer.position);
+        self.line_ending = Rc::clone(&buffer.line_ending);
+        self.modified = Rc::clone(&buffer.modified);
+    }
 }
 
 impl Display for StatusBar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.terminal_size.borrow().0 as usize;
+        if let Some(message) = self.message.borrow().as_ref() {
+            // The lines before the last are drawn above the bar
+            let message = message.lines().last().unwrap_or_default();
+            return write!(f, "{message:<width$}");
+        }
+
+        if let Mode::Command = *self.mode.borrow() {
+            let command_line = self.command_line.borrow().to_string();
+            return write!(f, "{command_line:<width$}");
+        }
+
         let mode = match *self.mode.borrow() {
             Mode::Insert => "Insert",
             Mode::Normal => "Normal",
-            Mode::Visual => "Visual",
+            Mode::Visual(VisualMode::Charwise) => "Visual",
+            Mode::Visual(VisualMode::Linewise) => "Visual Line",
+            Mode::Visual(VisualMode::Blockwise) => "Visual Block",
+            Mode::Command => "Command",
+            Mode::Confirm => "Confirm",
+            Mode::UndoTree => "Undo Tree",
+        };
+        let mode = match *self.recording.borrow() {
+            Some(name) => format!("{mode} recording @{name}"),
+            None => mode.to_owned(),
+        };
+        let entry = match (self.entry.borrow().as_ref(), *self.modified.borrow()) {
+            (Some(entry), false) => format!("{}", entry.display()),
+            (Some(entry), true) => format!("{} [+]", entry.display()),
+            (None, false) => "[scratch]".to_owned(),
+            (None, true) => "[scratch] [+]".to_owned(),
         };
-        let entry = match self.entry.borrow().as_ref() {
-            Some(entry) => format!("{}", entry.display()),
-            None => "[scratch]".to_owned(),
+        let search_count = match *self.search_count.borrow() {
+            Some((index, total)) => {
+                let count = |count: usize| match count > MAX_SEARCH_COUNT {
+                    true => format!(">{MAX_SEARCH_COUNT}"),
+                    false => count.to_string(),
+                };
+                format!("match {} of {}    ", count(index), count(total))
+            }
+            None => String::new(),
         };
-        let position = format!("{}:{}", self.position.borrow().0, self.position.borrow().1);
+        let position = format!(
+            "{search_count}{}    {}:{}",
+            self.line_ending.borrow(),
+            self.position.borrow().0,
+            self.position.borrow().1
+        );
         let center_indent: String = {
             let lhs_length = 1 + mode.len() + 4 + entry.len();
-            let width = self.terminal_size.borrow().0 as usize;
 
-            iter::repeat(' ')
-                .take(width - lhs_length - position.len() - 1)
-                .collect()
+            " ".repeat(width.saturating_sub(lhs_length + position.len() + 1))
         };
 
         write!(f, " {mode}    {entry}{center_indent}{position} ")
@@ -1,89 +1,136 @@
-use crate::{editor::Move, error::Result, Config};
-use crossterm::{
-    cursor, event, style,
-    terminal::{self, ClearType},
-    Command, ExecutableCommand,
-};
-use std::{
-    cell::RefCell,
-    fmt::Display,
-    io::{self, Stdout},
-    rc::Rc,
+use crate::{
+    backend::{Backend, CrosstermBackend},
+    editor::Move,
+    error::Result,
+    screen::{Grid, Style},
+    Config,
 };
+use crossterm::event::Event;
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
+/// Drawing goes into a frame that is only sent to the backend on
+/// [`Terminal::flush`], as the cells that changed since the last frame.
 #[derive(Debug)]
 pub struct Terminal {
-    stdout: Stdout,
+    backend: Box<dyn Backend>,
     config: Config,
     pub size: Rc<RefCell<(u16, u16)>>,
+    suspended: bool, // Output is dropped, as while a macro plays
+    frame: Grid,     // Being drawn
+    screen: Grid,    // As last flushed to the backend
+    cursor_visible: bool,
 }
 
 impl Terminal {
     #[inline]
     pub fn new(config: Config) -> Result<Self> {
+        Self::with_backend(config, Box::new(CrosstermBackend::new()))
+    }
+
+    pub fn with_backend(config: Config, backend: Box<dyn Backend>) -> Result<Self> {
+        let size = backend.size()?;
+        let grid = Grid::new(grid_size(size), config.line_wrapping);
         let terminal = Self {
-            stdout: io::stdout(),
+            backend,
             config,
-            size: Rc::new(RefCell::new(terminal::size()?)),
+            size: Rc::new(RefCell::new(size)),
+            suspended: false,
+            frame: grid.clone(),
+            screen: grid,
+            cursor_visible: true,
         };
 
         Ok(terminal)
     }
 
     pub fn initialize(&mut self, start_position: (usize, usize)) -> Result<()> {
-        self.enable_raw_mode()?;
-        self.initialize_terminal()?;
-        self.initialize_theme()?;
+        self.backend.enter(&self.config)?;
         self.cursor_move_to(start_position)?;
 
-        Ok(())
+        self.flush()
     }
 
-    fn initialize_terminal(&mut self) -> Result<()> {
-        if self.config.alternate_screen {
-            self.execute(terminal::EnterAlternateScreen)?;
-        } else {
-            self.clear()?;
-        }
+    #[inline]
+    pub fn write<T: Display>(&mut self, data: T) -> Result<()> {
+        self.draw(data, Style::Plain)
+    }
 
-        if self.config.line_wrapping {
-            self.execute(terminal::EnableLineWrap)?;
-        }
+    /// Writes `data` in reverse video, as a selection is drawn.
+    pub fn write_highlighted<T: Display>(&mut self, data: T) -> Result<()> {
+        self.draw(data, Style::Reversed)
+    }
 
-        if self.config.mouse_capture {
-            self.execute(event::EnableMouseCapture)?;
+    fn draw<T: Display>(&mut self, data: T, style: Style) -> Result<()> {
+        if !self.suspended {
+            self.frame.write(&data.to_string(), style);
         }
 
         Ok(())
     }
 
-    fn initialize_theme(&mut self) -> Result<()> {
-        if let Some(color) = self.config.theme.foreground_color {
-            self.execute(style::SetForegroundColor(color.into()))?;
+    pub fn clear(&mut self) -> Result<()> {
+        if !self.suspended {
+            self.frame.clear();
         }
 
-        if let Some(color) = self.config.theme.background_color {
-            self.execute(style::SetBackgroundColor(color.into()))?;
-        }
+        Ok(())
+    }
 
-        if let Some(color) = self.config.theme.underline_color {
-            self.execute(style::SetUnderlineColor(color.into()))?;
-        }
+    pub fn clear_current_line(&mut self) -> Result<()> {
+        let (column, row) = self.frame.cursor();
+        self.frame.move_to((0, row));
+        self.clear_until_newline()?;
+        self.frame.move_to((column, row));
 
         Ok(())
     }
 
-    #[inline]
-    pub fn write<T: Display>(&mut self, data: T) -> Result<()> {
-        self.execute(style::Print(data))
-    }
+    pub fn clear_until_newline(&mut self) -> Result<()> {
+        if !self.suspended {
+            self.frame.clear_until_newline();
+        }
 
-    pub fn clear(&mut self) -> Result<()> {
-        self.execute(terminal::Clear(ClearType::All))
+        Ok(())
     }
 
-    pub fn clear_current_line(&mut self) -> Result<()> {
-        self.execute(terminal::Clear(ClearType::CurrentLine))
+    /// Sends the terminal what changed in the frame since the last one, all
+    /// in one write. A resize redraws the whole frame.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.suspended {
+            return Ok(());
+        }
+
+        let size = grid_size(*self.size.borrow());
+        if self.frame.size() != size {
+            self.frame.resize(size);
+            self.screen = Grid::new(size, self.config.line_wrapping);
+            self.backend.clear()?;
+        }
+
+        self.backend.show_cursor(false)?;
+        let mut drawn_to = None; // Where the backend's cursor was left
+        for ((column, row), cell) in self.frame.diff(&self.screen) {
+            // The tail of a wide grapheme is drawn along with it
+            if cell.width() == 0 {
+                continue;
+            }
+
+            if drawn_to != Some((column, row)) {
+                self.backend.move_cursor((column as u16, row as u16))?;
+            }
+            self.backend.write(&cell.symbol, cell.style)?;
+            drawn_to = Some((column + cell.width(), row));
+        }
+
+        let (column, row) = self.frame.cursor();
+        self.backend.move_cursor((column as u16, row as u16))?;
+        if self.cursor_visible {
+            self.backend.show_cursor(true)?;
+        }
+        self.backend.flush()?;
+        self.screen = self.frame.clone();
+
+        Ok(())
     }
 
     #[inline]
@@ -111,100 +158,94 @@ impl Terminal {
         Cursor::hide(self)
     }
 
-    pub fn scroll_up(&mut self, n: u16) -> Result<()> {
-        self.execute(terminal::ScrollUp(n))
+    /// Stops drawing anything until [`Self::resume`] is called.
+    pub fn suspend(&mut self) {
+        self.suspended = true;
     }
 
-    pub fn scroll_down(&mut self, n: u16) -> Result<()> {
-        self.execute(terminal::ScrollDown(n))
+    pub fn resume(&mut self) {
+        self.suspended = false;
     }
 
     #[inline]
-    pub fn size(&self) -> Result<(u16, u16)> {
-        Ok(terminal::size()?)
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
     }
 
     #[inline]
-    fn enable_raw_mode(&self) -> Result<()> {
-        terminal::enable_raw_mode()?;
-
-        Ok(())
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
     #[inline]
-    fn disable_raw_mode(&self) -> Result<()> {
-        terminal::disable_raw_mode()?;
-
-        Ok(())
+    pub fn size(&self) -> Result<(u16, u16)> {
+        self.backend.size()
     }
 
     #[inline]
-    fn execute(&mut self, command: impl Command) -> Result<()> {
-        self.stdout.execute(command)?;
-
-        Ok(())
+    pub fn read_event(&mut self) -> Result<Event> {
+        self.backend.read_event()
     }
 }
 
 impl Drop for Terminal {
     fn drop(&mut self) {
-        if self.config.alternate_screen {
-            if let Err(err) = self.execute(terminal::LeaveAlternateScreen) {
-                eprintln!("failed to leave alternate screen: {err}")
-            };
-        }
-
-        if self.config.line_wrapping {
-            if let Err(err) = self.execute(terminal::DisableLineWrap) {
-                eprintln!("failed to disable line wrapping: {err}")
-            }
-        }
-
-        if self.config.mouse_capture {
-            if let Err(err) = self.execute(event::DisableMouseCapture) {
-                eprintln!("failed to disable mouse capture: {err}")
-            }
+        if let Err(err) = self.backend.leave(&self.config) {
+            eprintln!("failed to restore the terminal: {err}")
         }
-
-        if let Err(err) = self.disable_raw_mode() {
-            eprintln!("failed to disable raw mode: {err}")
-        };
     }
 }
 
+/// Cells of the grid for a terminal `size`.
+fn grid_size((width, height): (u16, u16)) -> (usize, usize) {
+    (width as usize, height as usize)
+}
+
+/// Moves the cursor the frame is drawn from, and where the terminal's is
+/// left once it is flushed.
 pub struct Cursor;
 
 impl Cursor {
     #[inline]
     pub fn show(terminal: &mut Terminal) -> Result<()> {
-        terminal.execute(cursor::Show)
+        terminal.cursor_visible = true;
+
+        Ok(())
     }
 
     #[inline]
     pub fn hide(terminal: &mut Terminal) -> Result<()> {
-        terminal.execute(cursor::Hide)
+        terminal.cursor_visible = false;
+
+        Ok(())
     }
 
     #[inline]
     pub fn reset(terminal: &mut Terminal) -> Result<()> {
-        terminal.execute(cursor::MoveTo(0, 0))
+        Self::move_to(terminal, 0, 0)
     }
 
     #[inline]
     pub fn move_(terminal: &mut Terminal, move_: Move) -> Result<()> {
+        let (column, row) = terminal.frame.cursor();
+        let (column, row) = (column as u16, row as u16);
         match move_ {
-            Move::Left(n) => terminal.execute(cursor::MoveLeft(n)),
-            Move::Right(n) => terminal.execute(cursor::MoveRight(n)),
-            Move::Up(n) => terminal.execute(cursor::MoveUp(n)),
-            Move::Down(n) => terminal.execute(cursor::MoveDown(n)),
-            Move::NextLine(n) => terminal.execute(cursor::MoveToNextLine(n)),
-            Move::PreviousLine(n) => terminal.execute(cursor::MoveToPreviousLine(n)),
-            Move::To(column, row) => terminal.execute(cursor::MoveTo(column, row)),
+            Move::Left(n) => Self::move_to(terminal, column.saturating_sub(n), row),
+            Move::Right(n) => Self::move_to(terminal, column + n, row),
+            Move::Up(n) => Self::move_to(terminal, column, row.saturating_sub(n)),
+            Move::Down(n) => Self::move_to(terminal, column, row + n),
+            Move::NextLine(n) => Self::move_to(terminal, 0, row + n),
+            Move::PreviousLine(n) => Self::move_to(terminal, 0, row.saturating_sub(n)),
+            Move::To(column, row) => Self::move_to(terminal, column, row),
         }
     }
 
     #[inline]
     pub fn move_to(terminal: &mut Terminal, column: u16, row: u16) -> Result<()> {
-        terminal.execute(cursor::MoveTo(column, row))
+        if !terminal.suspended {
+            terminal.frame.move_to((column as usize, row as usize));
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,567 @@
+//! Text objects, selecting the text surrounding the cursor for operators
+//! such as `ciw`, `da(` and `yi"`.
+
+use crate::{
+    frame_buffer::{FrameBuffer, Line},
+    motion::Class,
+    Span,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TextObject {
+    Word,
+    BigWord,
+    Sentence,
+    Paragraph,
+    Quote(char),
+    Bracket(char, char),
+    Tag,
+}
+
+impl TextObject {
+    /// Object named by the key typed after `i` or `a`.
+    pub fn from_key(key: char) -> Option<Self> {
+        let object = match key {
+            'w' => Self::Word,
+            'W' => Self::BigWord,
+            's' => Self::Sentence,
+            'p' => Self::Paragraph,
+            '"' | '\'' | '`' => Self::Quote(key),
+            '(' | ')' | 'b' => Self::Bracket('(', ')'),
+            '[' | ']' => Self::Bracket('[', ']'),
+            '{' | '}' | 'B' => Self::Bracket('{', '}'),
+            '<' | '>' => Self::Bracket('<', '>'),
+            't' => Self::Tag,
+            _ => return None,
+        };
+
+        Some(object)
+    }
+}
+
+impl Display for TextObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::Word => "Word",
+            Self::BigWord => "WORD",
+            Self::Sentence => "Sentence",
+            Self::Paragraph => "Paragraph",
+            Self::Quote(quote) => return write!(f, "Quote `{quote}`"),
+            Self::Bracket(open, close) => return write!(f, "Bracket `{open}{close}`"),
+            Self::Tag => "Tag",
+        };
+
+        write!(f, "{message}")
+    }
+}
+
+impl FrameBuffer {
+    /// Char range of `object` at `position`, along with whether it spans
+    /// whole lines.
+    ///
+    /// The inner object excludes the surrounding blanks or delimiters that
+    /// the `around` one takes in.
+    pub fn text_object(
+        &self,
+        object: TextObject,
+        around: bool,
+        position: (usize, usize),
+    ) -> Option<(Span, bool)> {
+        match object {
+            TextObject::Word => self.word_object(position, around, false),
+            TextObject::BigWord => self.word_object(position, around, true),
+            TextObject::Sentence => self.sentence_object(position, around),
+            TextObject::Paragraph => self.paragraph_object(position.1, around),
+            TextObject::Quote(quote) => self.quote_object(position, quote, around),
+            TextObject::Bracket(open, close) => self.bracket_object(position, open, close, around),
+            TextObject::Tag => self.tag_object(position, around),
+        }
+    }
+
+    fn word_object(
+        &self,
+        (column, row): (usize, usize),
+        around: bool,
+        big: bool,
+    ) -> Option<(Span, bool)> {
+        let line = self.get(Line::Index(row))?;
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        if graphemes.is_empty() {
+            return None;
+        }
+
+        let len = graphemes.len();
+        let class = |i: usize| Class::of(Some(graphemes[i]), big);
+        let run = |i: usize| {
+            let (mut start, mut end) = (i, i + 1);
+            while start > 0 && class(start - 1) == class(i) {
+                start -= 1;
+            }
+            while end < len && class(end) == class(i) {
+                end += 1;
+            }
+
+            (start, end)
+        };
+
+        let column = column.min(len - 1);
+        let (mut start, mut end) = run(column);
+        if around {
+            // Blanks after the word are taken in, or those before it when
+            // there are none after
+            match class(column) {
+                Class::Blank if end < len => end = run(end).1,
+                Class::Blank => {}
+                _ if end < len && class(end) == Class::Blank => end = run(end).1,
+                _ if start > 0 && class(start - 1) == Class::Blank => start = run(start - 1).0,
+                _ => {}
+            }
+        }
+
+        Some((
+            self.char_index((start, row))..self.char_index((end, row)),
+            false,
+        ))
+    }
+
+    /// Rows of the run of empty, or of non-empty, lines holding `row`.
+    fn paragraph_rows(&self, row: usize) -> Span {
+        let empty = |row: usize| self.line_is_empty(Line::Index(row));
+        let (mut start, mut end) = (row, row + 1);
+        while start > 0 && empty(start - 1) == empty(row) {
+            start -= 1;
+        }
+        while end < self.len() && empty(end) == empty(row) {
+            end += 1;
+        }
+
+        start..end
+    }
+
+    fn paragraph_object(&self, row: usize, around: bool) -> Option<(Span, bool)> {
+        if row >= self.len() {
+            return None;
+        }
+
+        let mut rows = self.paragraph_rows(row);
+        if around {
+            if rows.end < self.len() {
+                rows.end = self.paragraph_rows(rows.end).end;
+            } else if rows.start > 0 {
+                rows.start = self.paragraph_rows(rows.start - 1).start;
+            }
+        }
+
+        Some((self.rows_chars(&rows), true))
+    }
+
+    /// Sentences end at `.`, `!` or `?`, optionally followed by closing
+    /// brackets or quotes, and then by a blank or the end of the paragraph.
+    fn sentence_object(&self, position: (usize, usize), around: bool) -> Option<(Span, bool)> {
+        if self.line_is_empty(Line::Index(position.1)) {
+            return self.paragraph_object(position.1, around);
+        }
+
+        let rows = self.paragraph_rows(position.1);
+        let range = self.rows_chars(&rows);
+        let chars: Vec<char> = self
+            .slice_chars(range.start..range.end - 1)
+            .chars()
+            .collect();
+        let len = chars.len();
+        let blank = |i: usize| chars[i].is_whitespace();
+        let skip_blanks = |mut i: usize| {
+            while i < len && blank(i) {
+                i += 1;
+            }
+
+            i
+        };
+
+        let mut sentences = vec![];
+        let mut start = skip_blanks(0);
+        let mut i = start;
+        while i < len {
+            if matches!(chars[i], '.' | '!' | '?') {
+                let mut end = i + 1;
+                while end < len && matches!(chars[end], ')' | ']' | '"' | '\'') {
+                    end += 1;
+                }
+
+                if end == len || blank(end) {
+                    sentences.push(start..end);
+                    start = skip_blanks(end);
+                    i = start;
+                    continue;
+                }
+            }
+
+            i += 1;
+        }
+        if start < len {
+            sentences.push(start..len);
+        }
+
+        let cursor = self.char_index(position) - range.start;
+        let sentence = sentences
+            .iter()
+            .rev()
+            .find(|sentence| sentence.start <= cursor)
+            .or_else(|| sentences.first())?;
+
+        let (mut start, mut end) = (sentence.start, sentence.end);
+        if around {
+            match skip_blanks(end) {
+                next if next > end => end = next,
+                _ => {
+                    while start > 0 && blank(start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+        }
+
+        Some((range.start + start..range.start + end, false))
+    }
+
+    /// Quotes pair up from the start of the line, skipping those escaped with
+    /// a backslash.
+    fn quote_object(
+        &self,
+        (column, row): (usize, usize),
+        quote: char,
+        around: bool,
+    ) -> Option<(Span, bool)> {
+        let line = self.get(Line::Index(row))?;
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        let is_quote = |i: usize| {
+            graphemes[i].chars().eq(std::iter::once(quote)) && (i == 0 || graphemes[i - 1] != "\\")
+        };
+        let quotes: Vec<usize> = (0..graphemes.len()).filter(|&i| is_quote(i)).collect();
+
+        let pairs = quotes.chunks_exact(2).map(|pair| (pair[0], pair[1]));
+        let (open, close) = pairs
+            .clone()
+            .find(|&(open, close)| open <= column && column <= close)
+            .or_else(|| pairs.clone().find(|&(open, _)| open > column))?;
+
+        let (mut start, mut end) = match around {
+            true => (open, close + 1),
+            false => (open + 1, close),
+        };
+        if around {
+            let blank = |i: usize| graphemes[i].trim().is_empty();
+            match end < graphemes.len() && blank(end) {
+                true => {
+                    while end < graphemes.len() && blank(end) {
+                        end += 1;
+                    }
+                }
+                false => {
+                    while start > 0 && blank(start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+        }
+
+        Some((
+            self.char_index((start, row))..self.char_index((end, row)),
+            false,
+        ))
+    }
+
+    /// The innermost `open`/`close` pair enclosing the cursor, which may be
+    /// on either bracket.
+    ///
+    /// An inner block whose brackets sit on lines of their own spans the
+    /// lines between them.
+    fn bracket_object(
+        &self,
+        position: (usize, usize),
+        open: char,
+        close: char,
+        around: bool,
+    ) -> Option<(Span, bool)> {
+        let cursor = self
+            .char_index(position)
+            .min(self.len_chars().checked_sub(1)?);
+
+        // Back from the cursor to the first bracket left open, then on to
+        // the one closing it
+        let mut start = None;
+        let mut depth = 0;
+        let before = self.chars_at(cursor + 1).reversed();
+        for (index, c) in (0..=cursor).rev().zip(before) {
+            match c {
+                c if c == close && index != cursor => depth += 1,
+                c if c == open && depth == 0 => {
+                    start = Some(index);
+                    break;
+                }
+                c if c == open => depth -= 1,
+                _ => {}
+            }
+        }
+        let start = start?;
+
+        let mut end = None;
+        let mut depth = 0;
+        for (index, c) in (start + 1..).zip(self.chars_at(start + 1)) {
+            match c {
+                c if c == close && depth == 0 => {
+                    end = Some(index);
+                    break;
+                }
+                c if c == close => depth -= 1,
+                c if c == open => depth += 1,
+                _ => {}
+            }
+        }
+        let end = end?;
+
+        if around {
+            return Some((start..end + 1, false));
+        }
+
+        let mut start = start + 1;
+        if self.char_at(start) == Some('\n') {
+            start += 1;
+        }
+
+        // Whether the closing bracket has a line to itself, but for indent
+        let indent = self
+            .chars_at(end)
+            .reversed()
+            .take_while(|c| matches!(c, ' ' | '\t'))
+            .count();
+        let line_start = end - indent;
+        match line_start.checked_sub(1).and_then(|i| self.char_at(i)) {
+            Some('\n') if line_start > start && self.char_at(start - 1) == Some('\n') => {
+                Some((start..line_start, true))
+            }
+            _ => Some((start.min(end)..end, false)),
+        }
+    }
+
+    /// The innermost pair of matching tags enclosing the cursor, which may be
+    /// on either tag.
+    ///
+    /// Tags are read outward from the cursor: those before it leave some
+    /// opened, and the first of those after it to close one of them ends the
+    /// pair.
+    fn tag_object(&self, position: (usize, usize), around: bool) -> Option<(Span, bool)> {
+        let cursor = self.char_index(position);
+
+        // A tag under the cursor belongs to the side it opens or closes
+        let under_cursor = match self.char_at(cursor) {
+            Some('<') => Some(cursor),
+            _ => {
+                let before = self.chars_at(cursor).reversed();
+                let offset = before.take_while(|c| *c != '>').position(|c| c == '<');
+                offset.map(|offset| cursor - offset - 1)
+            }
+        };
+        let pivot = match under_cursor.and_then(|start| self.tag_at(start)) {
+            Some(tag) if tag.span.end > cursor && tag.closing => tag.span.start,
+            Some(tag) if tag.span.end > cursor => tag.span.end,
+            _ => cursor,
+        };
+
+        let mut opened = vec![]; // Tags before the pivot left open, innermost first
+        let mut closed: Vec<Tag> = vec![];
+        let mut index = pivot;
+        while let Some(offset) = self.chars_at(index).reversed().position(|c| c == '<') {
+            index -= offset + 1;
+            let tag = match self.tag_at(index) {
+                Some(tag) if tag.paired && tag.span.end <= pivot => tag,
+                _ => continue,
+            };
+
+            if tag.closing {
+                closed.push(tag);
+            } else if let Some(i) = closed.iter().rposition(|closed| closed.name == tag.name) {
+                closed.truncate(i);
+            } else {
+                opened.push(tag);
+            }
+        }
+
+        let mut open: Vec<Tag> = vec![];
+        let mut index = pivot;
+        while let Some(offset) = self.chars_at(index).position(|c| c == '<') {
+            let tag = match self.tag_at(index + offset) {
+                Some(tag) => tag,
+                None => break,
+            };
+            index = tag.span.end;
+
+            if !tag.paired {
+                continue;
+            } else if !tag.closing {
+                open.push(tag);
+            } else if let Some(i) = open.iter().rposition(|open| open.name == tag.name) {
+                open.truncate(i);
+            } else if let Some(opening) = opened.iter().find(|opened| opened.name == tag.name) {
+                return match around {
+                    true => Some((opening.span.start..tag.span.end, false)),
+                    false => Some((opening.span.end..tag.span.start, false)),
+                };
+            }
+        }
+
+        None
+    }
+
+    /// The tag the `<` at `start` begins, `None` when no `>` ends it.
+    fn tag_at(&self, start: usize) -> Option<Tag> {
+        let len = self.chars_at(start + 1).position(|c| c == '>')?;
+        let inner: String = self.chars_at(start + 1).take(len).collect();
+        let closing = inner.starts_with('/');
+        let name: String = inner[closing as usize..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+            .collect();
+
+        Some(Tag {
+            span: start..start + len + 2,
+            paired: !name.is_empty() && !inner.ends_with('/'),
+            name,
+            closing,
+        })
+    }
+}
+
+struct Tag {
+    span: Span,
+    name: String,
+    closing: bool,
+    paired: bool, // Named and not self-closing, so able to pair with another
+}
+
+#[cfg(test)]
+mod test {
+    use super::TextObject;
+    use crate::{FrameBuffer, Span};
+
+    fn buffer(lines: &[&str]) -> FrameBuffer {
+        let lines = lines.iter().map(|line| line.to_string()).collect();
+
+        FrameBuffer::new(lines, None, Span { start: 0, end: 5 })
+    }
+
+    fn select(
+        buffer: &FrameBuffer,
+        object: TextObject,
+        around: bool,
+        position: (usize, usize),
+    ) -> Option<String> {
+        buffer
+            .text_object(object, around, position)
+            .map(|(chars, _)| buffer.slice_chars(chars))
+    }
+
+    #[test]
+    fn words_and_quotes() {
+        let buffer = buffer(&["say \"hi there\"  now"]);
+
+        assert_eq!(
+            select(&buffer, TextObject::Word, false, (1, 0)).unwrap(),
+            "say"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Word, true, (1, 0)).unwrap(),
+            "say "
+        );
+        assert_eq!(
+            select(&buffer, TextObject::BigWord, false, (4, 0)).unwrap(),
+            "\"hi"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Word, true, (18, 0)).unwrap(),
+            "  now"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Quote('"'), false, (7, 0)).unwrap(),
+            "hi there"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Quote('"'), true, (0, 0)).unwrap(),
+            "\"hi there\"  "
+        );
+    }
+
+    #[test]
+    fn sentences_and_paragraphs() {
+        let buffer = buffer(&["One two. Three", "four! Five", "", "Six."]);
+
+        assert_eq!(
+            select(&buffer, TextObject::Sentence, false, (10, 0)).unwrap(),
+            "Three\nfour!"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Sentence, true, (2, 0)).unwrap(),
+            "One two. "
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Sentence, true, (7, 1)).unwrap(),
+            " Five"
+        );
+        assert_eq!(
+            buffer.text_object(TextObject::Paragraph, false, (3, 1)),
+            Some((0..26, true))
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Paragraph, true, (0, 0)).unwrap(),
+            "One two. Three\nfour! Five\n\n"
+        );
+    }
+
+    #[test]
+    fn brackets_and_tags() {
+        let buffer = buffer(&[
+            "f(a, (b)) {",
+            "    x;",
+            "}",
+            "<p><b>bold</b> text</p>",
+            "<div><br/><a>x</a> y</div>",
+        ]);
+
+        assert_eq!(
+            select(&buffer, TextObject::Bracket('(', ')'), false, (3, 0)).unwrap(),
+            "a, (b)"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Bracket('(', ')'), true, (7, 0)).unwrap(),
+            "(b)"
+        );
+        assert_eq!(
+            buffer.text_object(TextObject::Bracket('{', '}'), false, (4, 1)),
+            Some((12..19, true))
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Tag, false, (8, 3)).unwrap(),
+            "bold"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Tag, true, (15, 3)).unwrap(),
+            "<p><b>bold</b> text</p>"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Tag, true, (15, 4)).unwrap(),
+            "<a>x</a>"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Tag, false, (1, 4)).unwrap(),
+            "<br/><a>x</a> y"
+        );
+        assert_eq!(
+            select(&buffer, TextObject::Tag, false, (7, 4)).unwrap(),
+            "<br/><a>x</a> y"
+        );
+        assert_eq!(buffer.text_object(TextObject::Tag, false, (0, 0)), None);
+    }
+}
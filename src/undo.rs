@@ -0,0 +1,441 @@
+use crate::error::{Result, SerdeError};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{self, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A single reversible edit to the rope, indexed in chars.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Edit {
+    Insert { index: usize, text: String },
+    Remove { index: usize, text: String },
+}
+
+impl Edit {
+    pub fn invert(&self) -> Self {
+        match self {
+            Self::Insert { index, text } => Self::Remove {
+                index: *index,
+                text: text.clone(),
+            },
+            Self::Remove { index, text } => Self::Insert {
+                index: *index,
+                text: text.clone(),
+            },
+        }
+    }
+
+    /// Folds `next` into this edit when it directly continues it, as typing
+    /// or backspacing through a line does.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (
+                Self::Insert { index, text },
+                Self::Insert {
+                    index: next,
+                    text: added,
+                },
+            ) if *index + text.chars().count() == *next => {
+                text.push_str(added);
+                true
+            }
+            (
+                Self::Remove { index, text },
+                Self::Remove {
+                    index: next,
+                    text: removed,
+                },
+            ) if *next + removed.chars().count() == *index => {
+                text.insert_str(0, removed);
+                *index = *next;
+                true
+            }
+            (
+                Self::Remove { index, text },
+                Self::Remove {
+                    index: next,
+                    text: removed,
+                },
+            ) if *index == *next => {
+                text.push_str(removed);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Edits making up one undoable step, along with the cursor positions to
+/// restore on either side of it.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ChangeSet {
+    pub edits: Vec<Edit>,
+    pub before: (usize, usize),
+    pub after: (usize, usize),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UndoNode {
+    pub seq: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub change: ChangeSet,
+    pub time: u64, // Seconds since the unix epoch
+    redo_child: Option<usize>,
+}
+
+/// Every state the buffer has been in, as a tree of change sets.
+///
+/// Node `0` is the unmodified buffer; undoing past a branch and then editing
+/// starts a new branch instead of discarding the redo history. Nodes are
+/// stored in creation order, so a node's index doubles as its sequence number.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: usize,
+    saved: usize,
+    #[serde(skip)]
+    pending: ChangeSet,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        let root = UndoNode {
+            seq: 0,
+            parent: None,
+            children: vec![],
+            change: ChangeSet::default(),
+            time: now(),
+            redo_child: None,
+        };
+
+        Self {
+            nodes: vec![root],
+            current: 0,
+            saved: 0,
+            pending: ChangeSet::default(),
+        }
+    }
+
+    #[inline]
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    #[inline]
+    pub fn nodes(&self) -> &[UndoNode] {
+        &self.nodes
+    }
+
+    /// Whether the current state differs from the last saved one.
+    pub fn is_modified(&self) -> bool {
+        self.current != self.saved || !self.pending.edits.is_empty()
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current;
+    }
+
+    /// Adds `edit` to the pending change set, which was entered with the
+    /// cursor at `position`.
+    pub fn record(&mut self, edit: Edit, position: (usize, usize)) {
+        if self.pending.edits.is_empty() {
+            self.pending.before = position;
+        }
+
+        let merged = match self.pending.edits.last_mut() {
+            Some(last) => last.merge(&edit),
+            None => false,
+        };
+
+        if !merged {
+            self.pending.edits.push(edit);
+        }
+    }
+
+    /// Closes the pending change set as a child of the current node, leaving
+    /// the cursor at `position`. Returns whether there was anything to commit.
+    pub fn commit(&mut self, position: (usize, usize)) -> bool {
+        if self.pending.edits.is_empty() {
+            return false;
+        }
+
+        let mut change = std::mem::take(&mut self.pending);
+        change.after = position;
+
+        let seq = self.nodes.len();
+        self.nodes.push(UndoNode {
+            seq,
+            parent: Some(self.current),
+            children: vec![],
+            change,
+            time: now(),
+            redo_child: None,
+        });
+        self.nodes[self.current].children.push(seq);
+        self.nodes[self.current].redo_child = Some(seq);
+        self.current = seq;
+
+        true
+    }
+
+    /// Joins the nodes committed since the tree held `len` of them into a
+    /// single node, so that a command made up of many is undone in one step.
+    ///
+    /// Nothing is joined unless they were committed one after the other,
+    /// with none of them saved.
+    pub fn join_since(&mut self, len: usize) {
+        let linear = self.current + 1 == self.nodes.len()
+            && (len + 1..self.nodes.len()).all(|seq| self.nodes[seq].parent == Some(seq - 1));
+        if self.nodes.len() <= len + 1 || !linear || self.saved > len {
+            return;
+        }
+
+        let joined = self.nodes.split_off(len + 1);
+        let node = &mut self.nodes[len];
+        for other in joined {
+            node.change.edits.extend(other.change.edits);
+            node.change.after = other.change.after;
+        }
+        node.children.clear();
+        node.redo_child = None;
+        self.current = len;
+    }
+
+    /// Edits reverting the current node, and the cursor to restore.
+    pub fn undo(&mut self) -> Option<(Vec<Edit>, (usize, usize))> {
+        let node = &self.nodes[self.current];
+        let parent = node.parent?;
+        let edits = node.change.edits.iter().rev().map(Edit::invert).collect();
+        let position = node.change.before;
+
+        self.nodes[parent].redo_child = Some(self.current);
+        self.current = parent;
+
+        Some((edits, position))
+    }
+
+    /// Edits reapplying the most recently undone child, and the cursor to
+    /// restore.
+    pub fn redo(&mut self) -> Option<(Vec<Edit>, (usize, usize))> {
+        let child = self.nodes[self.current].redo_child?;
+        let change = &self.nodes[child].change;
+        let redo = (change.edits.clone(), change.after);
+        self.current = child;
+
+        Some(redo)
+    }
+
+    /// Edits taking the buffer from the current state to the state after
+    /// node `seq`, through their closest common ancestor.
+    pub fn jump(&mut self, seq: usize) -> Option<(Vec<Edit>, (usize, usize))> {
+        if seq >= self.nodes.len() {
+            return None;
+        }
+
+        let target_path = self.ancestors(seq);
+        let mut edits = vec![];
+        let mut position = self.nodes[self.current].change.after;
+        while !target_path.contains(&self.current) {
+            let (undone, before) = self.undo()?;
+            edits.extend(undone);
+            position = before;
+        }
+
+        let common = target_path.iter().position(|&i| i == self.current)?;
+        for &node in target_path[..common].iter().rev() {
+            self.nodes[self.current].redo_child = Some(node);
+            let (redone, after) = self.redo()?;
+            edits.extend(redone);
+            position = after;
+        }
+
+        Some((edits, position))
+    }
+
+    /// `seq` followed by each of its ancestors up to the root.
+    fn ancestors(&self, seq: usize) -> Vec<usize> {
+        let mut path = vec![seq];
+        while let Some(parent) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+
+        path
+    }
+}
+
+/// Undo history persisted for a file, which only applies while the file's
+/// contents still hash to `hash`.
+#[derive(Debug, Deserialize, Serialize)]
+struct UndoFile<T> {
+    hash: u64,
+    tree: T,
+}
+
+impl UndoTree {
+    /// Undo file for `entry` within `undo_dir`, named after the entry's
+    /// absolute path with separators replaced by `%`.
+    pub fn file_path(undo_dir: &Path, entry: &Path) -> Option<PathBuf> {
+        let absolute = fs::canonicalize(entry).ok()?;
+        let name = absolute
+            .to_string_lossy()
+            .replace(path::MAIN_SEPARATOR, "%");
+
+        Some(undo_dir.join(format!("{name}.ron")))
+    }
+
+    /// Persists the tree for contents hashing to `hash`, which must be the
+    /// state the tree is currently at.
+    pub fn save(&self, path: &Path, hash: u64) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = UndoFile { hash, tree: self };
+        let data = match ron::to_string(&file) {
+            Ok(data) => data,
+            Err(err) => return Err(SerdeError::Serialize(err.to_string()).into()),
+        };
+        let temp_path = path.with_extension("ron.tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(temp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Loads the tree persisted at `path`, ignoring it when it is missing,
+    /// malformed or was saved for contents other than those hashing to
+    /// `hash`.
+    pub fn load(path: &Path, hash: u64) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        let file: UndoFile<Self> = ron::from_str(&data).ok()?;
+        if file.hash != hash || !file.tree.is_consistent() {
+            return None;
+        }
+
+        let mut tree = file.tree;
+        tree.saved = tree.current;
+
+        Some(tree)
+    }
+
+    /// Whether every link within the tree points at an existing node.
+    fn is_consistent(&self) -> bool {
+        let len = self.nodes.len();
+        let linked = |seq: &usize| *seq < len;
+
+        len > 0
+            && linked(&self.current)
+            && self.nodes.iter().enumerate().all(|(i, node)| {
+                node.seq == i
+                    && node.parent.map_or(i == 0, |parent| parent < i)
+                    && node.children.iter().all(linked)
+                    && node.redo_child.iter().all(linked)
+            })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Edit, UndoTree};
+
+    fn insert(index: usize, text: &str) -> Edit {
+        Edit::Insert {
+            index,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn merges_typing() {
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "a"), (0, 0));
+        tree.record(insert(1, "b"), (1, 0));
+        tree.commit((2, 0));
+
+        let (edits, position) = tree.undo().unwrap();
+        assert_eq!(
+            edits,
+            vec![Edit::Remove {
+                index: 0,
+                text: "ab".to_owned()
+            }]
+        );
+        assert_eq!(position, (0, 0));
+        assert!(tree.undo().is_none());
+    }
+
+    #[test]
+    fn joins_commands() {
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "a"), (0, 0));
+        tree.commit((1, 0));
+        for (i, text) in ["b", "c"].into_iter().enumerate() {
+            tree.record(insert(i + 1, text), (i + 1, 0));
+            tree.commit((i + 2, 0));
+        }
+        tree.join_since(2);
+
+        assert_eq!(tree.nodes().len(), 3);
+        let (edits, position) = tree.undo().unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!(position, (1, 0));
+        assert_eq!(tree.current(), 1);
+    }
+
+    #[test]
+    fn persisted_history() {
+        let path = std::env::temp_dir().join("cherry_persisted_history.ron");
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "a"), (0, 0));
+        tree.commit((1, 0));
+        tree.save(&path, 42).unwrap();
+
+        assert!(UndoTree::load(&path, 7).is_none());
+        let mut loaded = UndoTree::load(&path, 42).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.current(), 1);
+        assert!(!loaded.is_modified());
+        assert!(loaded.undo().is_some());
+    }
+
+    #[test]
+    fn jump_across_branches() {
+        let mut tree = UndoTree::new();
+        tree.record(insert(0, "a"), (0, 0));
+        tree.commit((1, 0));
+        tree.undo();
+        tree.record(insert(0, "b"), (0, 0));
+        tree.commit((1, 0));
+
+        let (edits, _) = tree.jump(1).unwrap();
+        assert_eq!(
+            edits,
+            vec![
+                Edit::Remove {
+                    index: 0,
+                    text: "b".to_owned()
+                },
+                insert(0, "a")
+            ]
+        );
+        assert_eq!(tree.current(), 1);
+        assert_eq!(tree.redo().map(|(edits, _)| edits), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! Conversions between the three ways a position within a line can be
+//! expressed: grapheme index (the cursor column), byte offset (the storage
+//! index) and display column (terminal cells).
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Number of grapheme clusters in `line`.
+pub fn grapheme_len(line: &str) -> usize {
+    line.graphemes(true).count()
+}
+
+/// Byte offset at which grapheme `index` starts, clamped to the line length.
+pub fn grapheme_to_byte(line: &str, index: usize) -> usize {
+    match line.grapheme_indices(true).nth(index) {
+        Some((byte, _)) => byte,
+        None => line.len(),
+    }
+}
+
+/// Grapheme index of the cluster containing `byte`.
+pub fn byte_to_grapheme(line: &str, byte: usize) -> usize {
+    line.grapheme_indices(true)
+        .take_while(|(offset, grapheme)| offset + grapheme.len() <= byte)
+        .count()
+}
+
+/// Grapheme at `index`, if any.
+pub fn grapheme_at(line: &str, index: usize) -> Option<&str> {
+    line.graphemes(true).nth(index)
+}
+
+/// Terminal cells occupied by `grapheme` when drawn at display `column`.
+///
+/// Tabs extend to the next multiple of `tab_width`, East Asian wide
+/// characters take two cells and zero-width clusters take none.
+pub fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    match grapheme {
+        "\t" => tab_width - column % tab_width,
+        grapheme => grapheme.width(),
+    }
+}
+
+/// Display column at which grapheme `index` starts.
+pub fn grapheme_to_display(line: &str, index: usize, tab_width: usize) -> usize {
+    line.graphemes(true)
+        .take(index)
+        .fold(0, |column, grapheme| {
+            column + grapheme_width(grapheme, column, tab_width)
+        })
+}
+
+/// Grapheme index of the cluster covering display `column`.
+///
+/// Columns past the end of the line map past the last grapheme.
+pub fn display_to_grapheme(line: &str, column: usize, tab_width: usize) -> usize {
+    let mut display = 0;
+    for (i, grapheme) in line.graphemes(true).enumerate() {
+        display += grapheme_width(grapheme, display, tab_width);
+        if display > column {
+            return i;
+        }
+    }
+
+    grapheme_len(line) + (column - display)
+}
+
+/// Text as drawn on screen from grapheme `index` onwards, with tabs
+/// expanded to spaces so the terminal never applies its own tab stops.
+pub fn expand_tabs_from(line: &str, index: usize, tab_width: usize) -> String {
+    let mut column = grapheme_to_display(line, index, tab_width);
+    let mut expanded = String::with_capacity(line.len());
+    for grapheme in line.graphemes(true).skip(index) {
+        let width = grapheme_width(grapheme, column, tab_width);
+        match grapheme {
+            "\t" => expanded.push_str(&" ".repeat(width)),
+            grapheme => expanded.push_str(grapheme),
+        }
+
+        column += width;
+    }
+
+    expanded
+}
+
+#[inline]
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    expand_tabs_from(line, 0, tab_width)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grapheme_byte_round_trip() {
+        let line = "añ🦀e\u{301}x";
+
+        assert_eq!(grapheme_len(line), 5);
+        assert_eq!(grapheme_to_byte(line, 2), 3);
+        assert_eq!(grapheme_to_byte(line, 4), 10);
+        assert_eq!(grapheme_to_byte(line, 9), line.len());
+        assert_eq!(byte_to_grapheme(line, 3), 2);
+        assert_eq!(byte_to_grapheme(line, 10), 4);
+    }
+
+    #[test]
+    fn display_columns() {
+        let line = "a界b";
+
+        assert_eq!(grapheme_to_display(line, 2, 4), 3);
+        assert_eq!(display_to_grapheme(line, 1, 4), 1);
+        assert_eq!(display_to_grapheme(line, 2, 4), 1);
+        assert_eq!(display_to_grapheme(line, 3, 4), 2);
+        assert_eq!(display_to_grapheme(line, 6, 4), 5);
+    }
+
+    #[test]
+    fn tab_stops() {
+        let line = "a\tb\u{200b}\tc";
+
+        assert_eq!(grapheme_to_display(line, 2, 4), 4);
+        assert_eq!(grapheme_to_display(line, 4, 4), 5);
+        assert_eq!(grapheme_to_display(line, 5, 4), 8);
+        assert_eq!(display_to_grapheme(line, 3, 4), 1);
+        assert_eq!(display_to_grapheme(line, 6, 4), 4);
+        assert_eq!(expand_tabs(line, 4), "a   b\u{200b}   c");
+        assert_eq!(expand_tabs_from(line, 1, 8), "       b\u{200b}       c");
+    }
+}
@@ -1,3 +1,54 @@
+use std::{
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
 pub fn newline<'a>() -> &'a str {
     "\r\n"
 }
+
+/// Expands a leading `~` to the user's home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+/// 64-bit FNV-1a over everything written to it, which unlike
+/// `DefaultHasher` is stable across runs and toolchains.
+#[derive(Debug, Clone, Copy)]
+pub struct ContentHash(u64);
+
+impl ContentHash {
+    pub fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    #[inline]
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for ContentHash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for ContentHash {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
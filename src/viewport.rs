@@ -0,0 +1,125 @@
+//! Rows of a [`FrameBuffer`] in view, which follow the cursor while keeping
+//! `scrolloff` rows of context above and below it where there are any.
+//!
+//! The end of [`FrameBuffer::viewable_rows`] is the last row in view, rather
+//! than one past it.
+
+use crate::frame_buffer::FrameBuffer;
+
+impl FrameBuffer {
+    /// Number of rows in view.
+    pub fn view_height(&self) -> usize {
+        self.viewable_rows.end - self.viewable_rows.start + 1
+    }
+
+    /// Rows of context kept around the cursor, which can be at most half of
+    /// the view.
+    pub fn scroll_context(&self, scroll_off: usize) -> usize {
+        scroll_off.min((self.view_height() - 1) / 2)
+    }
+
+    /// Shows `top` as the first row in view. The view stops short of
+    /// scrolling the last line any closer to the top than its context.
+    pub fn scroll_to(&mut self, top: usize, scroll_off: usize) {
+        let last = self.len().saturating_sub(1);
+        let top = top.min(last.saturating_sub(self.scroll_context(scroll_off)));
+        let height = self.view_height();
+
+        self.viewable_rows = top..top + height - 1;
+    }
+
+    /// Scrolls the view `rows` down, or up when negative.
+    pub fn scroll_by(&mut self, rows: isize, scroll_off: usize) {
+        let top = self.viewable_rows.start as isize + rows;
+        self.scroll_to(top.max(0) as usize, scroll_off);
+    }
+
+    /// Scrolls just far enough to show `row` with its context around it.
+    pub fn scroll_to_row(&mut self, row: usize, scroll_off: usize) {
+        let context = self.scroll_context(scroll_off);
+        let last = self.len().saturating_sub(1);
+        let below = context.min(last.saturating_sub(row));
+
+        let top = match row {
+            row if row < self.viewable_rows.start + context => row.saturating_sub(context),
+            row if row + below > self.viewable_rows.end => row + below + 1 - self.view_height(),
+            _ => return,
+        };
+        self.scroll_to(top, scroll_off);
+    }
+
+    /// Row nearest `row` that is in view with its context around it, where
+    /// the cursor moves to when scrolling would leave it behind.
+    pub fn row_in_view(&self, row: usize, scroll_off: usize) -> usize {
+        let context = self.scroll_context(scroll_off);
+        let last = self.len().saturating_sub(1);
+
+        let min = match self.viewable_rows.start {
+            0 => 0,
+            top => (top + context).min(last),
+        };
+        let max = match self.viewable_rows.end >= last {
+            true => last,
+            false => self.viewable_rows.end - context,
+        };
+
+        row.clamp(min, max.max(min))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::FrameBuffer;
+
+    fn buffer() -> FrameBuffer {
+        let lines = (0..20).map(|i| format!("line {i}")).collect();
+
+        // Five rows in view
+        FrameBuffer::new(lines, None, 0..4)
+    }
+
+    #[test]
+    fn follows_rows() {
+        let mut buffer = buffer();
+        buffer.scroll_to_row(3, 1);
+        assert_eq!(buffer.viewable_rows, 0..4);
+
+        // Down far enough to show a row of context below
+        buffer.scroll_to_row(4, 1);
+        assert_eq!(buffer.viewable_rows, 1..5);
+        buffer.scroll_to_row(10, 1);
+        assert_eq!(buffer.viewable_rows, 7..11);
+
+        // The last line has nothing below it
+        buffer.scroll_to_row(19, 1);
+        assert_eq!(buffer.viewable_rows, 15..19);
+
+        // Context is at most half the view
+        buffer.scroll_to_row(10, 9);
+        assert_eq!(buffer.viewable_rows, 8..12);
+        buffer.scroll_to_row(0, 9);
+        assert_eq!(buffer.viewable_rows, 0..4);
+    }
+
+    #[test]
+    fn scrolls() {
+        let mut buffer = buffer();
+        buffer.scroll_by(-3, 0);
+        assert_eq!(buffer.viewable_rows, 0..4);
+
+        // As far as the last line at the top, less its context
+        buffer.scroll_by(30, 0);
+        assert_eq!(buffer.viewable_rows, 19..23);
+        buffer.scroll_by(30, 2);
+        assert_eq!(buffer.viewable_rows, 17..21);
+
+        // The cursor is kept in view with its context
+        assert_eq!(buffer.row_in_view(3, 2), 19);
+        buffer.scroll_to(5, 1);
+        assert_eq!(buffer.row_in_view(3, 1), 6);
+        assert_eq!(buffer.row_in_view(7, 1), 7);
+        assert_eq!(buffer.row_in_view(12, 1), 8);
+        buffer.scroll_to(0, 1);
+        assert_eq!(buffer.row_in_view(0, 1), 0);
+    }
+}
@@ -0,0 +1,230 @@
+use cherry::{
+    backend::MemoryBackend, Config, Editor, FrameBuffer, History, Message, Style, Terminal,
+};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+const WIDTH: u16 = 30;
+const HEIGHT: u16 = 6;
+
+fn editor(lines: &[&str]) -> (Editor, MemoryBackend) {
+    let backend = MemoryBackend::new(WIDTH, HEIGHT);
+    let terminal = Terminal::with_backend(Config::default(), Box::new(backend.clone())).unwrap();
+    let lines = lines.iter().map(|line| line.to_string()).collect();
+    let buffer = FrameBuffer::new(lines, None, 0..HEIGHT as usize);
+
+    let mut editor = Editor::new(terminal, buffer);
+    editor.initialize().unwrap();
+
+    (editor, backend)
+}
+
+/// Types `keys`, where `\x1b` stands for `Esc` and `\n` for `Enter`.
+fn type_keys(editor: &mut Editor, keys: &str) {
+    for key in keys.chars() {
+        let code = match key {
+            '\x1b' => KeyCode::Esc,
+            '\n' => KeyCode::Enter,
+            key => KeyCode::Char(key),
+        };
+        press(editor, code, KeyModifiers::NONE);
+    }
+}
+
+fn press(editor: &mut Editor, code: KeyCode, modifiers: KeyModifiers) {
+    let event = Event::Key(KeyEvent::new(code, modifiers));
+    editor.handle_event(&event).unwrap();
+}
+
+#[test]
+fn draws_the_buffer() {
+    let (_editor, backend) = editor(&["one", "two"]);
+    let lines = backend.lines();
+
+    assert_eq!(lines[0], "   1 one");
+    assert_eq!(lines[1], "   2 two");
+    assert_eq!(lines[2], "   3");
+    assert_eq!(backend.cursor(), (5, 0));
+}
+
+#[test]
+fn redraws_edits() {
+    let (mut editor, backend) = editor(&["one", "two", "three"]);
+    type_keys(&mut editor, "jdd");
+    assert_eq!(backend.lines()[1], "   2 three");
+
+    type_keys(&mut editor, "Ahree\x1b");
+    assert_eq!(backend.lines()[1], "   2 threehree");
+    assert_eq!(backend.cursor(), (14, 1));
+
+    type_keys(&mut editor, "u");
+    assert_eq!(backend.lines()[1], "   2 three");
+}
+
+#[test]
+fn operates_over_paragraphs() {
+    // From within the indent a paragraph motion covers whole lines, and from
+    // further along it stops at the end of the line before its target
+    let cases: [(&[&str], &str, &[&str]); 4] = [
+        (&["a", "b", "", "c"], "d}", &["   1", "   2 c", "   3"]),
+        (
+            &["a", "b", "", "c"],
+            "c}x\x1b",
+            &["   1 x", "   2", "   3 c"],
+        ),
+        (&["a b", "c", "", "d"], "wd}", &["   1 a", "   2", "   3 d"]),
+        (&["one", "two"], "dw", &["   1", "   2 two", "   3"]),
+    ];
+
+    for (lines, keys, expected) in cases {
+        let (mut editor, backend) = editor(lines);
+        type_keys(&mut editor, keys);
+        assert_eq!(&backend.lines()[..expected.len()], expected, "{keys}");
+    }
+}
+
+#[test]
+fn deletes_marks_with_their_lines() {
+    let (mut editor, backend) = editor(&["one", "two", "three"]);
+    type_keys(&mut editor, "jmaggmb'a");
+    assert_eq!(backend.cursor(), (5, 1));
+
+    type_keys(&mut editor, "ddgg'a");
+    assert!(backend.lines()[HEIGHT as usize - 1].starts_with("E20"));
+    type_keys(&mut editor, "j'b");
+    assert_eq!(backend.cursor(), (5, 0));
+}
+
+#[test]
+fn scrolls_with_the_cursor() {
+    let lines: Vec<_> = (1..=20).map(|i| format!("line {i}")).collect();
+    let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+    let (mut editor, backend) = editor(&lines);
+
+    press(&mut editor, KeyCode::Char('e'), KeyModifiers::CONTROL);
+    assert_eq!(backend.lines()[0], "   2 line 2");
+    assert_eq!(backend.cursor(), (5, 0));
+
+    type_keys(&mut editor, "G");
+    let lines = backend.lines();
+    assert_eq!(lines[HEIGHT as usize - 2], "  20 line 20");
+    assert_eq!(backend.cursor(), (5, HEIGHT - 2));
+
+    type_keys(&mut editor, "zt");
+    assert_eq!(backend.lines()[0], "  20 line 20");
+    assert_eq!(backend.lines()[1], "  21");
+}
+
+#[test]
+fn highlights_selections() {
+    let (mut editor, backend) = editor(&["one two"]);
+    type_keys(&mut editor, "wve");
+
+    let styles: Vec<_> = (8..13)
+        .map(|column| backend.style_at((column, 0)))
+        .collect();
+    assert_eq!(
+        styles,
+        [
+            Some(Style::Plain),
+            Some(Style::Reversed),
+            Some(Style::Reversed),
+            Some(Style::Reversed),
+            Some(Style::Plain)
+        ]
+    );
+
+    type_keys(&mut editor, "\x1b");
+    assert_eq!(backend.style_at((10, 0)), Some(Style::Plain));
+}
+
+#[test]
+fn appends_to_blocks() {
+    // After `$` a block reaches the end of each line, however long, until
+    // the cursor moves along a line again
+    let cases: [(&str, &str, &[&str]); 3] = [
+        ("", "jj$A X\x1b", &["   1 abcd X", "   2 a X", "   3 abc X"]),
+        ("", "$jjA X\x1b", &["   1 abcd X", "   2 a X", "   3 abc X"]),
+        (
+            "lll",
+            "jj$hA X\x1b",
+            &["   1 abcd X", "   2 a    X", "   3 abc  X"],
+        ),
+    ];
+
+    for (before, keys, expected) in cases {
+        let (mut editor, backend) = editor(&["abcd", "a", "abc"]);
+        type_keys(&mut editor, before);
+        press(&mut editor, KeyCode::Char('v'), KeyModifiers::CONTROL);
+        type_keys(&mut editor, keys);
+        assert_eq!(&backend.lines()[..expected.len()], expected, "{keys}");
+    }
+}
+
+#[test]
+fn resizes() {
+    let (mut editor, backend) = editor(&["one", "two", "three"]);
+    backend.resize(WIDTH, 3);
+    editor.handle_event(&Event::Resize(WIDTH, 3)).unwrap();
+
+    let lines = backend.lines();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "   1 one");
+    assert_eq!(lines[1], "   2 two");
+}
+
+#[test]
+fn replays_history() {
+    let (mut recorded, recorded_screen) = editor(&["one", "two", "three"]);
+    type_keys(&mut recorded, "jddAfour\x1bggx");
+
+    let path = std::env::temp_dir().join(format!("cherry-history-{}.ron", std::process::id()));
+    recorded.history().save(&path).unwrap();
+    let history = History::load(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(history.entries.len(), 12);
+
+    // Each event is replayed once a key is pressed, so the last is left
+    let (mut replayed, replayed_screen) = editor(&["one", "two", "three"]);
+    for _ in 0..11 {
+        replayed_screen.push_event(Event::Key(KeyEvent::new(
+            KeyCode::Char(' '),
+            KeyModifiers::NONE,
+        )));
+    }
+    replayed_screen.push_event(Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+    assert!(matches!(replayed.replay(&history), Ok(Message::Continue)));
+
+    let lines = replayed_screen.lines();
+    assert_eq!(lines[0], "   1 one");
+    assert_eq!(lines[1], "   2 threefour");
+    assert_ne!(lines, recorded_screen.lines());
+    type_keys(&mut replayed, "x");
+    assert_eq!(replayed_screen.lines(), recorded_screen.lines());
+}
+
+#[test]
+fn switches_buffers() {
+    let (mut editor, backend) = editor(&["one"]);
+    let path = std::env::temp_dir().join(format!("cherry-buffer-{}.txt", std::process::id()));
+    type_keys(&mut editor, &format!(":e {}\n", path.display()));
+    assert_eq!(backend.lines()[0], "   1");
+
+    type_keys(&mut editor, "itwo\x1b:bp\n");
+    assert_eq!(backend.lines()[0], "   1 one");
+
+    type_keys(&mut editor, ":ls\n");
+    let lines = backend.lines();
+    assert!(lines[HEIGHT as usize - 2].starts_with("  1 %a   \"[No Name]\""));
+    assert!(lines[HEIGHT as usize - 1].starts_with("  2 #h + \"/"));
+
+    // Changes to a hidden buffer are kept until it is closed with `!`
+    type_keys(&mut editor, ":bn\n");
+    assert_eq!(backend.lines()[0], "   1 two");
+    type_keys(&mut editor, ":bd\n");
+    assert!(backend.lines()[HEIGHT as usize - 1].starts_with("E89"));
+    type_keys(&mut editor, ":bd!\n");
+    assert_eq!(backend.lines()[0], "   1 one");
+
+    type_keys(&mut editor, ":b 2\n");
+    assert!(backend.lines()[HEIGHT as usize - 1].starts_with("E86"));
+}
@@ -0,0 +1,89 @@
+use cherry::{
+    backend::MemoryBackend, error::Error, headless, Config, Editor, FrameBuffer, Terminal,
+};
+use std::{env, fs, path::PathBuf};
+
+fn editor(name: &str, text: &str) -> (Editor, PathBuf) {
+    let path = env::temp_dir().join(format!("cherry-headless-{}-{name}", std::process::id()));
+    fs::write(&path, text).unwrap();
+
+    let backend = MemoryBackend::new(80, 24);
+    let terminal = Terminal::with_backend(Config::default(), Box::new(backend)).unwrap();
+    let buffer = FrameBuffer::try_from_path(path.clone(), 0..24).unwrap();
+
+    (Editor::new(terminal, buffer), path)
+}
+
+#[test]
+fn writes_edits() {
+    let (mut editor, path) = editor("edits", "one two\nthree\n");
+    let commands = [":%s/t/T/g".to_owned()];
+    headless::run(&mut editor, "dwA!<Esc>j.", &commands).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "Two!\nThree!\n");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn writes_line_endings() {
+    let (mut editor, path) = editor("line-endings", "one\ntwo\n");
+    let commands = ["set ff=dos".to_owned(), "x".to_owned()];
+    headless::run(&mut editor, "", &commands).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\r\ntwo\r\n");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn fails_on_errors() {
+    let (mut editor, path) = editor("errors", "one\n");
+    let result = headless::run(&mut editor, "x:nonsense\n", &[]);
+    assert!(matches!(result, Err(Error::Command(_))));
+
+    // Quitting leaves the file as it was
+    let commands = ["q!".to_owned()];
+    headless::run(&mut editor, "x", &commands).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\n");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn writes_backups_when_set() {
+    let (mut editor, path) = editor("backup", "one\n");
+    let mut backup = path.clone().into_os_string();
+    backup.push("~");
+    let commands = [
+        "set backup".to_owned(),
+        "s/one/two/".to_owned(),
+        "w".to_owned(),
+    ];
+    headless::run(&mut editor, "", &commands).unwrap();
+    assert_eq!(fs::read_to_string(&backup).unwrap(), "one\n");
+    fs::remove_file(&backup).unwrap();
+
+    let commands = ["set invbackup".to_owned(), "s/two/three/".to_owned()];
+    headless::run(&mut editor, "", &commands).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "three\n");
+    assert!(fs::metadata(&backup).is_err());
+
+    let commands = ["set ts!".to_owned()];
+    let result = headless::run(&mut editor, "", &commands);
+    assert!(matches!(result, Err(Error::Command(_))));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn overwrites_other_files_with_bang() {
+    let (_, other) = editor("overwrite-other", "two\n");
+    let (mut editor, path) = editor("overwrite", "one\n");
+    let commands = [format!("w {}", other.display())];
+    let result = headless::run(&mut editor, "", &commands);
+    assert!(matches!(result, Err(Error::Command(_))));
+    assert_eq!(fs::read_to_string(&other).unwrap(), "two\n");
+
+    let commands = [format!("w! {}", other.display())];
+    headless::run(&mut editor, "", &commands).unwrap();
+    assert_eq!(fs::read_to_string(&other).unwrap(), "one\n");
+    fs::remove_file(path).unwrap();
+    fs::remove_file(other).unwrap();
+}